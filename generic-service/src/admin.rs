@@ -0,0 +1,569 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::routing::get;
+use axum::Router;
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{mpsc, AcquireError, OwnedSemaphorePermit, Semaphore};
+use tonic::{Request, Response, Status};
+
+use crate::service_stubs::admin_server::Admin;
+use crate::service_stubs::{ConfigDocument, ConfigPatch, FaultSettings, GetConfigRequest};
+use crate::{ServiceConfig, ServiceConfigFromJSON};
+
+/// How long to wait for an editor to finish writing before reloading the config file
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Faults injected at runtime, applied on top of the configured behaviour
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    pub latency_multiplier: f64,
+    pub error_rate: f64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Faults {
+            latency_multiplier: 1.0,
+            error_rate: 0.0,
+        }
+    }
+}
+
+impl Faults {
+    pub fn checked(self) -> Result<Self, String> {
+        if self.latency_multiplier < 0.0 || !self.latency_multiplier.is_finite() {
+            return Err("latency_multiplier must be a non-negative number".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.error_rate) {
+            return Err("error_rate must be within [0, 1]".to_string());
+        }
+        Ok(self)
+    }
+
+    /// Both sets of faults at once: latencies multiply, errors are independent chances
+    pub fn and(self, other: Faults) -> Faults {
+        Faults {
+            latency_multiplier: self.latency_multiplier * other.latency_multiplier,
+            error_rate: 1.0 - (1.0 - self.error_rate) * (1.0 - other.error_rate),
+        }
+    }
+}
+
+/// Worker pool limiting the requests a service handles at once; the rest queue for a permit
+#[derive(Clone)]
+pub struct Workers {
+    semaphore: Arc<Semaphore>,
+    /// Permits still to be retired after the limit was lowered below the requests in flight
+    retiring: Arc<AtomicUsize>,
+}
+
+impl Workers {
+    fn new(limit: usize) -> Self {
+        Workers {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            retiring: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Wait for a free worker
+    pub async fn acquire(&self) -> Result<WorkerPermit, AcquireError> {
+        let permit = self.semaphore.clone().acquire_owned().await?;
+        Ok(WorkerPermit {
+            permit: Some(permit),
+            retiring: self.retiring.clone(),
+        })
+    }
+
+    /// Take back up to `count` retiring permits, returning how many were still owed
+    fn cancel_retiring(&self, count: usize) -> usize {
+        let owed = self
+            .retiring
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |owed| {
+                Some(owed - owed.min(count))
+            })
+            .unwrap();
+        owed.min(count)
+    }
+}
+
+/// A worker held by a request in flight
+pub struct WorkerPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    retiring: Arc<AtomicUsize>,
+}
+
+impl Drop for WorkerPermit {
+    /// Retire the permit instead of handing it to a queued request while the pool is
+    /// above its limit
+    fn drop(&mut self) {
+        let retire = self
+            .retiring
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |owed| {
+                owed.checked_sub(1)
+            })
+            .is_ok();
+        if let (true, Some(permit)) = (retire, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+/// Worker pool along with the limit it was sized for
+struct WorkerPool {
+    workers: Workers,
+    limit: usize,
+}
+
+/// Behaviour of a service that can change while it runs: its own section of the config,
+/// the worker pool limiting its concurrency and injected faults
+pub struct Runtime {
+    service_name: String,
+    /// Index of this replica, when the service has several
+    replica: Option<usize>,
+    /// "service.method" of every method in the deployment, which calls may target
+    deployment_methods: HashSet<String>,
+    /// Own section of the config document, as last applied
+    section: Mutex<Value>,
+    config: RwLock<Arc<ServiceConfig>>,
+    workers: RwLock<Option<WorkerPool>>,
+    faults: RwLock<Faults>,
+}
+
+impl Runtime {
+    pub fn new(
+        service_name: &str,
+        replica: Option<usize>,
+        section: Value,
+        config_json: &HashMap<String, ServiceConfigFromJSON>,
+    ) -> Result<Self, String> {
+        let deployment_methods = config_json
+            .iter()
+            .flat_map(|(service, config)| {
+                config
+                    .methods
+                    .keys()
+                    .map(move |method| format!("{}.{}", service, method))
+            })
+            .collect();
+        let runtime = Runtime {
+            service_name: service_name.to_string(),
+            replica,
+            deployment_methods,
+            section: Mutex::new(Value::Null),
+            config: RwLock::new(Arc::new(ServiceConfig {
+                methods: HashMap::new(),
+                network: HashMap::new(),
+                replica: Faults::default(),
+            })),
+            workers: RwLock::new(None),
+            faults: RwLock::new(Faults::default()),
+        };
+        runtime.apply(section)?;
+        Ok(runtime)
+    }
+
+    pub fn config(&self) -> Arc<ServiceConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn workers(&self) -> Option<Workers> {
+        self.workers
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|pool| pool.workers.clone())
+    }
+
+    pub fn faults(&self) -> Faults {
+        *self.faults.read().unwrap()
+    }
+
+    fn set_faults(&self, faults: Faults) -> Result<Faults, String> {
+        let faults = faults.checked()?;
+        *self.faults.write().unwrap() = faults;
+        println!(
+            "Faults set: latency x{}, error rate {}",
+            faults.latency_multiplier, faults.error_rate
+        );
+        Ok(faults)
+    }
+
+    fn section(&self) -> Value {
+        self.section.lock().unwrap().clone()
+    }
+
+    /// Replace the service's section of the config, keeping the current one if the new one
+    /// is invalid. Requests already running finish with the config they started with.
+    fn apply(&self, section: Value) -> Result<Value, String> {
+        self.update(|_| section)
+    }
+
+    /// Apply a JSON merge patch (RFC 7396) to the service's section, returning the result
+    fn patch(&self, patch: Value) -> Result<Value, String> {
+        let section = self.update(|current| {
+            let mut section = current.clone();
+            merge_patch(&mut section, patch);
+            section
+        })?;
+        println!("Config of {} updated", self.service_name);
+        Ok(section)
+    }
+
+    /// Derive a new section from the current one and apply it, holding the section's lock
+    /// so concurrent updates don't overwrite each other
+    fn update(&self, derive: impl FnOnce(&Value) -> Value) -> Result<Value, String> {
+        let mut current = self.section.lock().unwrap();
+        let section = derive(&current);
+        let parsed: ServiceConfigFromJSON =
+            serde_json::from_value(section.clone()).map_err(|e| e.to_string())?;
+        if parsed.concurrency == Some(0) {
+            return Err("concurrency must be at least 1".to_string());
+        }
+        let config = ServiceConfig::from_json(&parsed, self.replica)?;
+        // Only services of the deployment are reachable, though own methods may be new
+        for call in config.methods.values().flat_map(|method| {
+            method
+                .calls
+                .iter()
+                .flatten()
+                .flatten()
+                .chain(&method.async_calls)
+        }) {
+            let target = format!("{}.{}", call.service_name, call.method_name);
+            let own_method = call.service_name == self.service_name
+                && config.methods.contains_key(&call.method_name);
+            if !own_method && !self.deployment_methods.contains(&target) {
+                return Err(format!(
+                    "Call target {} is not part of the deployment",
+                    target
+                ));
+            }
+        }
+
+        self.resize_workers(parsed.concurrency);
+        *self.config.write().unwrap() = Arc::new(config);
+        *current = section.clone();
+        Ok(section)
+    }
+
+    /// Change the concurrency limit. A changed limit resizes the current pool, so requests
+    /// in flight keep counting against it; only a pool added to an unlimited service starts
+    /// empty, with the requests already running on top of it.
+    fn resize_workers(&self, limit: Option<usize>) {
+        let mut pool = self.workers.write().unwrap();
+        match (pool.as_mut(), limit) {
+            (Some(pool), Some(limit)) => {
+                let workers = &pool.workers;
+                if limit > pool.limit {
+                    // Permits not yet retired are kept before new ones are added
+                    let increase = limit - pool.limit;
+                    let kept = workers.cancel_retiring(increase);
+                    workers.semaphore.add_permits(increase - kept);
+                } else if limit < pool.limit {
+                    // Permits of requests in flight are retired as they come back, before
+                    // the requests queued for them can take them
+                    let excess = pool.limit - limit;
+                    let held = excess - workers.semaphore.forget_permits(excess);
+                    workers.retiring.fetch_add(held, Ordering::SeqCst);
+                }
+                pool.limit = limit;
+            }
+            (_, limit) => {
+                *pool = limit.map(|limit| WorkerPool {
+                    workers: Workers::new(limit),
+                    limit,
+                })
+            }
+        }
+    }
+}
+
+/// Apply a JSON merge patch: objects merge key by key, null removes a key and anything
+/// else replaces the target
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Admin gRPC service, served next to the service itself
+pub struct AdminService {
+    runtime: Arc<Runtime>,
+}
+
+impl AdminService {
+    pub fn new(runtime: Arc<Runtime>) -> Self {
+        AdminService { runtime }
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn set_faults(
+        &self,
+        request: Request<FaultSettings>,
+    ) -> Result<Response<FaultSettings>, Status> {
+        let settings = request.into_inner();
+        let faults = self
+            .runtime
+            .set_faults(Faults {
+                // 0 is what an unset protobuf field reads as
+                latency_multiplier: if settings.latency_multiplier == 0.0 {
+                    1.0
+                } else {
+                    settings.latency_multiplier
+                },
+                error_rate: settings.error_rate,
+            })
+            .map_err(Status::invalid_argument)?;
+        Ok(Response::new(FaultSettings {
+            latency_multiplier: faults.latency_multiplier,
+            error_rate: faults.error_rate,
+        }))
+    }
+
+    async fn get_config(
+        &self,
+        _request: Request<GetConfigRequest>,
+    ) -> Result<Response<ConfigDocument>, Status> {
+        Ok(Response::new(ConfigDocument {
+            json: self.runtime.section().to_string(),
+        }))
+    }
+
+    async fn update_config(
+        &self,
+        request: Request<ConfigPatch>,
+    ) -> Result<Response<ConfigDocument>, Status> {
+        let patch: Value = serde_json::from_str(&request.into_inner().merge_patch)
+            .map_err(|e| Status::invalid_argument(format!("Invalid merge patch: {}", e)))?;
+        let section = self
+            .runtime
+            .patch(patch)
+            .map_err(|e| Status::invalid_argument(format!("Invalid config: {}", e)))?;
+        Ok(Response::new(ConfigDocument {
+            json: section.to_string(),
+        }))
+    }
+}
+
+/// HTTP counterpart of the admin service: `GET`/`PATCH /admin/config` and
+/// `GET`/`PUT /admin/faults`
+pub fn router(runtime: Arc<Runtime>) -> Router {
+    Router::new()
+        .route("/admin/config", get(get_config).patch(patch_config))
+        .route("/admin/faults", get(get_faults).put(put_faults))
+        .with_state(runtime)
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> HttpResponse {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        serde_json::to_string(body).expect("Response serializes to JSON"),
+    )
+        .into_response()
+}
+
+fn bad_request(error: String) -> HttpResponse {
+    json_response(
+        StatusCode::BAD_REQUEST,
+        &serde_json::json!({ "error": error }),
+    )
+}
+
+async fn get_config(State(runtime): State<Arc<Runtime>>) -> HttpResponse {
+    json_response(StatusCode::OK, &runtime.section())
+}
+
+async fn patch_config(State(runtime): State<Arc<Runtime>>, body: Bytes) -> HttpResponse {
+    let patch = match serde_json::from_slice(&body) {
+        Ok(patch) => patch,
+        Err(e) => return bad_request(format!("Invalid merge patch: {}", e)),
+    };
+    match runtime.patch(patch) {
+        Ok(section) => json_response(StatusCode::OK, &section),
+        Err(e) => bad_request(format!("Invalid config: {}", e)),
+    }
+}
+
+async fn get_faults(State(runtime): State<Arc<Runtime>>) -> HttpResponse {
+    json_response(StatusCode::OK, &runtime.faults())
+}
+
+async fn put_faults(State(runtime): State<Arc<Runtime>>, body: Bytes) -> HttpResponse {
+    let faults = match serde_json::from_slice(&body) {
+        Ok(faults) => faults,
+        Err(e) => return bad_request(format!("Invalid faults: {}", e)),
+    };
+    match runtime.set_faults(faults) {
+        Ok(faults) => json_response(StatusCode::OK, &faults),
+        Err(e) => bad_request(e),
+    }
+}
+
+/// Reload the service's section whenever the config file changes. The directory is watched
+/// rather than the file, since editors often replace files instead of writing to them.
+pub async fn watch_config_file(
+    runtime: Arc<Runtime>,
+    path: PathBuf,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (sender, mut changes) = mpsc::unbounded_channel();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // Reading the file raises access events, which must not trigger another reload
+        if let Ok(event) = event {
+            if (event.kind.is_create() || event.kind.is_modify())
+                && event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref())
+            {
+                let _ = sender.send(());
+            }
+        }
+    })?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    println!("👀 Watching {} for config changes", path.display());
+
+    while changes.recv().await.is_some() {
+        tokio::time::sleep(RELOAD_DEBOUNCE).await;
+        while changes.try_recv().is_ok() {}
+        match reload(&runtime, &path) {
+            Ok(true) => println!("Config of {} reloaded", runtime.service_name),
+            Ok(false) => {}
+            Err(e) => eprintln!("Keeping the current config, reload failed: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Apply the service's section of the config file, telling whether it changed
+fn reload(runtime: &Runtime, path: &Path) -> Result<bool, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut document: HashMap<String, Value> =
+        serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let section = document
+        .remove(&runtime.service_name)
+        .ok_or("Own service not found in config")?;
+    if section == runtime.section() {
+        return Ok(false);
+    }
+    runtime.apply(section).map(|_| true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn runtime(concurrency: usize) -> Runtime {
+        let section = json!({
+            "ip": "svc",
+            "port": "50051",
+            "concurrency": concurrency,
+            "methods": {
+                "get": {"latency_distribution": {"type": "constant", "parameters": {"value": 1}}}
+            }
+        });
+        let config_json = HashMap::from([(
+            "svc".to_string(),
+            serde_json::from_value(section.clone()).unwrap(),
+        )]);
+        Runtime::new("svc", None, section, &config_json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn raising_concurrency_adds_permits_to_the_same_pool() {
+        let runtime = runtime(2);
+        let workers = runtime.workers().unwrap();
+        let _held = (
+            workers.acquire().await.unwrap(),
+            workers.acquire().await.unwrap(),
+        );
+        runtime.patch(json!({"concurrency": 3})).unwrap();
+        let semaphore = &workers.semaphore;
+        assert!(Arc::ptr_eq(
+            semaphore,
+            &runtime.workers().unwrap().semaphore
+        ));
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn lowering_concurrency_retires_permits_as_requests_finish() {
+        let runtime = runtime(3);
+        let workers = runtime.workers().unwrap();
+        let first = workers.acquire().await.unwrap();
+        let second = workers.acquire().await.unwrap();
+        let third = workers.acquire().await.unwrap();
+        let queued = tokio::spawn({
+            let workers = workers.clone();
+            async move { workers.acquire().await.map(|_| ()) }
+        });
+        tokio::task::yield_now().await;
+        runtime.patch(json!({"concurrency": 1})).unwrap();
+
+        // Two of the requests in flight exceed the new limit, so their permits are retired
+        // ahead of the queued request
+        drop(first);
+        drop(second);
+        tokio::task::yield_now().await;
+        assert!(!queued.is_finished());
+        assert_eq!(workers.semaphore.available_permits(), 0);
+
+        drop(third);
+        queued.await.unwrap().unwrap();
+        assert_eq!(workers.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn raising_concurrency_cancels_permits_not_yet_retired() {
+        let runtime = runtime(2);
+        let workers = runtime.workers().unwrap();
+        let held = (
+            workers.acquire().await.unwrap(),
+            workers.acquire().await.unwrap(),
+        );
+        runtime.patch(json!({"concurrency": 1})).unwrap();
+        runtime.patch(json!({"concurrency": 3})).unwrap();
+        assert_eq!(workers.semaphore.available_permits(), 1);
+        drop(held);
+        assert_eq!(workers.semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn zero_latency_multiplier_is_kept_outside_grpc() {
+        let faults = Faults {
+            latency_multiplier: 0.0,
+            error_rate: 0.0,
+        };
+        assert_eq!(faults.checked().unwrap().latency_multiplier, 0.0);
+    }
+}
@@ -0,0 +1,198 @@
+use rand::Rng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::service_stubs::service_client::ServiceClient;
+
+/// How a caller picks the replica of a callee to send a call to
+#[derive(Clone, Copy, Debug)]
+pub enum Policy {
+    RoundRobin,
+    /// The replica with the fewest of this caller's calls in flight
+    LeastOutstanding,
+    /// The less busy of two replicas picked at random
+    PowerOfTwoChoices,
+}
+
+impl Policy {
+    pub fn from_name(name: Option<&str>) -> Result<Self, String> {
+        match name {
+            None | Some("round_robin") => Ok(Policy::RoundRobin),
+            Some("least_outstanding") => Ok(Policy::LeastOutstanding),
+            Some("power_of_two_choices") => Ok(Policy::PowerOfTwoChoices),
+            Some(other) => Err(format!("Unsupported load balancing policy '{}'", other)),
+        }
+    }
+}
+
+struct Replica {
+    name: String,
+    client: ServiceClient<Channel>,
+    outstanding: AtomicUsize,
+}
+
+/// Clients of every replica of one callee. Outstanding calls are only those of this
+/// caller, so each caller balances on its own view like a client-side load balancer.
+pub struct Balancer {
+    policy: Policy,
+    replicas: Vec<Arc<Replica>>,
+    next: AtomicUsize,
+}
+
+impl Balancer {
+    /// Create a client for every replica, given as `(name, url)`. Each connects on its
+    /// first call, so an unreachable replica only fails the calls sent to it.
+    pub fn connect(
+        policy: Policy,
+        replicas: Vec<(String, String)>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut clients = Vec::new();
+        for (name, url) in replicas {
+            println!("Adding replica {} at {}", name, url);
+            clients.push(Arc::new(Replica {
+                name,
+                client: ServiceClient::new(Endpoint::from_shared(url)?.connect_lazy()),
+                outstanding: AtomicUsize::new(0),
+            }));
+        }
+        if clients.is_empty() {
+            return Err("Service has no replicas".into());
+        }
+        Ok(Balancer {
+            policy,
+            replicas: clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Pick a replica for one call, which counts as outstanding until the pick is dropped
+    pub fn pick(&self) -> Pick {
+        let count = self.replicas.len();
+        let load = |index: usize| self.replicas[index].outstanding.load(Ordering::Relaxed);
+        let index = match self.policy {
+            _ if count == 1 => 0,
+            Policy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % count,
+            // Ties go to the next replica in turn, so an idle deployment still spreads calls
+            Policy::LeastOutstanding => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (start..start + count)
+                    .map(|index| index % count)
+                    .min_by_key(|&index| load(index))
+                    .expect("Balancer has replicas")
+            }
+            Policy::PowerOfTwoChoices => {
+                let mut rng = rand::rng();
+                let first = rng.random_range(0..count);
+                let second = (first + rng.random_range(1..count)) % count;
+                if load(second) < load(first) {
+                    second
+                } else {
+                    first
+                }
+            }
+        };
+        let replica = self.replicas[index].clone();
+        replica.outstanding.fetch_add(1, Ordering::Relaxed);
+        Pick { replica }
+    }
+}
+
+/// Replica chosen for one call
+pub struct Pick {
+    replica: Arc<Replica>,
+}
+
+impl Pick {
+    pub fn name(&self) -> &str {
+        &self.replica.name
+    }
+
+    pub fn client(&self) -> ServiceClient<Channel> {
+        self.replica.client.clone()
+    }
+}
+
+impl Drop for Pick {
+    fn drop(&mut self) {
+        self.replica.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Balancer over replicas that are never connected to
+    fn balancer(policy: Policy, count: usize) -> Balancer {
+        let replicas = (0..count)
+            .map(|index| (index.to_string(), "http://127.0.0.1:1".to_string()))
+            .collect();
+        Balancer::connect(policy, replicas).unwrap()
+    }
+
+    fn names(picks: &[Pick]) -> Vec<&str> {
+        picks.iter().map(Pick::name).collect()
+    }
+
+    #[test]
+    fn policy_names() {
+        assert!(matches!(Policy::from_name(None), Ok(Policy::RoundRobin)));
+        assert!(matches!(
+            Policy::from_name(Some("least_outstanding")),
+            Ok(Policy::LeastOutstanding)
+        ));
+        assert!(matches!(
+            Policy::from_name(Some("power_of_two_choices")),
+            Ok(Policy::PowerOfTwoChoices)
+        ));
+        assert!(Policy::from_name(Some("random")).is_err());
+    }
+
+    #[tokio::test]
+    async fn round_robin_takes_replicas_in_turn() {
+        let balancer = balancer(Policy::RoundRobin, 3);
+        let picks: Vec<Pick> = (0..4).map(|_| balancer.pick()).collect();
+        assert_eq!(names(&picks), ["0", "1", "2", "0"]);
+    }
+
+    #[tokio::test]
+    async fn least_outstanding_avoids_busy_replicas() {
+        let balancer = balancer(Policy::LeastOutstanding, 3);
+        // Idle replicas are taken in turn, then the one whose call finished first
+        let mut picks: Vec<Pick> = (0..3).map(|_| balancer.pick()).collect();
+        assert_eq!(names(&picks), ["0", "1", "2"]);
+        picks.remove(1);
+        assert_eq!(balancer.pick().name(), "1");
+    }
+
+    #[tokio::test]
+    async fn power_of_two_choices_picks_the_less_busy_replica() {
+        let balancer = balancer(Policy::PowerOfTwoChoices, 2);
+        let busy = balancer.pick();
+        for _ in 0..20 {
+            assert_ne!(balancer.pick().name(), busy.name());
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_picks_stop_counting_as_outstanding() {
+        let balancer = balancer(Policy::RoundRobin, 2);
+        let outstanding =
+            |index: usize| balancer.replicas[index].outstanding.load(Ordering::Relaxed);
+        let pick = balancer.pick();
+        assert_eq!((outstanding(0), outstanding(1)), (1, 0));
+        drop(pick);
+        assert_eq!(outstanding(0), 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_replicas_fail_their_calls() {
+        let balancer = balancer(Policy::RoundRobin, 2);
+        let request = crate::service_stubs::ServiceRequest {
+            method_name: "get".to_string(),
+            call_path: Vec::new(),
+        };
+        assert!(balancer.pick().client().get_data(request).await.is_err());
+    }
+}
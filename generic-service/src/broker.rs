@@ -0,0 +1,205 @@
+use opentelemetry::trace::SpanKind;
+use opentelemetry::KeyValue;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tonic::{Request, Response, Status};
+
+use crate::service_stubs::broker_server::Broker;
+use crate::service_stubs::{PublishRequest, PublishResponse};
+use crate::{telemetry, GenericService};
+
+/// Deliveries of one message before it is dropped
+const MAX_DELIVERY_ATTEMPTS: u32 = 8;
+/// Wait before the first redelivery, doubling with every further attempt
+const INITIAL_REDELIVERY_BACKOFF: Duration = Duration::from_millis(50);
+/// Longest wait between two deliveries of a message
+const MAX_REDELIVERY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Queue of messages for a single callee method
+struct Topic {
+    sender: mpsc::UnboundedSender<PublishRequest>,
+    queue_length: Arc<AtomicU64>,
+}
+
+pub struct MessageBroker {
+    service: Arc<GenericService>,
+    topics: Mutex<HashMap<String, Topic>>,
+}
+
+impl MessageBroker {
+    pub fn new(service: GenericService) -> Self {
+        MessageBroker {
+            service: Arc::new(service),
+            topics: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Consume one topic in order, like a single-partition consumer. Deliveries happen after
+    /// the producer has answered, so they are not part of the runner's results; they show up
+    /// in this service's downstream call metrics and in the producer's trace instead.
+    async fn consume(
+        service: Arc<GenericService>,
+        mut receiver: mpsc::UnboundedReceiver<PublishRequest>,
+        queue_length: Arc<AtomicU64>,
+    ) {
+        while let Some(message) = receiver.recv().await {
+            let topic_name = format!("{}.{}", message.service_name, message.method_name);
+            let remaining = queue_length.fetch_sub(1, Ordering::SeqCst) - 1;
+            service
+                .metrics
+                .broker_queue_length
+                .with_label_values(&[&topic_name])
+                .set(remaining as i64);
+            // Deliveries continue the producer's trace, so consumer work links back to it
+            let cx = telemetry::start_span(
+                &telemetry::extract_from_headers(&message.headers),
+                format!("{}/{} deliver", message.service_name, message.method_name),
+                SpanKind::Consumer,
+                vec![KeyValue::new(
+                    "messaging.destination.name",
+                    format!("{}.{}", message.service_name, message.method_name),
+                )],
+            );
+            let trace_id = telemetry::trace_id(&cx);
+            // Redeliver with exponential backoff until the consumer succeeds or the attempts
+            // run out, so a callee that is down doesn't keep the consumer spinning
+            let mut backoff = INITIAL_REDELIVERY_BACKOFF;
+            for attempt in 0..MAX_DELIVERY_ATTEMPTS {
+                let response = service
+                    .call_service(
+                        &message.service_name,
+                        &message.method_name,
+                        &message.call_path,
+                        &cx,
+                    )
+                    .await;
+                println!(
+                    "Delivered async message to {} for trace {} (attempt {}, error: {})",
+                    topic_name,
+                    trace_id,
+                    attempt,
+                    response.is_err()
+                );
+                if response.is_ok() {
+                    break;
+                }
+                if attempt + 1 == MAX_DELIVERY_ATTEMPTS {
+                    eprintln!(
+                        "Dropped async message to {} for trace {} after {} attempts",
+                        topic_name, trace_id, MAX_DELIVERY_ATTEMPTS
+                    );
+                    service
+                        .metrics
+                        .broker_dropped_messages
+                        .with_label_values(&[&topic_name])
+                        .inc();
+                } else {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_REDELIVERY_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Broker for MessageBroker {
+    async fn publish(
+        &self,
+        request: Request<PublishRequest>,
+    ) -> Result<Response<PublishResponse>, Status> {
+        let message = request.into_inner();
+        let topic_name = format!("{}.{}", message.service_name, message.method_name);
+        println!("Received message for topic: {}", topic_name);
+
+        let mut topics = self.topics.lock().await;
+        let topic = topics.entry(topic_name.clone()).or_insert_with(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let queue_length = Arc::new(AtomicU64::new(0));
+            tokio::spawn(Self::consume(
+                self.service.clone(),
+                receiver,
+                queue_length.clone(),
+            ));
+            Topic {
+                sender,
+                queue_length,
+            }
+        });
+
+        let queue_length = topic.queue_length.fetch_add(1, Ordering::SeqCst) + 1;
+        self.service
+            .metrics
+            .broker_queue_length
+            .with_label_values(&[&topic_name])
+            .set(queue_length as i64);
+        topic
+            .sender
+            .send(message)
+            .map_err(|_| Status::unavailable("Topic consumer stopped"))?;
+
+        Ok(Response::new(PublishResponse { queue_length }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::Runtime;
+    use crate::metrics::Metrics;
+    use serde_json::json;
+
+    /// Broker whose only callee, `svc`, nothing listens for
+    fn broker_service() -> GenericService {
+        let section = json!({
+            "ip": "127.0.0.1",
+            "port": "1",
+            "methods": {
+                "get": {"latency_distribution": {"type": "constant", "parameters": {"value": 1}}}
+            }
+        });
+        let config_json = HashMap::from([(
+            "svc".to_string(),
+            serde_json::from_value(section.clone()).unwrap(),
+        )]);
+        GenericService {
+            service_name: "svc".to_string(),
+            runtime: Arc::new(Runtime::new("svc", None, section, &config_json).unwrap()),
+            config_json,
+            services: Arc::new(Mutex::new(HashMap::new())),
+            broker: Arc::new(Mutex::new(None)),
+            metrics: Arc::new(Metrics::new("svc")),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn undeliverable_messages_are_retried_then_dropped() {
+        let service = Arc::new(broker_service());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        sender
+            .send(PublishRequest {
+                service_name: "svc".to_string(),
+                method_name: "get".to_string(),
+                headers: HashMap::new(),
+                call_path: Vec::new(),
+            })
+            .unwrap();
+        drop(sender);
+        MessageBroker::consume(service.clone(), receiver, Arc::new(AtomicU64::new(1))).await;
+
+        let metrics = &service.metrics;
+        let attempts = metrics
+            .downstream_errors
+            .with_label_values(&["svc.get", "127.0.0.1"])
+            .get();
+        assert_eq!(attempts, MAX_DELIVERY_ATTEMPTS as u64);
+        let dropped = metrics
+            .broker_dropped_messages
+            .with_label_values(&["svc.get"])
+            .get();
+        assert_eq!(dropped, 1);
+    }
+}
@@ -1,46 +1,138 @@
 use futures::future;
+use opentelemetry::trace::{SpanKind, Status as SpanStatus, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
 use prost_types::Timestamp;
-use rand_distr::{Bernoulli, Distribution, Normal};
+use rand::Rng;
+use rand_distr::{Bernoulli, Distribution, Exp, Normal, Uniform};
 use serde::{Deserialize, Serialize};
-use service_stubs::service_client::ServiceClient;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::env;
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
 use tokio::time::sleep;
 use tonic::transport::Channel;
 use tonic::{transport::Server, Request, Response, Status};
 
+mod admin;
+mod balancer;
+mod broker;
+mod metrics;
+mod telemetry;
+
 pub mod service_stubs {
     tonic::include_proto!("service");
 }
 
+use admin::{AdminService, Faults, Runtime};
+use balancer::{Balancer, Pick, Policy};
+use broker::MessageBroker;
+use metrics::Metrics;
+use service_stubs::admin_server::AdminServer;
+use service_stubs::broker_client::BrokerClient;
+use service_stubs::broker_server::BrokerServer;
 use service_stubs::service_server::{Service, ServiceServer};
-use service_stubs::{CallData, ServiceRequest, ServiceResponse};
+use service_stubs::{CallData, PublishRequest, ServiceRequest, ServiceResponse};
+
+/// Name the orchestrator gives the message broker in the shared config
+const BROKER_SERVICE_NAME: &str = "message_broker";
 
 #[derive(Serialize, Deserialize)]
 struct ServiceConfigFromJSON {
     ip: String,
     port: String,
     methods: HashMap<String, MethodConfigFromJSON>,
+    #[serde(default)]
+    concurrency: Option<usize>,
+    // Delay and loss of this service's calls, by callee
+    #[serde(default)]
+    network: HashMap<String, LinkConfigFromJSON>,
+    // Host of each replica when there are several; otherwise `ip` is the only one
+    #[serde(default)]
+    replicas: Vec<String>,
+    // How callers pick a replica, round robin when absent
+    #[serde(default)]
+    load_balancing: Option<String>,
+    #[serde(default)]
+    replica_overrides: Vec<ReplicaOverrideFromJSON>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplicaOverrideFromJSON {
+    replica: usize,
+    // Latency multiplier and extra error rate, like injected faults
+    #[serde(flatten)]
+    behaviour: Faults,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkConfigFromJSON {
+    #[serde(default)]
+    delay_ms: f64,
+    #[serde(default)]
+    jitter_ms: f64,
+    #[serde(default)]
+    loss: f64,
 }
 #[derive(Serialize, Deserialize)]
 struct MethodConfigFromJSON {
     calls: Option<Vec<Vec<String>>>,
+    #[serde(default)]
+    async_calls: Vec<String>,
     latency_distribution: DistributionConfigFromJSON,
-    error_rate: DistributionConfigFromJSON,
+    // Optional in the runner's config, where a missing error rate never errors
+    #[serde(default)]
+    error_rate: Option<DistributionConfigFromJSON>,
+    #[serde(default)]
+    max_recursion_depth: Option<usize>,
 }
 
 struct ServiceConfig {
     methods: HashMap<String, MethodConfig>,
+    network: HashMap<String, Link>,
+    // How this replica differs from the others
+    replica: Faults,
+}
+
+/// Simulated network between this service and one callee
+struct Link {
+    delay: Normal<f64>,
+    loss: Bernoulli,
+}
+
+impl Link {
+    fn from_json(link: &LinkConfigFromJSON) -> Result<Self, String> {
+        if link.delay_ms < 0.0 || link.jitter_ms < 0.0 {
+            return Err("delay_ms and jitter_ms must not be negative".to_string());
+        }
+        Ok(Link {
+            delay: Normal::new(link.delay_ms, link.jitter_ms).map_err(|e| e.to_string())?,
+            loss: Bernoulli::new(link.loss).map_err(|e| e.to_string())?,
+        })
+    }
+
+    /// Wait for the call to cross the link, telling whether it was lost on the way
+    async fn traverse(&self) -> bool {
+        let (delay_ms, lost) = {
+            let mut rng = rand::rng();
+            (self.delay.sample(&mut rng), self.loss.sample(&mut rng))
+        };
+        if delay_ms > 0.0 {
+            sleep(std::time::Duration::from_secs_f64(delay_ms / 1000.0)).await;
+        }
+        lost
+    }
 }
 
 struct MethodConfig {
     calls: Option<Vec<Vec<Call>>>,
+    async_calls: Vec<Call>,
     latency_distribution: Box<dyn DistributionSimulator<f64>>,
     error_rate: Box<dyn DistributionSimulator<bool>>,
+    // Nesting within one request after which the method stops making calls
+    max_recursion_depth: Option<usize>,
 }
 
 struct Call {
@@ -48,8 +140,30 @@ struct Call {
     method_name: String,
 }
 
+fn parse_call(call: &str) -> Result<Call, String> {
+    let (service_name, method_name) = call
+        .split_once('.')
+        .ok_or_else(|| format!("Invalid call '{}', expected 'service.method'", call))?;
+    Ok(Call {
+        service_name: service_name.to_string(),
+        method_name: method_name.to_string(),
+    })
+}
+
+fn now_timestamp() -> Timestamp {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before UNIX EPOCH");
+    Timestamp {
+        seconds: now.as_secs() as i64,
+        nanos: now.subsec_nanos() as i32,
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct DistributionConfigFromJSON {
+    // The runner writes "type"; older configs used "distribution_type"
+    #[serde(rename = "type", alias = "distribution_type")]
     distribution_type: String,
     parameters: HashMap<String, f64>,
 }
@@ -69,6 +183,77 @@ impl DistributionSimulator<f64> for NormalDistribution {
     }
 }
 
+struct ConstantDistribution {
+    value: f64,
+}
+
+impl DistributionSimulator<f64> for ConstantDistribution {
+    fn simulate(&self) -> f64 {
+        self.value
+    }
+}
+
+struct UniformDistribution {
+    distribution: rand_distr::Uniform<f64>,
+}
+
+impl DistributionSimulator<f64> for UniformDistribution {
+    fn simulate(&self) -> f64 {
+        let mut rng = rand::rng();
+        self.distribution.sample(&mut rng)
+    }
+}
+
+struct ExponentialDistribution {
+    distribution: rand_distr::Exp<f64>,
+}
+
+impl DistributionSimulator<f64> for ExponentialDistribution {
+    fn simulate(&self) -> f64 {
+        let mut rng = rand::rng();
+        self.distribution.sample(&mut rng)
+    }
+}
+
+/// Piecewise linear quantile function through percentile points such as `p0`, `p50`, `p100`
+struct EmpiricalDistribution {
+    quantiles: Vec<(f64, f64)>,
+}
+
+impl EmpiricalDistribution {
+    fn new(parameters: &HashMap<String, f64>) -> Self {
+        let mut quantiles: Vec<(f64, f64)> = parameters
+            .iter()
+            .filter_map(|(name, value)| {
+                let percent: f64 = name.strip_prefix('p')?.parse().ok()?;
+                Some((percent / 100.0, *value))
+            })
+            .collect();
+        quantiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        EmpiricalDistribution { quantiles }
+    }
+}
+
+impl DistributionSimulator<f64> for EmpiricalDistribution {
+    fn simulate(&self) -> f64 {
+        let fraction: f64 = rand::rng().random();
+        let upper = self.quantiles.partition_point(|(q, _)| *q < fraction);
+        match (upper.checked_sub(1), self.quantiles.get(upper)) {
+            (Some(lower), Some(&(q1, v1))) => {
+                let (q0, v0) = self.quantiles[lower];
+                if q1 > q0 {
+                    v0 + (v1 - v0) * (fraction - q0) / (q1 - q0)
+                } else {
+                    v1
+                }
+            }
+            (None, Some(&(_, value))) => value,
+            (Some(lower), None) => self.quantiles[lower].1,
+            (None, None) => 0.0,
+        }
+    }
+}
+
 struct BernoulliDistribution {
     distribution: rand_distr::Bernoulli,
 }
@@ -80,10 +265,138 @@ impl DistributionSimulator<bool> for BernoulliDistribution {
     }
 }
 
+impl ServiceConfig {
+    fn from_json(service: &ServiceConfigFromJSON, replica: Option<usize>) -> Result<Self, String> {
+        let methods = service
+            .methods
+            .iter()
+            .map(|(name, method)| {
+                MethodConfig::from_json(method)
+                    .map(|config| (name.clone(), config))
+                    .map_err(|e| format!("Method {}: {}", name, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let network = service
+            .network
+            .iter()
+            .map(|(callee, link)| {
+                Link::from_json(link)
+                    .map(|link| (callee.clone(), link))
+                    .map_err(|e| format!("Network link to {}: {}", callee, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let replica = match service
+            .replica_overrides
+            .iter()
+            .find(|behaviour| Some(behaviour.replica) == replica)
+        {
+            Some(behaviour) => behaviour
+                .behaviour
+                .checked()
+                .map_err(|e| format!("Replica {}: {}", behaviour.replica, e))?,
+            None => Faults::default(),
+        };
+        Ok(ServiceConfig {
+            methods,
+            network,
+            replica,
+        })
+    }
+}
+
+impl MethodConfig {
+    fn from_json(method: &MethodConfigFromJSON) -> Result<Self, String> {
+        let calls = match &method.calls {
+            Some(calls) => Some(
+                calls
+                    .iter()
+                    .map(|call_row| call_row.iter().map(|call| parse_call(call)).collect())
+                    .collect::<Result<_, _>>()?,
+            ),
+            None => None,
+        };
+        Ok(MethodConfig {
+            calls,
+            async_calls: method
+                .async_calls
+                .iter()
+                .map(|call| parse_call(call))
+                .collect::<Result<_, _>>()?,
+            latency_distribution: latency_simulator(&method.latency_distribution)?,
+            error_rate: error_simulator(method.error_rate.as_ref())?,
+            max_recursion_depth: method.max_recursion_depth,
+        })
+    }
+}
+
+fn parameter(distribution: &DistributionConfigFromJSON, name: &str) -> Result<f64, String> {
+    distribution.parameters.get(name).copied().ok_or_else(|| {
+        format!(
+            "{} distribution is missing '{}'",
+            distribution.distribution_type, name
+        )
+    })
+}
+
+fn latency_simulator(
+    distribution: &DistributionConfigFromJSON,
+) -> Result<Box<dyn DistributionSimulator<f64>>, String> {
+    Ok(match distribution.distribution_type.as_str() {
+        "normal" => Box::new(NormalDistribution {
+            distribution: Normal::new(
+                parameter(distribution, "mean")?,
+                parameter(distribution, "stddev")?,
+            )
+            .map_err(|e| e.to_string())?,
+        }),
+        "uniform" => Box::new(UniformDistribution {
+            distribution: Uniform::new_inclusive(
+                parameter(distribution, "min")?,
+                parameter(distribution, "max")?,
+            )
+            .map_err(|e| e.to_string())?,
+        }),
+        "constant" => Box::new(ConstantDistribution {
+            value: parameter(distribution, "value")?,
+        }),
+        "exponential" => Box::new(ExponentialDistribution {
+            distribution: Exp::new(parameter(distribution, "rate")?).map_err(|e| e.to_string())?,
+        }),
+        "empirical" => Box::new(EmpiricalDistribution::new(&distribution.parameters)),
+        other => return Err(format!("Unsupported distribution type '{}'", other)),
+    })
+}
+
+fn error_simulator(
+    distribution: Option<&DistributionConfigFromJSON>,
+) -> Result<Box<dyn DistributionSimulator<bool>>, String> {
+    let p = match distribution {
+        None => 0.0,
+        // The runner only warns about p outside [0, 1]
+        Some(error_rate) if error_rate.distribution_type == "bernoulli" => {
+            parameter(error_rate, "p")?.clamp(0.0, 1.0)
+        }
+        Some(error_rate) => {
+            return Err(format!(
+                "Unsupported distribution type '{}'",
+                error_rate.distribution_type
+            ))
+        }
+    };
+    Ok(Box::new(BernoulliDistribution {
+        distribution: Bernoulli::new(p).map_err(|e| e.to_string())?,
+    }))
+}
+
 pub struct GenericService {
-    config: ServiceConfig,
+    service_name: String,
+    // Behaviour the admin interface and config reloads can change while the service runs
+    runtime: Arc<Runtime>,
     config_json: HashMap<String, ServiceConfigFromJSON>,
-    services: Arc<Mutex<HashMap<String, ServiceClient<Channel>>>>,
+    // Replicas of every callee connected to so far
+    services: Arc<Mutex<HashMap<String, Arc<Balancer>>>>,
+    broker: Arc<Mutex<Option<BrokerClient<Channel>>>>,
+    metrics: Arc<Metrics>,
 }
 
 #[derive(Clone)]
@@ -100,103 +413,86 @@ pub struct ServiceErrorWrapper {
     received_at: Timestamp,
 }
 
+fn config_path() -> PathBuf {
+    env::var("CONFIG_PATH")
+        .unwrap_or_else(|_| "config/config.json".to_string())
+        .into()
+}
+
 impl GenericService {
     pub async fn new() -> Self {
-        let config_path_str =
-            env::var("CONFIG_PATH").unwrap_or_else(|_| "config/config.json".to_string());
-        let config_path = Path::new(&config_path_str);
-        let config_json: HashMap<String, ServiceConfigFromJSON> = serde_json::from_str(
-            &std::fs::read_to_string(config_path).expect("Failed to read config file"),
-        )
-        .expect("Failed to parse config file");
+        let config_path = config_path();
+        let content = std::fs::read_to_string(config_path).expect("Failed to read config file");
+        let config_json: HashMap<String, ServiceConfigFromJSON> =
+            serde_json::from_str(&content).expect("Failed to parse config file");
         let service_name = env::var("SERVICE_NAME").expect("Failed to get SERVICE_NAME");
-        config_json
-            .get(&service_name)
+        let mut document: HashMap<String, Value> =
+            serde_json::from_str(&content).expect("Failed to parse config file");
+        let section = document
+            .remove(&service_name)
             .expect("Own service not found in config");
-        let config = ServiceConfig {
-            methods: config_json[&service_name]
-                .methods
-                .iter()
-                .map(|(k, v)| {
-                    (
-                        k.clone(),
-                        MethodConfig {
-                            calls: v.calls.as_ref().map(|calls| {
-                                calls
-                                    .iter()
-                                    .map(|call_row| {
-                                        call_row
-                                            .iter()
-                                            .map(|call| {
-                                                let mut call_parts = call.split(".");
-                                                let service_name =
-                                                    call_parts.next().unwrap().to_string();
-                                                let method_name =
-                                                    call_parts.next().unwrap().to_string();
-                                                Call {
-                                                    service_name,
-                                                    method_name,
-                                                }
-                                            })
-                                            .collect()
-                                    })
-                                    .collect()
-                            }),
-                            latency_distribution: match v
-                                .latency_distribution
-                                .distribution_type
-                                .as_str()
-                            {
-                                "normal" => Box::new(NormalDistribution {
-                                    distribution: Normal::new(
-                                        v.latency_distribution.parameters["mean"],
-                                        v.latency_distribution.parameters["stddev"],
-                                    )
-                                    .unwrap(),
-                                }),
-                                _ => panic!("Unsupported distribution type"),
-                            },
-                            error_rate: match v.error_rate.distribution_type.as_str() {
-                                "bernoulli" => Box::new(BernoulliDistribution {
-                                    distribution: Bernoulli::new(v.error_rate.parameters["p"])
-                                        .unwrap(),
-                                }),
-                                _ => panic!("Unsupported distribution type"),
-                            },
-                        },
-                    )
-                })
-                .collect(),
-        };
+        // Set by the orchestrator on every replica, so it can find its overrides
+        let replica = env::var("REPLICA_INDEX")
+            .ok()
+            .map(|index| index.parse().expect("REPLICA_INDEX must be a number"));
+        let runtime = Runtime::new(&service_name, replica, section, &config_json)
+            .expect("Invalid service config");
         GenericService {
-            config,
+            metrics: Arc::new(Metrics::new(&service_name)),
+            runtime: Arc::new(runtime),
+            service_name,
             config_json,
             services: Arc::new(Mutex::new(HashMap::new())),
+            broker: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Pick the replica of a service to send a call to, setting up clients for all of them first
     pub async fn init_service_client(
         &self,
         service_name: &str,
-    ) -> Result<ServiceClient<Channel>, Box<dyn std::error::Error>> {
-        if self.services.lock().await.contains_key(service_name) {
-            return Ok(self
-                .services
-                .lock()
-                .await
-                .get(service_name)
-                .unwrap()
-                .clone());
+    ) -> Result<Pick, Box<dyn std::error::Error>> {
+        if let Some(balancer) = self.services.lock().await.get(service_name) {
+            return Ok(balancer.pick());
         }
-        let service_ip = self.config_json[service_name].ip.clone();
-        let service_port = self.config_json[service_name].port.clone();
-        let service_url = format!("http://{}:{}", service_ip, service_port);
-        println!("Connecting to service {} at {}", service_name, service_url);
-        let client = ServiceClient::connect(service_url).await?;
+        let service_cnf = &self.config_json[service_name];
+        let hosts = if service_cnf.replicas.is_empty() {
+            std::slice::from_ref(&service_cnf.ip)
+        } else {
+            &service_cnf.replicas[..]
+        };
+        println!("Connecting to service {}", service_name);
+        let replicas = hosts
+            .iter()
+            .map(|host| {
+                let url = format!("http://{}:{}", host, service_cnf.port);
+                (host.clone(), url)
+            })
+            .collect();
+        let policy = Policy::from_name(service_cnf.load_balancing.as_deref())?;
+        let balancer = Arc::new(Balancer::connect(policy, replicas)?);
+        let pick = balancer.pick();
         self.services
             .lock()
             .await
-            .insert(service_name.to_string(), client.clone());
+            .insert(service_name.to_string(), balancer);
+        Ok(pick)
+    }
+
+    pub async fn init_broker_client(
+        &self,
+    ) -> Result<BrokerClient<Channel>, Box<dyn std::error::Error>> {
+        if let Some(client) = self.broker.lock().await.as_ref() {
+            return Ok(client.clone());
+        }
+        let broker_cnf = self
+            .config_json
+            .get(BROKER_SERVICE_NAME)
+            .ok_or("Message broker not found in config")?;
+        let broker_url = format!("http://{}:{}", broker_cnf.ip, broker_cnf.port);
+        println!("Connecting to message broker at {}", broker_url);
+        let client = BrokerClient::connect(broker_url).await?;
+        *self.broker.lock().await = Some(client.clone());
         Ok(client)
     }
 
@@ -204,34 +500,76 @@ impl GenericService {
         &self,
         service_name: &str,
         method_name: &str,
+        call_path: &[String],
+        cx: &Context,
     ) -> Result<ServiceResponseWrapper, ServiceErrorWrapper> {
         println!(
             "Calling service {} with method {}",
             service_name, method_name
         );
 
-        let mut client = self
-            .init_service_client(service_name)
-            .await
-            .expect("Client connection failed");
-        let request = tonic::Request::new(ServiceRequest {
+        // Counts as outstanding on its replica until the call returns
+        let replica = match self.init_service_client(service_name).await {
+            Ok(replica) => replica,
+            Err(e) => {
+                // Fails like any other call, so the caller records it and retries its stage
+                eprintln!("Error connecting to service {}: {}", service_name, e);
+                let sent_at = now_timestamp();
+                return Result::Err(ServiceErrorWrapper {
+                    method_name: method_name.to_string(),
+                    received_at: sent_at,
+                    sent_at,
+                });
+            }
+        };
+        let mut client = replica.client();
+        let mut request = tonic::Request::new(ServiceRequest {
             method_name: method_name.to_string(),
+            call_path: call_path.to_vec(),
         });
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time before UNIX EPOCH");
-        let sent_at = Timestamp {
-            seconds: now.as_secs() as i64,
-            nanos: now.subsec_nanos() as i32,
+        // One client span per attempt, so retried calls show up individually
+        let attempt_cx = telemetry::start_span(
+            cx,
+            format!("{}/{}", service_name, method_name),
+            SpanKind::Client,
+            vec![
+                KeyValue::new("rpc.service", service_name.to_string()),
+                KeyValue::new("rpc.method", method_name.to_string()),
+                KeyValue::new("server.address", replica.name().to_string()),
+            ],
+        );
+        telemetry::inject_into_metadata(&attempt_cx, request.metadata_mut());
+        let callee = format!("{}.{}", service_name, method_name);
+        let started = Instant::now();
+        let sent_at = now_timestamp();
+        // A lost call fails like an unreachable callee, and is retried with its stage
+        let lost = match self.runtime.config().network.get(service_name) {
+            Some(link) => link.traverse().await,
+            None => false,
         };
-        let response = client.get_data(request).await;
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time before UNIX EPOCH");
-        let received_at = Timestamp {
-            seconds: now.as_secs() as i64,
-            nanos: now.subsec_nanos() as i32,
+        let response = if lost {
+            Err(Status::unavailable("Simulated network loss"))
+        } else {
+            client.get_data(request).await
         };
+        let received_at = now_timestamp();
+        self.metrics
+            .downstream_latency
+            .with_label_values(&[&callee, replica.name()])
+            .observe(started.elapsed().as_secs_f64());
+        self.metrics
+            .downstream_calls
+            .with_label_values(&[&callee, replica.name()])
+            .inc();
+        if let Err(e) = &response {
+            self.metrics
+                .downstream_errors
+                .with_label_values(&[&callee, replica.name()])
+                .inc();
+            attempt_cx
+                .span()
+                .set_status(SpanStatus::error(e.message().to_string()));
+        }
         match response {
             Ok(res) => {
                 println!("Response: {:?}", res);
@@ -252,36 +590,137 @@ impl GenericService {
             }
         }
     }
-}
 
-#[tonic::async_trait]
-impl Service for GenericService {
-    async fn get_data(
+    /// Enqueue a call on the message broker without waiting for the callee
+    async fn publish(
+        &self,
+        call: &Call,
+        stage: u32,
+        call_path: &[String],
+        cx: &Context,
+    ) -> CallData {
+        println!(
+            "Publishing to service {} with method {}",
+            call.service_name, call.method_name
+        );
+        // The consumer's spans become children of this one, linking them to the request
+        let publish_cx = telemetry::start_span(
+            cx,
+            format!("{}/{} publish", call.service_name, call.method_name),
+            SpanKind::Producer,
+            vec![KeyValue::new(
+                "messaging.destination.name",
+                format!("{}.{}", call.service_name, call.method_name),
+            )],
+        );
+        let sent_at = now_timestamp();
+        let request = tonic::Request::new(PublishRequest {
+            service_name: call.service_name.clone(),
+            method_name: call.method_name.clone(),
+            headers: telemetry::inject_into_headers(&publish_cx),
+            call_path: call_path.to_vec(),
+        });
+        let client = self.init_broker_client().await.map_err(|e| e.to_string());
+        let response = match client {
+            Ok(mut client) => client.publish(request).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        let callee = format!("{}.{}", call.service_name, call.method_name);
+        // Publishes go to the broker rather than to a replica of the callee
+        let labels = [callee.as_str(), BROKER_SERVICE_NAME];
+        self.metrics
+            .downstream_calls
+            .with_label_values(&labels)
+            .inc();
+        if let Err(e) = &response {
+            self.metrics
+                .downstream_errors
+                .with_label_values(&labels)
+                .inc();
+            eprintln!("Error publishing to broker: {}", e);
+            publish_cx.span().set_status(SpanStatus::error(e.clone()));
+        }
+        CallData {
+            method_name: call.method_name.clone(),
+            request_sent_at: Some(sent_at),
+            response_received_at: Some(now_timestamp()),
+            was_an_error: response.is_err(),
+            trace_id: telemetry::trace_id(&publish_cx),
+            was_async: true,
+            service_name: call.service_name.clone(),
+            calls: Vec::new(),
+            stage,
+            attempt: 0,
+        }
+    }
+
+    async fn handle_request(
         &self,
         request: Request<ServiceRequest>,
     ) -> Result<Response<ServiceResponse>, Status> {
-        let method_name = request.into_inner().method_name;
+        // Continue the caller's trace, or start one when this is an entry point
+        let parent_cx = telemetry::extract_from_metadata(request.metadata());
+        let ServiceRequest {
+            method_name,
+            mut call_path,
+        } = request.into_inner();
+        let cx = telemetry::start_span(
+            &parent_cx,
+            format!("{}/{}", self.service_name, method_name),
+            SpanKind::Server,
+            vec![
+                KeyValue::new("rpc.service", self.service_name.clone()),
+                KeyValue::new("rpc.method", method_name.clone()),
+            ],
+        );
+        let trace_id = telemetry::trace_id(&cx);
         println!("Received request for method: {}", method_name);
-        let method_cnf = self
-            .config
-            .methods
-            .get(&method_name)
-            .expect("Method not found in config");
+        // Hold on to the config the request started with, even if it is replaced meanwhile
+        let config = self.runtime.config();
+        let Some(method_cnf) = config.methods.get(&method_name) else {
+            return Err(Status::not_found(format!(
+                "Method {} not found in config",
+                method_name
+            )));
+        };
+        let faults = self.runtime.faults().and(config.replica);
         println!("Simulating Latency");
         // wait latency
-        let latency = method_cnf.latency_distribution.simulate();
+        let latency = method_cnf.latency_distribution.simulate() * faults.latency_multiplier;
         sleep(std::time::Duration::from_millis(latency.round() as u64)).await;
-        let error_rate = method_cnf.error_rate.simulate();
+        let error_rate = method_cnf.error_rate.simulate()
+            || (faults.error_rate > 0.0 && rand::rng().random_bool(faults.error_rate));
         if error_rate {
             println!("Simulating Error");
+            cx.span().set_status(SpanStatus::error("Simulated Error"));
             return Err(Status::internal("Simulated Error"));
         }
         println!("Did not Error");
         let mut call_list = Vec::new();
+
+        // A recursive method stops calling once it is nested deep enough in this request
+        let own_call = format!("{}.{}", self.service_name, method_name);
+        let depth = call_path
+            .iter()
+            .filter(|caller| **caller == own_call)
+            .count();
+        if method_cnf
+            .max_recursion_depth
+            .is_some_and(|limit| depth >= limit)
+        {
+            println!("Reached max recursion depth {}", depth);
+            return Ok(Response::new(ServiceResponse {
+                calls: call_list,
+                method_name,
+            }));
+        }
+        call_path.push(own_call);
+
         match &method_cnf.calls {
             Some(calls) => {
-                for call_row in calls {
+                for (stage, call_row) in calls.iter().enumerate() {
                     let mut succeeded = vec![false; call_row.len()];
+                    let mut attempt = 0;
                     while succeeded.contains(&false) {
                         let mut futures = Vec::new();
                         for (i, call) in call_row.iter().enumerate() {
@@ -290,7 +729,12 @@ impl Service for GenericService {
                             }
                             let service_to_call = &call.service_name;
                             let method_to_call = &call.method_name;
-                            futures.push(self.call_service(service_to_call, method_to_call));
+                            futures.push(self.call_service(
+                                service_to_call,
+                                method_to_call,
+                                &call_path,
+                                &cx,
+                            ));
                         }
                         let resp = future::join_all(futures).await;
                         let mut j = 0;
@@ -298,26 +742,39 @@ impl Service for GenericService {
                             if !succeeded[i] {
                                 succeeded[i] = resp[j].is_ok();
                                 let respj = resp[j].clone();
-                                let (method_name, sent_at, received_at, was_error) = match &respj {
-                                    Ok(r) => {
-                                        for c in &r.res.calls {
-                                            call_list.push(c.clone());
-                                        }
-                                        (r.res.method_name.clone(), r.sent_at, r.received_at, false)
-                                    }
-                                    Err(r) => {
-                                        (r.method_name.clone(), r.sent_at, r.received_at, true)
-                                    }
-                                };
+                                let (method_name, sent_at, received_at, was_error, calls) =
+                                    match &respj {
+                                        Ok(r) => (
+                                            r.res.method_name.clone(),
+                                            r.sent_at,
+                                            r.received_at,
+                                            false,
+                                            r.res.calls.clone(),
+                                        ),
+                                        Err(r) => (
+                                            r.method_name.clone(),
+                                            r.sent_at,
+                                            r.received_at,
+                                            true,
+                                            Vec::new(),
+                                        ),
+                                    };
                                 call_list.push(CallData {
                                     method_name,
                                     request_sent_at: Some(sent_at),
                                     response_received_at: Some(received_at),
                                     was_an_error: was_error,
+                                    trace_id: trace_id.clone(),
+                                    was_async: false,
+                                    service_name: call_row[i].service_name.clone(),
+                                    calls,
+                                    stage: stage as u32,
+                                    attempt,
                                 });
                                 j += 1;
                             }
                         });
+                        attempt += 1;
                     }
                 }
             }
@@ -325,6 +782,10 @@ impl Service for GenericService {
                 println!("No calls to make");
             }
         }
+        let async_stage = method_cnf.calls.as_ref().map_or(0, |calls| calls.len()) as u32;
+        for call in &method_cnf.async_calls {
+            call_list.push(self.publish(call, async_stage, &call_path, &cx).await);
+        }
         Ok(Response::new(ServiceResponse {
             calls: call_list,
             method_name,
@@ -332,17 +793,89 @@ impl Service for GenericService {
     }
 }
 
+#[tonic::async_trait]
+impl Service for GenericService {
+    async fn get_data(
+        &self,
+        request: Request<ServiceRequest>,
+    ) -> Result<Response<ServiceResponse>, Status> {
+        let method_name = request.get_ref().method_name.clone();
+        let labels = [method_name.as_str()];
+        let started = Instant::now();
+
+        // Wait for a worker when the service's concurrency is limited
+        let _permit = match self.runtime.workers() {
+            Some(workers) => {
+                self.metrics.queued.with_label_values(&labels).inc();
+                let permit = workers.acquire().await;
+                self.metrics.queued.with_label_values(&labels).dec();
+                Some(permit.expect("Worker pool closed"))
+            }
+            None => None,
+        };
+
+        self.metrics.requests.with_label_values(&labels).inc();
+        self.metrics.in_flight.with_label_values(&labels).inc();
+        let response = self.handle_request(request).await;
+        self.metrics.in_flight.with_label_values(&labels).dec();
+        if response.is_err() {
+            self.metrics.errors.with_label_values(&labels).inc();
+        }
+        self.metrics
+            .latency
+            .with_label_values(&labels)
+            .observe(started.elapsed().as_secs_f64());
+        response
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let port = env::var("SERVICE_PORT").unwrap_or_else(|_| "50051".to_string());
     let addr = format!("0.0.0.0:{}", port).parse()?;
 
     let service = GenericService::new().await;
+    let _tracer_provider = telemetry::init_tracer(&service.service_name)?;
+
+    let is_broker = env::var("SERVICE_ROLE").is_ok_and(|role| role == "broker");
+    // The admin endpoints share the metrics port; the broker has no behaviour to change
+    let metrics_port = env::var("METRICS_PORT").unwrap_or_else(|_| "9464".to_string());
+    let metrics = service.metrics.clone();
+    let admin_routes = if is_broker {
+        axum::Router::new()
+    } else {
+        admin::router(service.runtime.clone())
+    };
+    tokio::spawn(async move {
+        if let Err(e) = metrics::serve_metrics(metrics, admin_routes, metrics_port).await {
+            eprintln!("Metrics endpoint failed: {}", e);
+        }
+    });
+
+    if is_broker {
+        println!("📬 Message Broker listening on {}", addr);
+
+        Server::builder()
+            .add_service(BrokerServer::new(MessageBroker::new(service)))
+            .serve(addr)
+            .await?;
+        return Ok(());
+    }
 
     println!("🚀 Generic Service listening on {}", addr);
 
+    // Apply edits of the config file without a restart
+    let runtime = service.runtime.clone();
+    tokio::spawn(async move {
+        if let Err(e) = admin::watch_config_file(runtime, config_path()).await {
+            eprintln!("Config file watch failed: {}", e);
+        }
+    });
+
+    let admin = AdminService::new(service.runtime.clone());
     Server::builder()
         .add_service(ServiceServer::new(service))
+        .add_service(AdminServer::new(admin))
         .serve(addr)
         .await?;
 
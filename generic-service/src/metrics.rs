@@ -0,0 +1,165 @@
+use axum::{routing::get, Router};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Prometheus metrics of one generic service, labelled by method, callee and callee replica
+pub struct Metrics {
+    registry: Registry,
+    pub requests: IntCounterVec,
+    pub errors: IntCounterVec,
+    pub in_flight: IntGaugeVec,
+    pub queued: IntGaugeVec,
+    pub latency: HistogramVec,
+    pub downstream_calls: IntCounterVec,
+    pub downstream_errors: IntCounterVec,
+    pub downstream_latency: HistogramVec,
+    pub broker_queue_length: IntGaugeVec,
+    pub broker_dropped_messages: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new(service_name: &str) -> Self {
+        let registry = Registry::new_custom(
+            Some("generic_service".to_string()),
+            Some(HashMap::from([(
+                "service".to_string(),
+                service_name.to_string(),
+            )])),
+        )
+        .expect("Failed to create metrics registry");
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Requests handled, by method"),
+            &["method"],
+        )
+        .unwrap();
+        let errors = IntCounterVec::new(
+            Opts::new("errors_total", "Requests answered with an error, by method"),
+            &["method"],
+        )
+        .unwrap();
+        let in_flight = IntGaugeVec::new(
+            Opts::new("in_flight_requests", "Requests currently being handled"),
+            &["method"],
+        )
+        .unwrap();
+        let queued = IntGaugeVec::new(
+            Opts::new(
+                "queued_requests",
+                "Requests waiting for a free worker when concurrency is limited",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "request_duration_seconds",
+                "Time from receiving a request to answering it",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let downstream_calls = IntCounterVec::new(
+            Opts::new(
+                "downstream_calls_total",
+                "Call attempts made to other services, by callee and replica",
+            ),
+            &["callee", "replica"],
+        )
+        .unwrap();
+        let downstream_errors = IntCounterVec::new(
+            Opts::new(
+                "downstream_errors_total",
+                "Failed call attempts to other services, by callee and replica",
+            ),
+            &["callee", "replica"],
+        )
+        .unwrap();
+        let downstream_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "downstream_duration_seconds",
+                "Duration of call attempts to other services, by callee and replica",
+            ),
+            &["callee", "replica"],
+        )
+        .unwrap();
+        let broker_queue_length = IntGaugeVec::new(
+            Opts::new(
+                "broker_queue_length",
+                "Messages waiting on the broker, by topic",
+            ),
+            &["topic"],
+        )
+        .unwrap();
+        let broker_dropped_messages = IntCounterVec::new(
+            Opts::new(
+                "broker_dropped_messages_total",
+                "Messages given up on after every delivery attempt failed, by topic",
+            ),
+            &["topic"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(in_flight.clone())).unwrap();
+        registry.register(Box::new(queued.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry
+            .register(Box::new(downstream_calls.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(downstream_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(downstream_latency.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(broker_queue_length.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(broker_dropped_messages.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            errors,
+            in_flight,
+            queued,
+            latency,
+            downstream_calls,
+            downstream_errors,
+            downstream_latency,
+            broker_queue_length,
+            broker_dropped_messages,
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Failed to encode metrics");
+        String::from_utf8(buffer).expect("Metrics are not valid UTF-8")
+    }
+}
+
+/// Serve `GET /metrics` on the given port for Prometheus to scrape, along with `routes`
+pub async fn serve_metrics(
+    metrics: Arc<Metrics>,
+    routes: Router,
+    port: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let app = Router::new()
+        .route("/metrics", get(move || async move { metrics.render() }))
+        .merge(routes);
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+    println!("📈 Metrics available on {}/metrics", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
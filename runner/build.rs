@@ -1,5 +1,12 @@
 // In build.rs
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/sim.proto")?;
+    // Client for the generic services, used to drive load at the entry points
+    tonic_build::configure()
+        .build_server(false)
+        .compile_protos(
+            &["../generic-service/proto/service.proto"],
+            &["../generic-service/proto"],
+        )?;
     Ok(())
 }
@@ -0,0 +1,139 @@
+use rand::Rng;
+use rand_distr::{Distribution as _, Exp, Normal, Uniform};
+use std::f64::consts::PI;
+
+use crate::parser::Distribution;
+
+/// Mean delay in milliseconds the generic service sleeps for a latency distribution.
+///
+/// Negative samples sleep for zero, so the normal distribution is treated as truncated at 0.
+pub fn mean_latency_ms(distribution: &Distribution) -> f64 {
+    let parameter = |name: &str| {
+        distribution
+            .parameters
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    };
+    match distribution.distribution_type.as_str() {
+        "normal" => {
+            let (mean, stddev) = (parameter("mean"), parameter("stddev"));
+            if stddev <= 0.0 {
+                return mean.max(0.0);
+            }
+            let z = mean / stddev;
+            mean * normal_cdf(z) + stddev * normal_pdf(z)
+        }
+        "uniform" => (parameter("min") + parameter("max")) / 2.0,
+        "constant" => parameter("value"),
+        "exponential" => 1.0 / parameter("rate"),
+        "empirical" => empirical_quantiles(distribution)
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0) * (pair[0].1 + pair[1].1) / 2.0)
+            .sum(),
+        _ => 0.0,
+    }
+}
+
+/// Quantile points `(fraction, value)` of an empirical distribution, sorted by fraction.
+///
+/// Parameters are named after percentiles, e.g. `p0`, `p50`, `p99.9` and `p100`, and the
+/// distribution is linear between them.
+pub fn empirical_quantiles(distribution: &Distribution) -> Vec<(f64, f64)> {
+    let mut quantiles: Vec<(f64, f64)> = distribution
+        .parameters
+        .iter()
+        .filter_map(|(name, value)| {
+            let percent: f64 = name.strip_prefix('p')?.parse().ok()?;
+            Some((percent / 100.0, *value))
+        })
+        .collect();
+    quantiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+    quantiles
+}
+
+/// Value of a piecewise linear quantile function at `fraction`
+pub fn interpolate_quantile(quantiles: &[(f64, f64)], fraction: f64) -> f64 {
+    let upper = quantiles.partition_point(|(q, _)| *q < fraction);
+    match (
+        upper.checked_sub(1).map(|i| quantiles[i]),
+        quantiles.get(upper),
+    ) {
+        (Some((q0, v0)), Some(&(q1, v1))) if q1 > q0 => {
+            v0 + (v1 - v0) * (fraction - q0) / (q1 - q0)
+        }
+        (_, Some(&(_, value))) | (Some((_, value)), None) => value,
+        (None, None) => 0.0,
+    }
+}
+
+/// Draw one delay in milliseconds the way the generic service does: rounded, never negative
+pub fn sample_latency_ms<R: Rng + ?Sized>(distribution: &Distribution, rng: &mut R) -> f64 {
+    let parameter = |name: &str| {
+        distribution
+            .parameters
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    };
+    let sample = match distribution.distribution_type.as_str() {
+        "normal" => Normal::new(parameter("mean"), parameter("stddev"))
+            .map_or(parameter("mean"), |normal| normal.sample(rng)),
+        "uniform" => Uniform::new_inclusive(parameter("min"), parameter("max"))
+            .map_or(parameter("min"), |uniform| uniform.sample(rng)),
+        "constant" => parameter("value"),
+        "exponential" => Exp::new(parameter("rate")).map_or(0.0, |exp| exp.sample(rng)),
+        "empirical" => interpolate_quantile(&empirical_quantiles(distribution), rng.random()),
+        _ => 0.0,
+    };
+    sample.round().max(0.0)
+}
+
+/// Probability that a single invocation of a method fails
+pub fn error_probability(error_rate: Option<&Distribution>) -> f64 {
+    error_rate
+        .filter(|distribution| distribution.distribution_type == "bernoulli")
+        .and_then(|distribution| distribution.parameters.get("p"))
+        .map_or(0.0, |p| p.clamp(0.0, 1.0))
+}
+
+/// Expected attempts until a call succeeds, since callers retry failed calls
+pub fn expected_attempts(error_probability: f64) -> f64 {
+    if error_probability >= 1.0 {
+        f64::INFINITY
+    } else {
+        1.0 / (1.0 - error_probability)
+    }
+}
+
+/// Deliveries the broker makes of a message before dropping it, as in the generic service
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 8;
+
+/// Expected deliveries of an async message, since the broker redelivers failed messages
+/// until one succeeds or `MAX_DELIVERY_ATTEMPTS` have been made
+pub fn expected_deliveries(error_probability: f64) -> f64 {
+    if error_probability >= 1.0 {
+        MAX_DELIVERY_ATTEMPTS as f64
+    } else {
+        (1.0 - error_probability.powi(MAX_DELIVERY_ATTEMPTS as i32)) / (1.0 - error_probability)
+    }
+}
+
+pub fn normal_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * PI).sqrt()
+}
+
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / 2.0_f64.sqrt()))
+}
+
+/// Abramowitz and Stegun 7.1.26, accurate to about 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    if x >= 0.0 { value } else { -value }
+}
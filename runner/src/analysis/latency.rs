@@ -0,0 +1,148 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::distribution::{error_probability, sample_latency_ms};
+use crate::metrics::percentile;
+use crate::parser::{Distribution, SimulatorConfig};
+use crate::validator::rules::{split_call, topological_methods};
+
+/// Stage rounds after which a call that keeps failing is given up on
+const MAX_ROUNDS: usize = 1000;
+
+/// Estimated end-to-end latency distribution of one entry point
+#[derive(Debug, Serialize)]
+pub struct LatencyEstimate {
+    pub entry_point: String,
+    pub samples: usize,
+    /// Probability the entry point itself answers with an error
+    pub error_probability: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+}
+
+/// A method with its calls resolved to indices, so sampling needs no lookups
+struct SimulatedMethod<'a> {
+    latency: &'a Distribution,
+    error_probability: f64,
+    stages: Vec<Vec<usize>>,
+    max_recursion_depth: Option<u32>,
+}
+
+/// Estimate each entry point's latency distribution by Monte Carlo simulation.
+///
+/// Each sample replays what the generic service does: sleep for the method's own
+/// latency, stop there if it errors, otherwise run each stage's calls in parallel and
+/// retry the failed ones in rounds until all succeed. Async calls add no latency, and a
+/// recursive method makes no calls once nested `max_recursion_depth` times.
+pub fn estimate_latencies(
+    config: &SimulatorConfig,
+    samples: usize,
+    seed: u64,
+) -> Vec<LatencyEstimate> {
+    let order = topological_methods(config);
+    let index: HashMap<(&str, &str), usize> = order
+        .iter()
+        .enumerate()
+        .map(|(i, (service, method))| ((service.as_str(), method.as_str()), i))
+        .collect();
+    let methods: Vec<SimulatedMethod> = order
+        .iter()
+        .map(|(service, method)| {
+            let method = &config.services[service].methods[method];
+            SimulatedMethod {
+                latency: &method.latency_distribution,
+                error_probability: error_probability(method.error_rate.as_ref()),
+                stages: method
+                    .calls
+                    .iter()
+                    .map(|stage| {
+                        stage
+                            .iter()
+                            .map(|call| index[&split_call(call).expect("validated call")])
+                            .collect()
+                    })
+                    .collect(),
+                max_recursion_depth: method.max_recursion_depth,
+            }
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let entry_points = config.load.iter().flat_map(|load| &load.entry_points);
+    entry_points
+        .map(|entry_point| {
+            let root = index[&(entry_point.service.as_str(), entry_point.method.as_str())];
+            let mut errors = 0;
+            let mut depths = vec![0; methods.len()];
+            let mut latencies: Vec<f64> = (0..samples)
+                .map(|_| {
+                    let (latency, failed) = sample_method(&methods, root, &mut depths, &mut rng);
+                    if failed {
+                        errors += 1;
+                    }
+                    latency
+                })
+                .collect();
+            latencies.sort_by(f64::total_cmp);
+
+            LatencyEstimate {
+                entry_point: format!("{}.{}", entry_point.service, entry_point.method),
+                samples,
+                error_probability: errors as f64 / samples.max(1) as f64,
+                mean_ms: latencies.iter().sum::<f64>() / samples.max(1) as f64,
+                p50_ms: percentile(&latencies, 0.50),
+                p90_ms: percentile(&latencies, 0.90),
+                p95_ms: percentile(&latencies, 0.95),
+                p99_ms: percentile(&latencies, 0.99),
+                p999_ms: percentile(&latencies, 0.999),
+            }
+        })
+        .collect()
+}
+
+/// Sample one invocation, returning its latency and whether it answered with an error
+fn sample_method<R: Rng>(
+    methods: &[SimulatedMethod],
+    index: usize,
+    depths: &mut [u32],
+    rng: &mut R,
+) -> (f64, bool) {
+    let method = &methods[index];
+    let mut latency = sample_latency_ms(method.latency, rng);
+    if rng.random_bool(method.error_probability) {
+        return (latency, true);
+    }
+    if method
+        .max_recursion_depth
+        .is_some_and(|limit| depths[index] >= limit)
+    {
+        return (latency, false);
+    }
+
+    // How many invocations of each method are on the current call path
+    depths[index] += 1;
+    for stage in &method.stages {
+        let mut pending: Vec<usize> = stage.clone();
+        for _ in 0..MAX_ROUNDS {
+            if pending.is_empty() {
+                break;
+            }
+            let mut round_ms: f64 = 0.0;
+            pending.retain(|&callee| {
+                let (call_ms, failed) = sample_method(methods, callee, depths, rng);
+                round_ms = round_ms.max(call_ms);
+                failed
+            });
+            latency += round_ms;
+        }
+    }
+    depths[index] -= 1;
+
+    (latency, false)
+}
@@ -0,0 +1,252 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::distribution::{
+    error_probability, expected_attempts, expected_deliveries, mean_latency_ms,
+};
+use crate::parser::SimulatorConfig;
+use crate::validator::rules::{split_call, topological_methods};
+
+/// Utilization above which a service is flagged as likely to saturate
+pub const DEFAULT_SATURATION_THRESHOLD: f64 = 0.8;
+
+/// Expected steady-state load on every service, derived from the config alone
+#[derive(Debug, Serialize)]
+pub struct LoadPlan {
+    pub methods: Vec<MethodLoad>,
+    pub services: Vec<ServiceLoad>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodLoad {
+    pub service: String,
+    pub method: String,
+    /// Requests arriving per second, retries included
+    pub arrival_rps: f64,
+    pub error_probability: f64,
+    /// Mean time spent in the method's own latency
+    pub mean_latency_ms: f64,
+    /// Mean time a request occupies the service, waiting on downstream calls included
+    pub holding_time_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceLoad {
+    pub service: String,
+    pub arrival_rps: f64,
+    /// Seconds of own latency per second, i.e. the average number of requests doing work
+    pub busy_time: f64,
+    /// Average number of requests held at once (Little's law on the holding time)
+    pub concurrent_requests: f64,
+    /// Workers of all replicas together
+    pub concurrency: Option<u32>,
+    /// `concurrent_requests / concurrency`, when the service's concurrency is limited; assumes
+    /// calls spread evenly over identical replicas
+    pub utilization: Option<f64>,
+    pub status: CapacityStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapacityStatus {
+    /// No concurrency limit, so requests never queue
+    Unlimited,
+    Ok,
+    NearCapacity,
+    Saturated,
+}
+
+/// Propagate entry point rates through the call graph and estimate service capacity.
+///
+/// A request that errors makes no downstream calls, and callers retry each call until it
+/// succeeds, so a call to a method failing with probability p arrives 1 / (1 - p) times.
+/// Async calls are redelivered by the broker at most `MAX_DELIVERY_ATTEMPTS` (n) times, so
+/// they arrive (1 - p^n) / (1 - p) times.
+/// Holding times take the slowest expected call of each stage, which is a lower bound, and
+/// recursive calls are followed only once, which underestimates recursive configs.
+pub fn plan_load(config: &SimulatorConfig, saturation_threshold: f64) -> LoadPlan {
+    let order = topological_methods(config);
+
+    // Callees before callers: how long an invocation holds the service
+    let mut holding_ms: HashMap<(String, String), f64> = HashMap::new();
+    for (service_name, method_name) in order.iter().rev() {
+        let method = &config.services[service_name].methods[method_name];
+        let call_time = |call: &String| {
+            let (callee_service, callee_method) = split_call(call).expect("validated call");
+            let callee = &config.services[callee_service].methods[callee_method];
+            let p = error_probability(callee.error_rate.as_ref());
+            let retries = expected_attempts(p) - 1.0;
+            if retries.is_infinite() {
+                return f64::INFINITY;
+            }
+            // A recursive call back up the order has no holding time yet
+            let holding = holding_ms
+                .get(&(callee_service.to_string(), callee_method.to_string()))
+                .copied()
+                .unwrap_or_default();
+            // Failed attempts only take the callee's own latency
+            retries * mean_latency_ms(&callee.latency_distribution) + holding
+        };
+        let waiting_ms: f64 = method
+            .calls
+            .iter()
+            .map(|stage| stage.iter().map(call_time).fold(0.0, f64::max))
+            .sum();
+        let p = error_probability(method.error_rate.as_ref());
+        holding_ms.insert(
+            (service_name.clone(), method_name.clone()),
+            mean_latency_ms(&method.latency_distribution) + (1.0 - p) * waiting_ms,
+        );
+    }
+
+    // Callers before callees: how often each method is invoked
+    let mut arrival_rps: HashMap<(String, String), f64> = HashMap::new();
+    if let Some(load) = &config.load {
+        for entry_point in &load.entry_points {
+            *arrival_rps
+                .entry((entry_point.service.clone(), entry_point.method.clone()))
+                .or_default() += entry_point.requests_per_second as f64;
+        }
+    }
+    for (service_name, method_name) in &order {
+        let rate = arrival_rps
+            .get(&(service_name.clone(), method_name.clone()))
+            .copied()
+            .unwrap_or_default();
+        if rate == 0.0 {
+            continue;
+        }
+        let method = &config.services[service_name].methods[method_name];
+        let succeeding_rate = rate * (1.0 - error_probability(method.error_rate.as_ref()));
+        if succeeding_rate == 0.0 {
+            continue;
+        }
+        let sync_calls = method.calls.iter().flatten().map(|call| (call, false));
+        let async_calls = method.async_calls.iter().map(|call| (call, true));
+        for (call, is_async) in sync_calls.chain(async_calls) {
+            let (callee_service, callee_method) = split_call(call).expect("validated call");
+            let callee = &config.services[callee_service].methods[callee_method];
+            let p = error_probability(callee.error_rate.as_ref());
+            let attempts = if is_async {
+                expected_deliveries(p)
+            } else {
+                expected_attempts(p)
+            };
+            *arrival_rps
+                .entry((callee_service.to_string(), callee_method.to_string()))
+                .or_default() += succeeding_rate * attempts;
+        }
+    }
+
+    let mut methods: Vec<MethodLoad> = order
+        .iter()
+        .map(|(service_name, method_name)| {
+            let key = (service_name.clone(), method_name.clone());
+            let method = &config.services[service_name].methods[method_name];
+            MethodLoad {
+                service: service_name.clone(),
+                method: method_name.clone(),
+                arrival_rps: arrival_rps.get(&key).copied().unwrap_or_default(),
+                error_probability: error_probability(method.error_rate.as_ref()),
+                mean_latency_ms: mean_latency_ms(&method.latency_distribution),
+                holding_time_ms: holding_ms[&key],
+            }
+        })
+        .collect();
+    methods.sort_by(|a, b| (&a.service, &a.method).cmp(&(&b.service, &b.method)));
+
+    let mut services: Vec<ServiceLoad> = config
+        .services
+        .iter()
+        .map(|(service_name, service)| {
+            let service_methods = methods.iter().filter(|m| &m.service == service_name);
+            let (mut arrival, mut busy, mut concurrent) = (0.0, 0.0, 0.0);
+            for method in service_methods.filter(|m| m.arrival_rps > 0.0) {
+                arrival += method.arrival_rps;
+                busy += method.arrival_rps * method.mean_latency_ms / 1000.0;
+                concurrent += method.arrival_rps * method.holding_time_ms / 1000.0;
+            }
+            let concurrency = service
+                .concurrency
+                .map(|limit| limit.saturating_mul(service.replica_count()));
+            let utilization = concurrency.map(|limit| concurrent / limit as f64);
+            let status = match utilization {
+                None if arrival.is_infinite() => CapacityStatus::Saturated,
+                None => CapacityStatus::Unlimited,
+                Some(u) if u >= 1.0 => CapacityStatus::Saturated,
+                Some(u) if u >= saturation_threshold => CapacityStatus::NearCapacity,
+                Some(_) => CapacityStatus::Ok,
+            };
+            ServiceLoad {
+                service: service_name.clone(),
+                arrival_rps: arrival,
+                busy_time: busy,
+                concurrent_requests: concurrent,
+                concurrency,
+                utilization,
+                status,
+            }
+        })
+        .collect();
+    services.sort_by(|a, b| a.service.cmp(&b.service));
+
+    LoadPlan { methods, services }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `front.get` at 10 rps calling `back.get`, failing with probability `p`, sync or async
+    fn config(is_async: bool, p: f64) -> SimulatorConfig {
+        let (calls, async_calls) = if is_async {
+            (serde_json::json!([]), serde_json::json!(["back.get"]))
+        } else {
+            (serde_json::json!([["back.get"]]), serde_json::json!([]))
+        };
+        serde_json::from_value(serde_json::json!({
+            "services": {
+                "front": {"port": 50051, "methods": {"get": {
+                    "calls": calls,
+                    "async_calls": async_calls,
+                    "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+                }}},
+                "back": {"port": 50052, "methods": {"get": {
+                    "calls": [],
+                    "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+                    "error_rate": {"type": "bernoulli", "parameters": {"p": p}},
+                }}},
+            },
+            "load": {"entry_points": [
+                {"service": "front", "method": "get", "requests_per_second": 10},
+            ]},
+        }))
+        .unwrap()
+    }
+
+    fn back_rps(plan: &LoadPlan) -> f64 {
+        plan.methods
+            .iter()
+            .find(|method| method.service == "back")
+            .unwrap()
+            .arrival_rps
+    }
+
+    #[test]
+    fn failed_calls_arrive_again() {
+        let plan = plan_load(&config(false, 0.5), DEFAULT_SATURATION_THRESHOLD);
+        assert_eq!(back_rps(&plan), 20.0);
+    }
+
+    #[test]
+    fn async_deliveries_stop_at_the_broker_limit() {
+        let plan = plan_load(&config(true, 1.0), DEFAULT_SATURATION_THRESHOLD);
+        assert_eq!(back_rps(&plan), 80.0);
+    }
+
+    #[test]
+    fn sync_calls_retry_until_they_succeed() {
+        let plan = plan_load(&config(false, 1.0), DEFAULT_SATURATION_THRESHOLD);
+        assert!(back_rps(&plan).is_infinite());
+    }
+}
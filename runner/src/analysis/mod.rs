@@ -0,0 +1,3 @@
+pub mod distribution;
+pub mod latency;
+pub mod load;
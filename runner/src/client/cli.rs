@@ -1,6 +1,12 @@
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+use crate::generator::graph::GraphFormat;
+use crate::generator::topology::FanOutDistribution;
+use crate::importer::TraceFormat;
+use crate::parser::ConfigFormat;
+use crate::parser::variables::parse_assignment;
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "microservice-simulator-parser",
@@ -8,12 +14,245 @@ use structopt::StructOpt;
 )]
 pub struct CliOptions {
     #[structopt(short, long, parse(from_os_str))]
-    /// Path to the input JSON file
-    pub input: PathBuf,
+    /// Path to the input config file (JSON, YAML or TOML, by extension)
+    pub input: Option<PathBuf>,
 
     #[structopt(short, long, default_value = "localhost:50051")]
     /// Address of the orchestrator service
     pub orchestrator: String,
+
+    #[structopt(
+        long = "set",
+        global = true,
+        number_of_values = 1,
+        parse(try_from_str = parse_assignment)
+    )]
+    /// Value of a `${NAME}` variable in config files as NAME=value, overriding the environment
+    pub variables: Vec<(String, String)>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Check a config and list every problem found
+    Validate {
+        #[structopt(parse(from_os_str))]
+        /// Path to the input config file (JSON, YAML or TOML, by extension)
+        input: PathBuf,
+
+        #[structopt(long)]
+        /// Print the diagnostics as JSON
+        json: bool,
+    },
+    /// Estimate the load and capacity of every service without launching anything
+    #[structopt(alias = "analyze")]
+    Plan {
+        #[structopt(parse(from_os_str))]
+        /// Path to the input config file (JSON, YAML or TOML, by extension)
+        input: PathBuf,
+
+        #[structopt(long, default_value = "0.8")]
+        /// Utilization above which a service is flagged as likely to saturate
+        threshold: f64,
+
+        #[structopt(long)]
+        /// Print the plan as JSON instead of a table
+        json: bool,
+    },
+    /// Estimate the end-to-end latency distribution of every entry point
+    Estimate {
+        #[structopt(parse(from_os_str))]
+        /// Path to the input config file (JSON, YAML or TOML, by extension)
+        input: PathBuf,
+
+        #[structopt(long, default_value = "10000")]
+        /// Number of simulated requests per entry point
+        samples: usize,
+
+        #[structopt(long, default_value = "0")]
+        /// Seed for the random number generator
+        seed: u64,
+
+        #[structopt(long)]
+        /// Print the estimates as JSON instead of a table
+        json: bool,
+    },
+    /// Export the method-level call graph as Graphviz DOT or Mermaid
+    Graph {
+        #[structopt(parse(from_os_str))]
+        /// Path to the input config file (JSON, YAML or TOML, by extension)
+        input: PathBuf,
+
+        #[structopt(short, long, default_value = "dot")]
+        /// Output format: dot or mermaid
+        format: GraphFormat,
+
+        #[structopt(long)]
+        /// Annotate methods with expected RPS, mean latency and error rate
+        annotate: bool,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Write the graph to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Run a base config once per combination of swept parameters
+    Experiment {
+        #[structopt(parse(from_os_str))]
+        /// Path to the experiment file (JSON, YAML or TOML, by extension)
+        spec: PathBuf,
+
+        #[structopt(long)]
+        /// List and validate the combinations without running them
+        dry_run: bool,
+    },
+    /// Compare the artifacts of two runs and fail on regressions
+    Compare {
+        #[structopt(parse(from_os_str))]
+        /// Artifact directory of the baseline run, e.g. runs/sim-1700000000000
+        baseline: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        /// Artifact directory of the candidate run
+        candidate: PathBuf,
+
+        #[structopt(long, default_value = "0.1")]
+        /// Largest tolerated relative increase of p50, p90 or p99 latency
+        max_latency_increase: f64,
+
+        #[structopt(long, default_value = "0.1")]
+        /// Largest tolerated relative decrease of throughput
+        max_throughput_decrease: f64,
+
+        #[structopt(long, default_value = "0.01")]
+        /// Largest tolerated absolute increase of the error ratio
+        max_error_rate_increase: f64,
+
+        #[structopt(long, default_value = "0.05")]
+        /// Significance level latency and error changes must reach to count
+        alpha: f64,
+
+        #[structopt(long)]
+        /// Print the comparison as JSON instead of a table
+        json: bool,
+    },
+    /// Check the SLOs of a finished run against its artifacts
+    Slo {
+        #[structopt(parse(from_os_str))]
+        /// Artifact directory of the run, e.g. runs/sim-1700000000000
+        run_dir: PathBuf,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Config file whose `slos` replace those the run was started with
+        config: Option<PathBuf>,
+
+        #[structopt(long)]
+        /// Print the results as JSON instead of a table
+        json: bool,
+    },
+    /// Print a config with its includes, variables and templates resolved
+    Render {
+        #[structopt(parse(from_os_str))]
+        /// Path to the input config file (JSON, YAML or TOML, by extension)
+        input: PathBuf,
+
+        #[structopt(short, long, default_value = "json")]
+        /// Output format: json, yaml or toml
+        format: ConfigFormat,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Write the config to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Build a config from recorded traces
+    Import {
+        #[structopt(required = true, parse(from_os_str))]
+        /// Trace files to read
+        inputs: Vec<PathBuf>,
+
+        #[structopt(short, long, default_value = "alibaba")]
+        /// Trace format: alibaba (MSCallGraph CSV), jaeger (JSON export) or otlp (OTLP/JSON)
+        format: TraceFormat,
+
+        #[structopt(long, default_value = "1.0")]
+        /// Factor applied to the entry point rates seen in the traces
+        rate_scale: f64,
+
+        #[structopt(long)]
+        /// Seconds the traces cover, when not derivable from their timestamps
+        duration: Option<f64>,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Write the config to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Generate a random large-scale topology
+    Generate {
+        #[structopt(long, default_value = "50")]
+        /// Number of services
+        services: usize,
+
+        #[structopt(long, default_value = "4")]
+        /// Number of service layers, storage included
+        depth: usize,
+
+        #[structopt(long, default_value = "3")]
+        /// Maximum number of methods per service
+        methods: usize,
+
+        #[structopt(long, default_value = "poisson")]
+        /// Distribution of calls per method: constant, uniform, poisson or geometric
+        fan_out: FanOutDistribution,
+
+        #[structopt(long, default_value = "2.0")]
+        /// Mean number of calls per method
+        fan_out_mean: f64,
+
+        #[structopt(long, default_value = "8")]
+        /// Maximum number of calls per method
+        max_fan_out: usize,
+
+        #[structopt(long, default_value = "1.0")]
+        /// Preferential attachment exponent within [0, 10]; 0 picks callees uniformly
+        preferential_exponent: f64,
+
+        #[structopt(long, default_value = "0.2")]
+        /// Share of services that are leaf storage services
+        storage_share: f64,
+
+        #[structopt(long, default_value = "5.0")]
+        /// Lower bound of method mean latencies in ms
+        latency_min: f64,
+
+        #[structopt(long, default_value = "50.0")]
+        /// Upper bound of method mean latencies in ms
+        latency_max: f64,
+
+        #[structopt(long, default_value = "0.25")]
+        /// Latency standard deviation relative to the mean
+        latency_cv: f64,
+
+        #[structopt(long, default_value = "0.0")]
+        /// Lower bound of method error rates
+        error_min: f64,
+
+        #[structopt(long, default_value = "0.01")]
+        /// Upper bound of method error rates
+        error_max: f64,
+
+        #[structopt(long, default_value = "10")]
+        /// Requests per second sent to each frontend method
+        rps: u32,
+
+        #[structopt(long, default_value = "0")]
+        /// Seed for the random number generator
+        seed: u64,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Write the config to this file instead of stdout
+        output: Option<PathBuf>,
+    },
 }
 
 pub fn parse_cli_args() -> CliOptions {
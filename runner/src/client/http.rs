@@ -9,7 +9,7 @@ pub async fn submit_config_via_http(server_addr: &str, json_str: String) -> Resu
 
     // Prepare the request
     let response = client
-        .post(&format!("http://{}/submit", server_addr))
+        .post(format!("http://{}/submit", server_addr))
         .header("Content-Type", "application/json")
         .body(json_str)
         .send()
@@ -0,0 +1,523 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+
+use crate::loadgen::now_ms;
+use crate::metrics::{LiveMetrics, SeriesStats};
+use crate::orchestrator::launch_simulation_from_yaml;
+use crate::parser::{self, SimulatorConfig, include};
+use crate::report::RUNS_DIR;
+use crate::validator;
+
+const REPORT_JSON_FILE: &str = "experiment.json";
+const REPORT_CSV_FILE: &str = "experiment.csv";
+/// Most combinations an experiment may sweep, each being a full deployment and run
+const MAX_COMBINATIONS: usize = 10_000;
+
+/// A base config and the parameters to sweep over it, as written in an experiment file
+#[derive(Debug, Deserialize)]
+pub struct ExperimentSpec {
+    /// Config file every combination starts from, relative to the experiment file
+    pub base: PathBuf,
+    /// Length of every run, overriding `load.duration_seconds` of the base config
+    #[serde(default)]
+    pub duration_seconds: Option<u64>,
+    pub parameters: Vec<SweptParameter>,
+}
+
+/// A config field and the values it takes, either listed or as a range
+#[derive(Debug, Deserialize)]
+pub struct SweptParameter {
+    /// JSON pointer into the config with templates resolved, as `render` prints it,
+    /// e.g. "/load/entry_points/0/requests_per_second"
+    pub path: String,
+    #[serde(default)]
+    pub values: Vec<Value>,
+    #[serde(default)]
+    pub range: Option<ValueRange>,
+}
+
+/// Values from `start` to `end` inclusive, `step` apart
+#[derive(Debug, Deserialize)]
+pub struct ValueRange {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+impl SweptParameter {
+    fn expand(&self) -> Result<Vec<Value>> {
+        let values = match (&self.range, self.values.is_empty()) {
+            (None, false) => self.values.clone(),
+            (Some(range), true) => range.expand()?,
+            (None, true) => bail!("Parameter {} needs 'values' or a 'range'", self.path),
+            (Some(_), false) => bail!(
+                "Parameter {} has both 'values' and a 'range', expected one",
+                self.path
+            ),
+        };
+        if values.is_empty() {
+            bail!("Parameter {} has no values", self.path);
+        }
+        Ok(values)
+    }
+}
+
+impl ValueRange {
+    fn expand(&self) -> Result<Vec<Value>> {
+        if self.step.is_nan()
+            || self.step <= 0.0
+            || !self.start.is_finite()
+            || !self.end.is_finite()
+        {
+            bail!(
+                "Range {}..{} needs finite bounds and a positive step, found step {}",
+                self.start,
+                self.end,
+                self.step
+            );
+        }
+        // Integer ranges stay integers, so they fit integer fields such as requests_per_second
+        let integral = [self.start, self.end, self.step]
+            .iter()
+            .all(|bound| bound.fract() == 0.0);
+        let count = ((self.end - self.start) / self.step + 1e-9).floor() + 1.0;
+        if count > MAX_COMBINATIONS as f64 {
+            bail!(
+                "Range {}..{} with step {} has {} values, more than the limit of {}",
+                self.start,
+                self.end,
+                self.step,
+                count,
+                MAX_COMBINATIONS
+            );
+        }
+        Ok((0..count.max(0.0) as i64)
+            .map(|i| {
+                let value = self.start + i as f64 * self.step;
+                if integral {
+                    Value::from(value as i64)
+                } else {
+                    Value::from(value)
+                }
+            })
+            .collect())
+    }
+}
+
+/// One point of the sweep: the swept values and the config they produce
+#[derive(Debug)]
+pub struct Combination {
+    pub parameters: Vec<(String, Value)>,
+    pub config: SimulatorConfig,
+}
+
+/// Load an experiment file and build the validated config of every combination, the first
+/// parameter varying slowest
+pub fn load_experiment(
+    path: &Path,
+    variables: &HashMap<String, String>,
+) -> Result<Vec<Combination>> {
+    let spec: ExperimentSpec = serde_json::from_value(include::load_document(path, variables)?)
+        .with_context(|| format!("Invalid experiment file: {}", path.display()))?;
+    if spec.parameters.is_empty() {
+        bail!("Experiment {} sweeps no parameters", path.display());
+    }
+
+    let base_path = path.parent().unwrap_or(Path::new(".")).join(&spec.base);
+    let base = parser::parse_config_file(&base_path, variables)?;
+    let Some(load) = &base.load else {
+        bail!("Base config {} has no load to run", base_path.display());
+    };
+    if spec.duration_seconds.or(load.duration_seconds).is_none() {
+        bail!(
+            "Every run needs a duration: set duration_seconds in the experiment or load.duration_seconds in {}",
+            base_path.display()
+        );
+    }
+    let base = serde_json::to_value(&base)?;
+
+    let swept = expand_parameters(&spec.parameters)?;
+    let mut combinations = Vec::new();
+    for values in cartesian_product(&swept) {
+        let mut document = base.clone();
+        let parameters: Vec<(String, Value)> = spec
+            .parameters
+            .iter()
+            .zip(values)
+            .map(|(parameter, value)| (parameter.path.clone(), value))
+            .collect();
+        for (pointer, value) in &parameters {
+            set_pointer(&mut document, pointer, value.clone())?;
+        }
+
+        let index = combinations.len();
+        let mut config: SimulatorConfig = serde_json::from_value(document)
+            .with_context(|| format!("Combination {} is not a valid config", index))?;
+        if let (Some(duration), Some(load)) = (spec.duration_seconds, config.load.as_mut()) {
+            load.duration_seconds = Some(duration);
+        }
+        validator::validate_config(&config)
+            .with_context(|| format!("Combination {} failed validation", index))?;
+        combinations.push(Combination { parameters, config });
+    }
+    Ok(combinations)
+}
+
+/// Values of every parameter, as long as their combinations stay within the limit
+fn expand_parameters(parameters: &[SweptParameter]) -> Result<Vec<Vec<Value>>> {
+    let swept: Vec<Vec<Value>> = parameters
+        .iter()
+        .map(SweptParameter::expand)
+        .collect::<Result<_>>()?;
+    let count = swept
+        .iter()
+        .try_fold(1usize, |count, values| count.checked_mul(values.len()));
+    if count.is_none_or(|count| count > MAX_COMBINATIONS) {
+        bail!(
+            "The parameters have more than {} combinations",
+            MAX_COMBINATIONS
+        );
+    }
+    Ok(swept)
+}
+
+/// Every way of picking one value per list, the last list varying fastest
+fn cartesian_product(lists: &[Vec<Value>]) -> Vec<Vec<Value>> {
+    lists.iter().fold(vec![Vec::new()], |prefixes, list| {
+        prefixes
+            .iter()
+            .flat_map(|prefix| {
+                list.iter().map(move |value| {
+                    let mut combination = prefix.clone();
+                    combination.push(value.clone());
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+/// Set the value at a JSON pointer, adding the last key if its parent object exists. The
+/// empty pointer, which names the whole document, is rejected.
+fn set_pointer(document: &mut Value, pointer: &str, value: Value) -> Result<()> {
+    if !pointer.starts_with('/') {
+        bail!(
+            "Invalid JSON pointer '{}', expected it to start with '/'",
+            pointer
+        );
+    }
+    if let Some(target) = document.pointer_mut(pointer) {
+        *target = value;
+        return Ok(());
+    }
+    let (parent, key) = pointer.rsplit_once('/').expect("pointer starts with '/'");
+    match document.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(key.replace("~1", "/").replace("~0", "~"), value);
+            Ok(())
+        }
+        _ => bail!("Path {} does not exist in the base config", pointer),
+    }
+}
+
+/// Outcome of one combination
+#[derive(Debug, Serialize)]
+pub struct ExperimentRow {
+    pub index: usize,
+    /// Swept value of every parameter, by path
+    pub parameters: BTreeMap<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulation_id: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub throughput_rps: f64,
+    pub error_rate: f64,
+    pub entry_points: Vec<SeriesStats>,
+    /// Why the run failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExperimentReport {
+    pub experiment_id: String,
+    /// Paths of the swept parameters, in the order of the experiment file
+    pub parameters: Vec<String>,
+    pub rows: Vec<ExperimentRow>,
+}
+
+/// Run every combination in turn, each with its own deployment torn down before the next,
+/// and write the combined report to `runs/<experiment_id>`. A failed run is recorded and the
+/// sweep moves on; Ctrl-C stops the current run and the sweep.
+pub async fn run_experiment(
+    combinations: Vec<Combination>,
+    live: Arc<LiveMetrics>,
+) -> Result<(ExperimentReport, PathBuf)> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    });
+
+    let total = combinations.len();
+    let mut report = ExperimentReport {
+        experiment_id: format!("exp-{}", now_ms() as u64),
+        parameters: combinations
+            .first()
+            .map(|combination| {
+                combination
+                    .parameters
+                    .iter()
+                    .map(|(path, _)| path.clone())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        rows: Vec::new(),
+    };
+    for (index, combination) in combinations.into_iter().enumerate() {
+        info!(
+            "Running combination {} of {}: {}",
+            index + 1,
+            total,
+            describe(&combination.parameters)
+        );
+        let mut row = ExperimentRow {
+            index,
+            parameters: combination.parameters.into_iter().collect(),
+            simulation_id: None,
+            requests: 0,
+            errors: 0,
+            throughput_rps: 0.0,
+            error_rate: 0.0,
+            entry_points: Vec::new(),
+            error: None,
+        };
+        match launch_simulation_from_yaml(combination.config, live.clone()).await {
+            Ok(run) => {
+                let entry_points = run.summary.entry_points;
+                row.simulation_id = Some(run.simulation_id);
+                row.requests = entry_points.iter().map(|stats| stats.requests).sum();
+                row.errors = entry_points.iter().map(|stats| stats.errors).sum();
+                row.throughput_rps = entry_points.iter().map(|stats| stats.throughput_rps).sum();
+                row.error_rate = if row.requests == 0 {
+                    0.0
+                } else {
+                    row.errors as f64 / row.requests as f64
+                };
+                row.entry_points = entry_points;
+            }
+            Err(err) => {
+                error!("Combination {} failed: {:#}", index, err);
+                row.error = Some(format!("{:#}", err));
+            }
+        }
+        report.rows.push(row);
+
+        if interrupted.load(Ordering::SeqCst) {
+            warn!(
+                "Experiment interrupted after {} of {} combinations",
+                index + 1,
+                total
+            );
+            break;
+        }
+    }
+
+    let directory = write_experiment_report(&report)?;
+    Ok((report, directory))
+}
+
+/// "path=value" pairs of a combination
+pub fn describe(parameters: &[(String, Value)]) -> String {
+    parameters
+        .iter()
+        .map(|(path, value)| format!("{}={}", path, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write the report as JSON and as a CSV table with one row per combination
+fn write_experiment_report(report: &ExperimentReport) -> Result<PathBuf> {
+    let directory = Path::new(RUNS_DIR).join(&report.experiment_id);
+    fs::create_dir_all(&directory)
+        .with_context(|| format!("Failed to create directory: {:?}", directory))?;
+    fs::write(
+        directory.join(REPORT_JSON_FILE),
+        serde_json::to_string_pretty(report)?,
+    )
+    .with_context(|| format!("Failed to write experiment report to {:?}", directory))?;
+
+    let entry_points: BTreeSet<&str> = report
+        .rows
+        .iter()
+        .flat_map(|row| row.entry_points.iter().map(|stats| stats.name.as_str()))
+        .collect();
+    let mut writer = csv::Writer::from_path(directory.join(REPORT_CSV_FILE))
+        .with_context(|| format!("Failed to write experiment table to {:?}", directory))?;
+
+    let mut header: Vec<String> = vec!["index".to_string()];
+    header.extend(report.parameters.iter().cloned());
+    header.extend(
+        [
+            "simulation_id",
+            "requests",
+            "errors",
+            "throughput_rps",
+            "error_rate",
+        ]
+        .map(String::from),
+    );
+    for name in &entry_points {
+        for column in ["throughput_rps", "error_rate", "p50_ms", "p90_ms", "p99_ms"] {
+            header.push(format!("{} {}", name, column));
+        }
+    }
+    header.push("error".to_string());
+    writer.write_record(&header)?;
+
+    for row in &report.rows {
+        let mut record = vec![row.index.to_string()];
+        record.extend(
+            report
+                .parameters
+                .iter()
+                .map(|path| match row.parameters.get(path) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                    None => String::new(),
+                }),
+        );
+        record.extend([
+            row.simulation_id.clone().unwrap_or_default(),
+            row.requests.to_string(),
+            row.errors.to_string(),
+            row.throughput_rps.to_string(),
+            row.error_rate.to_string(),
+        ]);
+        for name in &entry_points {
+            match row.entry_points.iter().find(|stats| stats.name == *name) {
+                Some(stats) => record.extend([
+                    stats.throughput_rps.to_string(),
+                    stats.error_rate.to_string(),
+                    stats.p50_ms.to_string(),
+                    stats.p90_ms.to_string(),
+                    stats.p99_ms.to_string(),
+                ]),
+                None => record.extend(std::iter::repeat_n(String::new(), 5)),
+            }
+        }
+        record.push(row.error.clone().unwrap_or_default());
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+
+    info!("Experiment report written to {:?}", directory);
+    Ok(directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn range(start: f64, end: f64, step: f64) -> Result<Vec<Value>> {
+        ValueRange { start, end, step }.expand()
+    }
+
+    fn parameter(path: &str, values: Vec<Value>) -> SweptParameter {
+        SweptParameter {
+            path: path.to_string(),
+            values,
+            range: None,
+        }
+    }
+
+    #[test]
+    fn integer_ranges_stay_integers() {
+        assert_eq!(
+            range(10.0, 50.0, 20.0).unwrap(),
+            [json!(10), json!(30), json!(50)]
+        );
+    }
+
+    #[test]
+    fn fractional_ranges_include_their_end() {
+        assert_eq!(
+            range(0.0, 0.1, 0.05).unwrap(),
+            [json!(0.0), json!(0.05), json!(0.1)]
+        );
+    }
+
+    #[test]
+    fn empty_and_invalid_ranges() {
+        assert!(range(5.0, 1.0, 1.0).unwrap().is_empty());
+        assert!(range(0.0, 1.0, 0.0).is_err());
+        assert!(range(0.0, 1.0, f64::NAN).is_err());
+        assert!(range(0.0, f64::INFINITY, 1.0).is_err());
+    }
+
+    #[test]
+    fn huge_ranges_are_rejected_before_allocating() {
+        assert!(range(0.0, 1e12, 1e-6).is_err());
+        assert_eq!(
+            range(1.0, MAX_COMBINATIONS as f64, 1.0).unwrap().len(),
+            MAX_COMBINATIONS
+        );
+    }
+
+    #[test]
+    fn combinations_over_the_limit_are_rejected() {
+        let values: Vec<Value> = (0..1000).map(Value::from).collect();
+        let parameters = [parameter("/a", values.clone()), parameter("/b", values)];
+        assert!(expand_parameters(&parameters).is_err());
+        assert_eq!(expand_parameters(&parameters[..1]).unwrap()[0].len(), 1000);
+    }
+
+    #[test]
+    fn last_parameter_varies_fastest() {
+        let product = cartesian_product(&[vec![json!(1), json!(2)], vec![json!("a"), json!("b")]]);
+        assert_eq!(
+            product,
+            [
+                [json!(1), json!("a")],
+                [json!(1), json!("b")],
+                [json!(2), json!("a")],
+                [json!(2), json!("b")],
+            ]
+        );
+    }
+
+    #[test]
+    fn set_pointer_replaces_and_adds_fields() {
+        let mut document = json!({"load": {"entry_points": [{"requests_per_second": 1}]}});
+        set_pointer(
+            &mut document,
+            "/load/entry_points/0/requests_per_second",
+            json!(50),
+        )
+        .unwrap();
+        set_pointer(&mut document, "/load/duration_seconds", json!(60)).unwrap();
+        assert_eq!(
+            document,
+            json!({"load": {"entry_points": [{"requests_per_second": 50}], "duration_seconds": 60}})
+        );
+        assert!(set_pointer(&mut document, "/missing/field", json!(1)).is_err());
+    }
+
+    #[test]
+    fn set_pointer_rejects_the_whole_document_and_relative_pointers() {
+        let mut document = json!({"load": {}});
+        assert!(set_pointer(&mut document, "", json!(1)).is_err());
+        assert!(set_pointer(&mut document, "load", json!(1)).is_err());
+        assert_eq!(document, json!({"load": {}}));
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::analysis::load::{DEFAULT_SATURATION_THRESHOLD, plan_load};
+use crate::parser::SimulatorConfig;
+use crate::validator::rules::split_call;
+
+/// Node standing in for the load generator in front of the entry points
+const CLIENT_NODE: &str = "client";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            other => bail!(
+                "Unknown graph format '{}', expected 'dot' or 'mermaid'",
+                other
+            ),
+        }
+    }
+}
+
+/// Methods of one service, drawn as a subgraph
+struct Cluster<'a> {
+    id: String,
+    label: &'a str,
+    /// Node id and label of every method
+    nodes: Vec<(String, String)>,
+}
+
+/// A directed edge between two graph nodes
+struct Edge {
+    from: String,
+    to: String,
+    label: String,
+    is_async: bool,
+}
+
+/// Render the method-level call graph of a config as Graphviz DOT or Mermaid.
+///
+/// Methods are grouped by service, sync edges are labelled with their stage and how many
+/// calls run in parallel in it, and async edges are dashed. With `annotate`, method nodes
+/// also show the expected arrival rate, mean latency and error rate from the load plan.
+pub fn generate_graph(config: &SimulatorConfig, format: GraphFormat, annotate: bool) -> String {
+    let annotations: HashMap<(String, String), String> = if annotate {
+        plan_load(config, DEFAULT_SATURATION_THRESHOLD)
+            .methods
+            .into_iter()
+            .map(|load| {
+                let annotation = format!(
+                    "{:.1} rps, {:.1} ms, {:.1}% err",
+                    load.arrival_rps,
+                    load.mean_latency_ms,
+                    load.error_probability * 100.0
+                );
+                ((load.service, load.method), annotation)
+            })
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let mut services: Vec<_> = config.services.iter().collect();
+    services.sort_by_key(|(name, _)| name.as_str());
+
+    // Sanitized names can clash, so every id ends in an index of its own
+    let mut node_ids: HashMap<(&str, &str), String> = HashMap::new();
+    for (service_name, service) in &services {
+        let mut methods: Vec<_> = service.methods.keys().collect();
+        methods.sort();
+        for method_name in methods {
+            let id = format!(
+                "{}__{}_{}",
+                sanitize(service_name),
+                sanitize(method_name),
+                node_ids.len()
+            );
+            node_ids.insert((service_name.as_str(), method_name.as_str()), id);
+        }
+    }
+    let node_id = |service: &str, method: &str| node_ids.get(&(service, method)).cloned();
+
+    let mut clusters = Vec::new();
+    let mut edges = Vec::new();
+    for (index, &(service_name, service)) in services.iter().enumerate() {
+        let mut methods: Vec<_> = service.methods.iter().collect();
+        methods.sort_by_key(|(name, _)| name.as_str());
+
+        let mut nodes = Vec::new();
+        for (method_name, method) in methods {
+            let id = node_ids[&(service_name.as_str(), method_name.as_str())].clone();
+            let label = match annotations.get(&(service_name.clone(), method_name.clone())) {
+                Some(annotation) => format!("{}\n{}", method_name, annotation),
+                None => method_name.clone(),
+            };
+            nodes.push((id.clone(), label));
+
+            for (stage_index, stage) in method.calls.iter().enumerate() {
+                for call in stage {
+                    let Some(to) = split_call(call).and_then(|(s, m)| node_id(s, m)) else {
+                        continue;
+                    };
+                    let label = if stage.len() > 1 {
+                        format!("stage {} ({} parallel)", stage_index + 1, stage.len())
+                    } else {
+                        format!("stage {}", stage_index + 1)
+                    };
+                    edges.push(Edge {
+                        from: id.clone(),
+                        to,
+                        label,
+                        is_async: false,
+                    });
+                }
+            }
+            for call in &method.async_calls {
+                let Some(to) = split_call(call).and_then(|(s, m)| node_id(s, m)) else {
+                    continue;
+                };
+                edges.push(Edge {
+                    from: id.clone(),
+                    to,
+                    label: "async".to_string(),
+                    is_async: true,
+                });
+            }
+        }
+        clusters.push(Cluster {
+            id: format!("svc_{}_{}", sanitize(service_name), index),
+            label: service_name.as_str(),
+            nodes,
+        });
+    }
+
+    for entry_point in config.load.iter().flat_map(|load| &load.entry_points) {
+        let Some(to) = node_id(&entry_point.service, &entry_point.method) else {
+            continue;
+        };
+        edges.push(Edge {
+            from: CLIENT_NODE.to_string(),
+            to,
+            label: format!("{} rps", entry_point.requests_per_second),
+            is_async: false,
+        });
+    }
+
+    match format {
+        GraphFormat::Dot => render_dot(&clusters, &edges),
+        GraphFormat::Mermaid => render_mermaid(&clusters, &edges),
+    }
+}
+
+fn render_dot(clusters: &[Cluster], edges: &[Edge]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph simulation {\n");
+    out.push_str("  rankdir=LR;\n");
+    out.push_str("  node [shape=box, style=rounded];\n");
+    if edges.iter().any(|edge| edge.from == CLIENT_NODE) {
+        let _ = writeln!(out, "  {} [shape=ellipse];", CLIENT_NODE);
+    }
+    for cluster in clusters {
+        let _ = writeln!(out, "  subgraph cluster_{} {{", cluster.id);
+        let _ = writeln!(out, "    label=\"{}\";", escape_dot(cluster.label));
+        for (id, label) in &cluster.nodes {
+            let _ = writeln!(out, "    {} [label=\"{}\"];", id, escape_dot(label));
+        }
+        out.push_str("  }\n");
+    }
+    for edge in edges {
+        let style = if edge.is_async { ", style=dashed" } else { "" };
+        let _ = writeln!(
+            out,
+            "  {} -> {} [label=\"{}\"{}];",
+            edge.from,
+            edge.to,
+            escape_dot(&edge.label),
+            style
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(clusters: &[Cluster], edges: &[Edge]) -> String {
+    let mut out = String::new();
+    out.push_str("flowchart LR\n");
+    if edges.iter().any(|edge| edge.from == CLIENT_NODE) {
+        let _ = writeln!(out, "  {}((client))", CLIENT_NODE);
+    }
+    for cluster in clusters {
+        let _ = writeln!(
+            out,
+            "  subgraph {}[\"{}\"]",
+            cluster.id,
+            escape_mermaid(cluster.label)
+        );
+        for (id, label) in &cluster.nodes {
+            let _ = writeln!(out, "    {}[\"{}\"]", id, escape_mermaid(label));
+        }
+        out.push_str("  end\n");
+    }
+    for edge in edges {
+        let arrow = if edge.is_async { "-.->" } else { "-->" };
+        let _ = writeln!(
+            out,
+            "  {} {}|\"{}\"| {}",
+            edge.from,
+            arrow,
+            escape_mermaid(&edge.label),
+            edge.to
+        );
+    }
+    out
+}
+
+/// Part of a name usable in identifiers that are valid in both DOT and Mermaid
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Service `a` method `b__c` calling service `a__b` method `c`, whose sanitized names clash
+    fn config() -> SimulatorConfig {
+        let latency = serde_json::json!({"type": "constant", "parameters": {"value": 1.0}});
+        serde_json::from_value(serde_json::json!({
+            "services": {
+                "a": {"port": 50051, "methods": {
+                    "b__c": {"calls": [["a__b.c"]], "latency_distribution": latency},
+                }},
+                "a__b": {"port": 50052, "methods": {
+                    "c": {"calls": [], "latency_distribution": latency},
+                }},
+                "say \"hi\"": {"port": 50053, "methods": {
+                    "get": {"calls": [], "latency_distribution": latency},
+                }},
+            },
+            "load": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn node_ids_stay_unique_after_sanitizing() {
+        let dot = generate_graph(&config(), GraphFormat::Dot, false);
+        assert!(dot.contains("a__b__c_0 [label=\"b__c\"]"), "{}", dot);
+        assert!(dot.contains("a__b__c_1 [label=\"c\"]"), "{}", dot);
+        assert!(dot.contains("a__b__c_0 -> a__b__c_1"), "{}", dot);
+    }
+
+    #[test]
+    fn mermaid_subgraph_labels_are_escaped() {
+        let mermaid = generate_graph(&config(), GraphFormat::Mermaid, false);
+        assert!(
+            mermaid.contains("subgraph svc_say__hi__2[\"say #quot;hi#quot;\"]"),
+            "{}",
+            mermaid
+        );
+    }
+}
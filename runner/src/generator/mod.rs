@@ -1 +1,20 @@
+pub mod graph;
+pub mod topology;
 pub mod yaml;
+
+use std::collections::HashMap;
+
+use crate::parser::ServiceConfig;
+
+/// Give services consecutive container ports from `first_port`, in name order, so they
+/// don't collide when sharing a host; ports wrap around past 65535
+pub fn assign_service_ports(services: &mut HashMap<String, ServiceConfig>, first_port: u16) {
+    let available = usize::from(u16::MAX - first_port) + 1;
+    let mut names: Vec<String> = services.keys().cloned().collect();
+    names.sort();
+    for (index, name) in names.iter().enumerate() {
+        if let Some(service) = services.get_mut(name) {
+            service.port = first_port + (index % available) as u16;
+        }
+    }
+}
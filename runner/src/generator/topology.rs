@@ -0,0 +1,402 @@
+use anyhow::{Context, Result, bail};
+use rand::distr::weighted::WeightedIndex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution as _, Geometric, Poisson};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::assign_service_ports;
+use crate::parser::{
+    CURRENT_SCHEMA_VERSION, Distribution, EntryPoint, LoadConfig, MethodConfig, ServiceConfig,
+    SimulatorConfig,
+};
+use crate::validator;
+
+/// Container port of the first generated service, the others count up from it
+const FIRST_GENERATED_PORT: u16 = 50051;
+/// Chance that a call runs in parallel with the previous call of its method
+const PARALLEL_CALL_PROBABILITY: f64 = 0.5;
+/// Largest preferential attachment exponent, keeping callee weights finite
+const MAX_PREFERENTIAL_EXPONENT: f64 = 10.0;
+/// Weighted draws per callee before falling back to a uniform pick of an uncalled method
+const MAX_WEIGHTED_DRAWS: usize = 8;
+
+/// How many calls a method makes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FanOutDistribution {
+    Constant,
+    Uniform,
+    Poisson,
+    Geometric,
+}
+
+impl FromStr for FanOutDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "constant" => Ok(FanOutDistribution::Constant),
+            "uniform" => Ok(FanOutDistribution::Uniform),
+            "poisson" => Ok(FanOutDistribution::Poisson),
+            "geometric" => Ok(FanOutDistribution::Geometric),
+            other => bail!(
+                "Unknown fan-out distribution '{}', expected 'constant', 'uniform', 'poisson' or 'geometric'",
+                other
+            ),
+        }
+    }
+}
+
+/// Parameters of a generated topology
+#[derive(Debug, Clone)]
+pub struct TopologyParams {
+    pub services: usize,
+    /// Number of service layers, storage included
+    pub depth: usize,
+    pub methods_per_service: usize,
+    pub fan_out: FanOutDistribution,
+    pub fan_out_mean: f64,
+    pub max_fan_out: usize,
+    /// Callees are picked with weight (in-degree + 1)^exponent; 0 picks uniformly, 1 and
+    /// above concentrate calls on a few hubs and give power-law in-degrees
+    pub preferential_exponent: f64,
+    /// Share of services that are leaf storage services
+    pub storage_share: f64,
+    pub latency_mean_ms: (f64, f64),
+    /// Standard deviation of each method's latency, relative to its mean
+    pub latency_cv: f64,
+    pub error_rate: (f64, f64),
+    pub entry_rps: u32,
+    pub seed: u64,
+}
+
+impl TopologyParams {
+    fn check(&self) -> Result<()> {
+        if self.depth == 0 || self.services < self.depth {
+            bail!(
+                "Need at least one service per layer, got {} services for depth {}",
+                self.services,
+                self.depth
+            );
+        }
+        if self.methods_per_service == 0 {
+            bail!("Services need at least one method");
+        }
+        if !(0.0..1.0).contains(&self.storage_share) {
+            bail!(
+                "Storage share must be in [0, 1), got {}",
+                self.storage_share
+            );
+        }
+        if !(0.0..=MAX_PREFERENTIAL_EXPONENT).contains(&self.preferential_exponent) {
+            bail!(
+                "Preferential exponent must be within [0, {}], got {}",
+                MAX_PREFERENTIAL_EXPONENT,
+                self.preferential_exponent
+            );
+        }
+        if self.max_fan_out == 0 && self.depth > 1 {
+            bail!("Max fan-out must be at least 1 for methods to call deeper layers");
+        }
+        if self.fan_out_mean < 0.0 {
+            bail!(
+                "Fan-out mean must be non-negative, got {}",
+                self.fan_out_mean
+            );
+        }
+        let (latency_min, latency_max) = self.latency_mean_ms;
+        if latency_min <= 0.0 || latency_min > latency_max {
+            bail!("Invalid latency range {}..{} ms", latency_min, latency_max);
+        }
+        if self.latency_cv <= 0.0 {
+            bail!("Latency coefficient of variation must be positive");
+        }
+        let (error_min, error_max) = self.error_rate;
+        if error_min < 0.0 || error_min > error_max || error_max >= 1.0 {
+            bail!("Invalid error rate range {}..{}", error_min, error_max);
+        }
+        if self.entry_rps == 0 {
+            bail!("Entry point rate must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// A generated service before it becomes part of the config
+struct Node {
+    name: String,
+    layer: usize,
+    methods: Vec<String>,
+}
+
+/// Generate a random layered topology.
+///
+/// Frontends make up the first layer and are the entry points; storage services make up
+/// the last layer when there are any. Methods only call methods of deeper layers, so the
+/// graph is acyclic, and every method below the first layer is called by at least one
+/// method above it. No method makes more than `max_fan_out` calls.
+pub fn generate_topology(params: &TopologyParams) -> Result<SimulatorConfig> {
+    params.check()?;
+    let mut rng = StdRng::seed_from_u64(params.seed);
+
+    // Storage services need a layer of their own below the others
+    let storage = ((params.services as f64 * params.storage_share).round() as usize)
+        .min(params.services - params.depth + 1)
+        .min(if params.depth > 1 { params.services } else { 0 });
+    let compute_layers = if storage > 0 {
+        params.depth - 1
+    } else {
+        params.depth
+    };
+    let compute = params.services - storage;
+    let mut layers: Vec<usize> = (0..compute_layers).collect();
+    layers.extend((compute_layers..compute).map(|_| rng.random_range(0..compute_layers)));
+    layers.sort();
+
+    let mut nodes: Vec<Node> = layers
+        .into_iter()
+        .enumerate()
+        .map(|(index, layer)| {
+            let kind = if layer == 0 { "frontend" } else { "service" };
+            (format!("{}_{}", kind, index), layer)
+        })
+        .chain((0..storage).map(|index| (format!("storage_{}", index), params.depth - 1)))
+        .map(|(name, layer)| Node {
+            name,
+            layer,
+            methods: (0..rng.random_range(1..=params.methods_per_service))
+                .map(|index| format!("method_{}", index))
+                .collect(),
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.layer);
+
+    // Every method, and the methods it calls, by index into `methods`
+    let methods: Vec<(usize, &str)> = nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(node, n)| n.methods.iter().map(move |m| (node, m.as_str())))
+        .collect();
+    let layer_of = |method: usize| nodes[methods[method].0].layer;
+    let mut callees: Vec<Vec<usize>> = vec![Vec::new(); methods.len()];
+    let mut in_degree = vec![0usize; methods.len()];
+    let weight = |in_degree: usize| ((in_degree + 1) as f64).powf(params.preferential_exponent);
+
+    // Methods of every layer deeper than `layer`, which end `methods` since nodes are sorted
+    // by layer
+    let below = |layer: usize| methods.partition_point(|&(node, _)| nodes[node].layer <= layer);
+
+    // Callers of one layer share their candidates, whose weights are computed once per layer
+    // from the in-degrees the layers above gave them
+    let mut layer_callees: Option<(usize, WeightedIndex<f64>)> = None;
+    for (caller, chosen) in callees.iter_mut().enumerate() {
+        let layer = layer_of(caller);
+        let first = below(layer);
+        let wanted = sample_fan_out(params, &mut rng).min(methods.len() - first);
+        if wanted == 0 {
+            continue;
+        }
+        if layer_callees
+            .as_ref()
+            .is_none_or(|(callees_layer, _)| *callees_layer != layer)
+        {
+            let weights = (first..methods.len()).map(|callee| weight(in_degree[callee]));
+            layer_callees = Some((layer, WeightedIndex::new(weights)?));
+        }
+        let (_, candidates) = layer_callees.as_ref().expect("set above");
+
+        // A method calls each callee once. Skewed weights keep drawing the same hubs, so
+        // after a few draws the rest of the callees are picked uniformly.
+        while chosen.len() < wanted {
+            let callee = (0..MAX_WEIGHTED_DRAWS)
+                .map(|_| first + candidates.sample(&mut rng))
+                .find(|callee| !chosen.contains(callee))
+                .unwrap_or_else(|| {
+                    loop {
+                        let callee = rng.random_range(first..methods.len());
+                        if !chosen.contains(&callee) {
+                            break callee;
+                        }
+                    }
+                });
+            chosen.push(callee);
+        }
+        for &callee in chosen.iter() {
+            in_degree[callee] += 1;
+        }
+    }
+
+    // Give every method below the frontends a caller with room under the maximum fan-out,
+    // from the layer above it if possible and from any shallower layer otherwise
+    for (callee, degree) in in_degree.iter_mut().enumerate() {
+        let layer = layer_of(callee);
+        if layer == 0 || *degree > 0 {
+            continue;
+        }
+        let has_room = |caller: &usize| callees[*caller].len() < params.max_fan_out;
+        let parents = if layer == 1 { 0 } else { below(layer - 2) }..below(layer - 1);
+        let mut callers: Vec<usize> = parents.filter(has_room).collect();
+        if callers.is_empty() {
+            callers = (0..below(layer - 1)).filter(has_room).collect();
+        }
+        if callers.is_empty() {
+            bail!(
+                "Every method above layer {} already makes {} calls, so some methods would \
+                 have no caller; raise the maximum fan-out or lower the fan-out mean",
+                layer,
+                params.max_fan_out
+            );
+        }
+        let caller = callers[rng.random_range(0..callers.len())];
+        callees[caller].push(callee);
+        *degree += 1;
+    }
+
+    let mut services: HashMap<String, ServiceConfig> = HashMap::new();
+    for (method, (node, method_name)) in methods.iter().enumerate() {
+        let mut calls: Vec<Vec<String>> = Vec::new();
+        for &callee in &callees[method] {
+            let (callee_node, callee_method) = methods[callee];
+            let call = format!("{}.{}", nodes[callee_node].name, callee_method);
+            match calls.last_mut() {
+                Some(stage) if rng.random_bool(PARALLEL_CALL_PROBABILITY) => stage.push(call),
+                _ => calls.push(vec![call]),
+            }
+        }
+
+        let mean = rng.random_range(params.latency_mean_ms.0..=params.latency_mean_ms.1);
+        let error_rate = rng.random_range(params.error_rate.0..=params.error_rate.1);
+        services
+            .entry(nodes[*node].name.clone())
+            .or_insert_with(|| ServiceConfig {
+                port: FIRST_GENERATED_PORT,
+                methods: HashMap::new(),
+                concurrency: None,
+                replicas: None,
+                load_balancing: Default::default(),
+                replica_overrides: Vec::new(),
+            })
+            .methods
+            .insert(
+                method_name.to_string(),
+                MethodConfig {
+                    calls,
+                    async_calls: Vec::new(),
+                    latency_distribution: Distribution {
+                        distribution_type: "normal".to_string(),
+                        parameters: HashMap::from([
+                            ("mean".to_string(), mean),
+                            ("stddev".to_string(), mean * params.latency_cv),
+                        ]),
+                    },
+                    error_rate: Some(Distribution {
+                        distribution_type: "bernoulli".to_string(),
+                        parameters: HashMap::from([("p".to_string(), error_rate)]),
+                    }),
+                    max_recursion_depth: None,
+                },
+            );
+    }
+
+    let entry_points = methods
+        .iter()
+        .filter(|(node, _)| nodes[*node].layer == 0)
+        .map(|(node, method)| EntryPoint {
+            service: nodes[*node].name.clone(),
+            method: method.to_string(),
+            requests_per_second: params.entry_rps,
+        })
+        .collect();
+    assign_service_ports(&mut services, FIRST_GENERATED_PORT);
+    let config = SimulatorConfig {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        services,
+        load: Some(LoadConfig {
+            entry_points,
+            duration_seconds: None,
+        }),
+        telemetry: None,
+        slos: Vec::new(),
+        faults: Vec::new(),
+        network: None,
+    };
+
+    validator::validate_config(&config).context("Generated topology failed validation")?;
+    Ok(config)
+}
+
+fn sample_fan_out(params: &TopologyParams, rng: &mut StdRng) -> usize {
+    let mean = params.fan_out_mean;
+    let fan_out = match params.fan_out {
+        FanOutDistribution::Constant => mean.round() as usize,
+        FanOutDistribution::Uniform => rng.random_range(0..=(2.0 * mean).round() as usize),
+        FanOutDistribution::Poisson => Poisson::new(mean).map_or(0.0, |p| p.sample(rng)) as usize,
+        // Number of failures before the first success, which has the requested mean
+        FanOutDistribution::Geometric => {
+            Geometric::new(1.0 / (mean + 1.0)).map_or(0, |g| g.sample(rng)) as usize
+        }
+    };
+    fan_out.min(params.max_fan_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(preferential_exponent: f64) -> TopologyParams {
+        TopologyParams {
+            services: 200,
+            depth: 4,
+            methods_per_service: 2,
+            fan_out: FanOutDistribution::Poisson,
+            fan_out_mean: 3.0,
+            max_fan_out: 10,
+            preferential_exponent,
+            storage_share: 0.2,
+            latency_mean_ms: (1.0, 50.0),
+            latency_cv: 0.5,
+            error_rate: (0.0, 0.01),
+            entry_rps: 100,
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn invalid_preferential_exponents_are_rejected() {
+        for exponent in [
+            -1.0,
+            f64::NAN,
+            f64::INFINITY,
+            MAX_PREFERENTIAL_EXPONENT + 1.0,
+        ] {
+            assert!(
+                generate_topology(&params(exponent)).is_err(),
+                "{}",
+                exponent
+            );
+        }
+    }
+
+    #[test]
+    fn every_preferential_exponent_in_range_generates_a_valid_topology() {
+        for exponent in [0.0, 1.0, 3.0, MAX_PREFERENTIAL_EXPONENT] {
+            let config = generate_topology(&params(exponent)).unwrap();
+            assert_eq!(config.services.len(), 200);
+        }
+    }
+
+    #[test]
+    fn methods_make_at_most_max_fan_out_calls() {
+        let params = TopologyParams {
+            fan_out_mean: 1.0,
+            max_fan_out: 2,
+            ..params(1.0)
+        };
+        let config = generate_topology(&params).unwrap();
+        for method in config.services.values().flat_map(|s| s.methods.values()) {
+            let calls = method.calls.iter().flatten().count();
+            assert!(calls <= params.max_fan_out, "{} calls", calls);
+        }
+    }
+}
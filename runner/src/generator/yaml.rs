@@ -2,25 +2,48 @@ use anyhow::Result;
 use serde_yaml;
 use std::collections::HashMap;
 
-use crate::parser::SimulatorConfig;
+use crate::parser::{
+    FaultConfig, LoadBalancing, NetworkConfig, ReplicaOverride, SimulatorConfig, SloConfig,
+    TelemetryConfig,
+};
 
 #[derive(Debug, serde::Serialize)]
-struct SimulatorYaml {
-    services: HashMap<String, ServiceYaml>,
+struct SimulatorYaml<'a> {
+    services: HashMap<String, ServiceYaml<'a>>,
     load: Option<LoadYaml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    telemetry: Option<&'a TelemetryConfig>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    slos: &'a [SloConfig],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    faults: &'a [FaultConfig],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<&'a NetworkConfig>,
 }
 
 #[derive(Debug, serde::Serialize)]
-struct ServiceYaml {
+struct ServiceYaml<'a> {
     container_port: u16,
     methods: HashMap<String, MethodYaml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    concurrency: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replicas: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_balancing: Option<LoadBalancing>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    replica_overrides: &'a [ReplicaOverride],
 }
 
 #[derive(Debug, serde::Serialize)]
 struct MethodYaml {
     calls: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    async_calls: Vec<String>,
     latency_distribution: Distribution,
     error_rate: Option<Distribution>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_recursion_depth: Option<u32>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -32,6 +55,8 @@ struct Distribution {
 #[derive(Debug, serde::Serialize)]
 struct LoadYaml {
     entry_points: Vec<EntryPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_seconds: Option<u64>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -51,7 +76,13 @@ pub fn generate_simulator_yaml(config: &SimulatorConfig) -> Result<String> {
                 (
                     name.clone(),
                     ServiceYaml {
-                        container_port: service.port.clone(),
+                        container_port: service.port,
+                        concurrency: service.concurrency,
+                        replicas: service.replicas,
+                        // Only written when it differs from the default, like `replicas`
+                        load_balancing: (service.load_balancing != LoadBalancing::default())
+                            .then_some(service.load_balancing),
+                        replica_overrides: &service.replica_overrides,
                         methods: service
                             .methods
                             .iter()
@@ -60,6 +91,7 @@ pub fn generate_simulator_yaml(config: &SimulatorConfig) -> Result<String> {
                                     method_name.clone(),
                                     MethodYaml {
                                         calls: method.calls.clone(),
+                                        async_calls: method.async_calls.clone(),
                                         latency_distribution: Distribution {
                                             distribution_type: method
                                                 .latency_distribution
@@ -76,6 +108,7 @@ pub fn generate_simulator_yaml(config: &SimulatorConfig) -> Result<String> {
                                                 parameters: er.parameters.clone(),
                                             }
                                         }),
+                                        max_recursion_depth: method.max_recursion_depth,
                                     },
                                 )
                             })
@@ -94,10 +127,70 @@ pub fn generate_simulator_yaml(config: &SimulatorConfig) -> Result<String> {
                     requests_per_second: ep.requests_per_second,
                 })
                 .collect(),
+            duration_seconds: load.duration_seconds,
         }),
+        telemetry: config.telemetry.as_ref(),
+        slos: &config.slos,
+        faults: &config.faults,
+        network: config.network.as_ref(),
     };
 
     // Serialize to YAML
     let yaml = serde_yaml::to_string(&simulator_yaml)?;
     Ok(yaml)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_document_keeps_every_setting() {
+        let config: SimulatorConfig = serde_json::from_value(serde_json::json!({
+            "services": {
+                "svc": {
+                    "port": 50051,
+                    "concurrency": 4,
+                    "replicas": 3,
+                    "load_balancing": "least_outstanding",
+                    "replica_overrides": [{"replica": 2, "latency_multiplier": 10.0}],
+                    "methods": {
+                        "get": {
+                            "calls": [["svc.get"]],
+                            "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+                            "max_recursion_depth": 2,
+                        },
+                    },
+                },
+            },
+            "load": {
+                "entry_points": [{"service": "svc", "method": "get", "requests_per_second": 5}],
+                "duration_seconds": 30,
+            },
+            "telemetry": {"collector": true, "prometheus": true},
+            "slos": [{"entry_point": "svc.get", "objectives": ["p99 < 300ms"]}],
+            "faults": [{"at_seconds": 1, "service": "svc", "replica": 1, "action": "kill"}],
+            "network": {"edges": [{"caller": "svc", "callee": "svc", "delay_ms": 5}]},
+        }))
+        .unwrap();
+        let document: serde_json::Value =
+            serde_yaml::from_str(&generate_simulator_yaml(&config).unwrap()).unwrap();
+
+        let service = &document["services"]["svc"];
+        assert_eq!(service["concurrency"], 4);
+        assert_eq!(service["replicas"], 3);
+        assert_eq!(service["load_balancing"], "least_outstanding");
+        assert_eq!(service["replica_overrides"][0]["replica"], 2);
+        assert_eq!(
+            document["services"]["svc"]["methods"]["get"]["max_recursion_depth"],
+            2
+        );
+        assert_eq!(document["load"]["duration_seconds"], 30);
+        assert_eq!(document["telemetry"]["collector"], true);
+        assert_eq!(document["telemetry"]["prometheus"], true);
+        assert_eq!(document["slos"][0]["entry_point"], "svc.get");
+        assert_eq!(document["faults"][0]["action"], "kill");
+        assert_eq!(document["faults"][0]["replica"], 1);
+        assert_eq!(document["network"]["edges"][0]["delay_ms"], 5.0);
+    }
+}
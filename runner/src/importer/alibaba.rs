@@ -0,0 +1,255 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use super::{CallGraphBuilder, ObservedCall, sanitize_name};
+use crate::parser::SimulatorConfig;
+
+/// Method name used when a trace doesn't record the called interface
+const DEFAULT_METHOD: &str = "handle";
+/// Placeholders the traces use for microservices they couldn't identify
+const UNKNOWN_NAMES: [&str; 4] = ["", "UNKNOWN", "UNAVAILABLE", "(?)"];
+
+/// One row of an MSCallGraph CSV: a call from microservice `um` to `dm`
+#[derive(Debug)]
+struct CallRow {
+    rpcid: String,
+    dm: String,
+    interface: Option<String>,
+    rpctype: String,
+    rt_ms: f64,
+    timestamp_ms: Option<f64>,
+}
+
+impl CallRow {
+    fn is_known(&self) -> bool {
+        !UNKNOWN_NAMES.contains(&self.dm.as_str())
+    }
+
+    fn observed(&self) -> ObservedCall {
+        let method = self
+            .interface
+            .as_deref()
+            .map(sanitize_name)
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| DEFAULT_METHOD.to_string());
+        ObservedCall {
+            service: sanitize_name(&self.dm),
+            method,
+            start_ms: self.timestamp_ms,
+            duration_ms: self.rt_ms,
+            // The call graphs carry no status codes
+            is_error: false,
+            is_async: self.rpctype.eq_ignore_ascii_case("mq"),
+        }
+    }
+}
+
+/// Column positions in a CSV header, accepting both the 2021 and 2022 names
+struct Columns {
+    traceid: usize,
+    rpcid: usize,
+    dm: usize,
+    rpctype: usize,
+    rt: usize,
+    interface: Option<usize>,
+    timestamp: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &csv::StringRecord) -> Result<Self> {
+        let find = |names: &[&str]| header.iter().position(|column| names.contains(&column));
+        let require = |names: &[&str]| {
+            find(names).ok_or_else(|| anyhow!("Missing column '{}' in MSCallGraph CSV", names[0]))
+        };
+        // `um` isn't needed: callers are identified by their parent call
+        require(&["um"])?;
+        Ok(Columns {
+            traceid: require(&["traceid"])?,
+            rpcid: require(&["rpcid", "rpc_id"])?,
+            dm: require(&["dm"])?,
+            rpctype: require(&["rpctype"])?,
+            rt: require(&["rt"])?,
+            interface: find(&["interface"]),
+            timestamp: find(&["timestamp"]),
+        })
+    }
+}
+
+/// Build a config from Alibaba 2021/2022 MSCallGraph CSVs.
+///
+/// Calls are linked into trees by their hierarchical `rpcid`; a call whose parent isn't in
+/// the trace entered the system and becomes an entry point. Methods are named after the
+/// called interface, and `mq` calls become async calls.
+pub fn import_alibaba(
+    paths: &[PathBuf],
+    rate_scale: f64,
+    duration_seconds: Option<f64>,
+) -> Result<SimulatorConfig> {
+    let mut traces: HashMap<String, Vec<CallRow>> = HashMap::new();
+    let mut skipped = 0;
+    for path in paths {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let columns = Columns::from_header(reader.headers()?)
+            .with_context(|| format!("Unsupported CSV header in {}", path.display()))?;
+
+        for record in reader.records() {
+            let record = record.with_context(|| format!("Failed to read {}", path.display()))?;
+            let field = |index: usize| record.get(index).unwrap_or_default().trim();
+            // Response times are occasionally recorded negative
+            let Ok(rt) = field(columns.rt).parse::<f64>() else {
+                skipped += 1;
+                continue;
+            };
+            traces
+                .entry(field(columns.traceid).to_string())
+                .or_default()
+                .push(CallRow {
+                    rpcid: field(columns.rpcid).to_string(),
+                    dm: field(columns.dm).to_string(),
+                    interface: columns.interface.map(|index| field(index).to_string()),
+                    rpctype: field(columns.rpctype).to_string(),
+                    rt_ms: rt.abs(),
+                    timestamp_ms: columns
+                        .timestamp
+                        .and_then(|index| field(index).parse().ok()),
+                });
+        }
+    }
+    if skipped > 0 {
+        warn!("Skipped {} rows without a valid response time", skipped);
+    }
+    info!("Read {} traces", traces.len());
+
+    let mut builder = CallGraphBuilder::default();
+    for rows in traces.values() {
+        import_trace(&mut builder, rows);
+    }
+    builder.build(rate_scale, duration_seconds)
+}
+
+fn import_trace(builder: &mut CallGraphBuilder, rows: &[CallRow]) {
+    let mut by_rpcid: HashMap<&str, usize> = HashMap::new();
+    for (index, row) in rows.iter().enumerate() {
+        by_rpcid.entry(row.rpcid.as_str()).or_insert(index);
+    }
+
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let parent = row
+            .rpcid
+            .rsplit_once('.')
+            .and_then(|(parent, _)| by_rpcid.get(parent));
+        match parent {
+            Some(&parent) => children.entry(parent).or_default().push(index),
+            None => roots.push(index),
+        }
+    }
+
+    for root in roots {
+        if rows[root].is_known() {
+            let call = rows[root].observed();
+            builder.record_entry(&call.service, &call.method);
+            import_call(builder, rows, &children, root);
+        }
+    }
+}
+
+/// Record a call and, recursively, the calls it made; unidentified callees are left out
+fn import_call(
+    builder: &mut CallGraphBuilder,
+    rows: &[CallRow],
+    children: &HashMap<usize, Vec<usize>>,
+    index: usize,
+) {
+    let known: Vec<usize> = children
+        .get(&index)
+        .into_iter()
+        .flatten()
+        .copied()
+        .filter(|&child| rows[child].is_known())
+        .collect();
+    let calls: Vec<ObservedCall> = known.iter().map(|&child| rows[child].observed()).collect();
+    builder.record_invocation(&rows[index].observed(), &calls);
+    for child in known {
+        import_call(builder, rows, children, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Two identical requests to `frontend./home`, one second apart: `cart` and `catalog` are
+    /// called in parallel, then `db`, while `queue` gets a message and an unidentified
+    /// service is called last
+    const CALL_GRAPH: &str = "\
+traceid,timestamp,rpcid,um,rpctype,dm,interface,rt
+t1,1000,0,USER,http,frontend,/home,100
+t1,1010,0.1,frontend,rpc,cart,get,30
+t1,1015,0.2,frontend,rpc,catalog,list,40
+t1,1060,0.3,frontend,rpc,db,query,10
+t1,1070,0.4,frontend,mq,queue,notify,5
+t1,1080,0.5,frontend,rpc,UNKNOWN,,5
+t2,2000,0,USER,http,frontend,/home,100
+t2,2010,0.1,frontend,rpc,cart,get,30
+t2,2015,0.2,frontend,rpc,catalog,list,40
+t2,2060,0.3,frontend,rpc,db,query,10
+t2,2070,0.4,frontend,mq,queue,notify,5
+t2,2080,0.5,frontend,rpc,UNKNOWN,,5
+";
+
+    fn import(rate_scale: f64) -> SimulatorConfig {
+        let path = std::env::temp_dir().join(format!("mscallgraph-{}.csv", std::process::id()));
+        fs::write(&path, CALL_GRAPH).unwrap();
+        let config = import_alibaba(std::slice::from_ref(&path), rate_scale, None);
+        fs::remove_file(&path).unwrap();
+        config.unwrap()
+    }
+
+    #[test]
+    fn overlapping_calls_share_a_stage() {
+        let config = import(1.0);
+        let mut services: Vec<_> = config.services.keys().map(String::as_str).collect();
+        services.sort();
+        assert_eq!(services, ["cart", "catalog", "db", "frontend", "queue"]);
+
+        let home = &config.services["frontend"].methods["home"];
+        assert_eq!(
+            home.calls,
+            [vec!["cart.get", "catalog.list"], vec!["db.query"]]
+        );
+        assert_eq!(home.async_calls, ["queue.notify"]);
+        assert!(config.services["cart"].methods["get"].calls.is_empty());
+    }
+
+    #[test]
+    fn self_times_exclude_waiting_on_stages() {
+        let config = import(1.0);
+        let home = &config.services["frontend"].methods["home"];
+        // 100 ms minus 45 ms for `cart` and `catalog` together and 10 ms for `db`
+        assert_eq!(home.latency_distribution.distribution_type, "empirical");
+        assert_eq!(home.latency_distribution.parameters["p0"], 45.0);
+        assert_eq!(home.latency_distribution.parameters["p100"], 45.0);
+        assert_eq!(home.error_rate.as_ref().unwrap().parameters["p"], 0.0);
+    }
+
+    #[test]
+    fn entry_rates_are_roots_over_the_observed_span() {
+        // Two roots between 1.000 s and 2.100 s
+        let entry_points = import(11.0).load.unwrap().entry_points;
+        assert_eq!(entry_points.len(), 1);
+        assert_eq!(
+            (
+                entry_points[0].service.as_str(),
+                entry_points[0].method.as_str()
+            ),
+            ("frontend", "home")
+        );
+        assert_eq!(entry_points[0].requests_per_second, 20);
+    }
+}
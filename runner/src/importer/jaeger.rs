@@ -0,0 +1,167 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::spans::{Span, SpanKind};
+
+/// Export of the Jaeger query API and UI: `{"data": [trace, ...]}`
+#[derive(Debug, Deserialize)]
+struct JaegerExport {
+    data: Vec<JaegerTrace>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JaegerTrace {
+    spans: Vec<JaegerSpan>,
+    #[serde(default)]
+    processes: HashMap<String, JaegerProcess>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JaegerSpan {
+    #[serde(rename = "traceID")]
+    trace_id: String,
+    #[serde(rename = "spanID")]
+    span_id: String,
+    operation_name: String,
+    #[serde(default)]
+    references: Vec<JaegerReference>,
+    /// Microseconds since the epoch
+    start_time: f64,
+    /// Microseconds
+    duration: f64,
+    #[serde(default)]
+    tags: Vec<JaegerTag>,
+    #[serde(rename = "processID", default)]
+    process_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JaegerReference {
+    ref_type: String,
+    #[serde(rename = "spanID")]
+    span_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JaegerTag {
+    key: String,
+    value: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JaegerProcess {
+    service_name: String,
+}
+
+/// Read the spans of a Jaeger JSON export, as downloaded from the UI or query API
+pub fn read_jaeger_spans(path: &Path) -> Result<Vec<Span>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {} as JSON", path.display()))?;
+    // Accept the API envelope, a bare list of traces or a single trace
+    let traces: Vec<JaegerTrace> = if value.get("data").is_some() {
+        serde_json::from_value::<JaegerExport>(value).map(|export| export.data)
+    } else if value.is_array() {
+        serde_json::from_value(value)
+    } else if value.get("spans").is_some() {
+        serde_json::from_value(value).map(|trace| vec![trace])
+    } else {
+        bail!("{} is not a Jaeger trace export", path.display());
+    }
+    .with_context(|| format!("Unexpected Jaeger trace format in {}", path.display()))?;
+
+    Ok(traces.into_iter().flat_map(convert_trace).collect())
+}
+
+fn convert_trace(trace: JaegerTrace) -> Vec<Span> {
+    let processes = trace.processes;
+    trace
+        .spans
+        .into_iter()
+        .map(|span| {
+            let tag = |key: &str| {
+                span.tags
+                    .iter()
+                    .find(|tag| tag.key == key)
+                    .map(|tag| &tag.value)
+            };
+            let parent = span.references.first();
+            let kind = match tag("span.kind").and_then(Value::as_str) {
+                Some("server") => SpanKind::Server,
+                Some("client") => SpanKind::Client,
+                Some("producer") => SpanKind::Producer,
+                Some("consumer") => SpanKind::Consumer,
+                _ => SpanKind::Internal,
+            };
+            let is_error = tag("error").is_some_and(|value| {
+                value.as_bool() == Some(true) || value.as_str() == Some("true")
+            }) || tag("otel.status_code").and_then(Value::as_str) == Some("ERROR");
+
+            Span {
+                trace_id: span.trace_id,
+                parent_id: parent.map(|reference| reference.span_id.clone()),
+                follows_parent: parent
+                    .is_some_and(|reference| reference.ref_type == "FOLLOWS_FROM"),
+                span_id: span.span_id,
+                service: processes
+                    .get(&span.process_id)
+                    .map_or_else(|| span.process_id.clone(), |p| p.service_name.clone()),
+                operation: span.operation_name,
+                kind,
+                start_ms: span.start_time / 1000.0,
+                duration_ms: span.duration / 1000.0,
+                is_error,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{"data": [{
+        "processes": {"p1": {"serviceName": "frontend"}, "p2": {"serviceName": "worker"}},
+        "spans": [
+            {"traceID": "t", "spanID": "a", "operationName": "GET /home", "processID": "p1",
+             "startTime": 1000000, "duration": 40000,
+             "tags": [{"key": "span.kind", "value": "server"}, {"key": "error", "value": true}]},
+            {"traceID": "t", "spanID": "b", "operationName": "process", "processID": "p2",
+             "startTime": 1050000, "duration": 5000,
+             "references": [{"refType": "FOLLOWS_FROM", "spanID": "a"}],
+             "tags": [{"key": "span.kind", "value": "consumer"}]}
+        ]
+    }]}"#;
+
+    #[test]
+    fn exports_are_read_into_spans() {
+        let path = std::env::temp_dir().join(format!("jaeger-{}.json", std::process::id()));
+        fs::write(&path, EXPORT).unwrap();
+        let spans = read_jaeger_spans(&path);
+        fs::remove_file(&path).unwrap();
+        let spans = spans.unwrap();
+
+        let (home, process) = (&spans[0], &spans[1]);
+        assert_eq!(
+            (home.service.as_str(), home.operation.as_str()),
+            ("frontend", "GET /home")
+        );
+        assert_eq!(home.kind, SpanKind::Server);
+        assert_eq!((home.start_ms, home.duration_ms), (1000.0, 40.0));
+        assert!(home.is_error && home.parent_id.is_none());
+
+        assert_eq!(process.service, "worker");
+        assert_eq!(process.kind, SpanKind::Consumer);
+        assert_eq!(process.parent_id.as_deref(), Some("a"));
+        assert!(process.follows_parent && !process.is_error);
+    }
+}
@@ -0,0 +1,468 @@
+pub mod alibaba;
+pub mod jaeger;
+pub mod otlp;
+pub mod spans;
+
+use anyhow::{Result, bail};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::generator::assign_service_ports;
+use crate::parser::{
+    CURRENT_SCHEMA_VERSION, Distribution, EntryPoint, LoadConfig, MethodConfig, ServiceConfig,
+    SimulatorConfig,
+};
+
+/// Container port of the first imported service, the others count up from it
+const FIRST_IMPORTED_PORT: u16 = 50051;
+/// Percentiles kept when fitting an empirical latency distribution
+const FITTED_PERCENTILES: [f64; 13] = [
+    0.0, 10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 95.0, 99.0, 100.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// Alibaba cluster trace MSCallGraph CSVs
+    Alibaba,
+    /// Jaeger JSON exports
+    Jaeger,
+    /// OTLP/JSON trace dumps
+    Otlp,
+}
+
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "alibaba" => Ok(TraceFormat::Alibaba),
+            "jaeger" => Ok(TraceFormat::Jaeger),
+            "otlp" => Ok(TraceFormat::Otlp),
+            other => bail!(
+                "Unknown trace format '{}', expected 'alibaba', 'jaeger' or 'otlp'",
+                other
+            ),
+        }
+    }
+}
+
+/// Build a config from trace files of the given format
+pub fn import_traces(
+    format: TraceFormat,
+    paths: &[PathBuf],
+    rate_scale: f64,
+    duration_seconds: Option<f64>,
+) -> Result<SimulatorConfig> {
+    match format {
+        TraceFormat::Alibaba => alibaba::import_alibaba(paths, rate_scale, duration_seconds),
+        TraceFormat::Jaeger => import_spans(
+            paths,
+            jaeger::read_jaeger_spans,
+            rate_scale,
+            duration_seconds,
+        ),
+        TraceFormat::Otlp => {
+            import_spans(paths, otlp::read_otlp_spans, rate_scale, duration_seconds)
+        }
+    }
+}
+
+fn import_spans(
+    paths: &[PathBuf],
+    read: fn(&Path) -> Result<Vec<spans::Span>>,
+    rate_scale: f64,
+    duration_seconds: Option<f64>,
+) -> Result<SimulatorConfig> {
+    let mut spans = Vec::new();
+    for path in paths {
+        spans.extend(read(path)?);
+    }
+    let mut builder = CallGraphBuilder::default();
+    spans::record_spans(&mut builder, spans);
+    builder.build(rate_scale, duration_seconds)
+}
+
+/// A method invocation observed in a trace
+#[derive(Debug, Clone)]
+pub struct ObservedCall {
+    pub service: String,
+    pub method: String,
+    /// Start time in ms, when the trace records it
+    pub start_ms: Option<f64>,
+    pub duration_ms: f64,
+    pub is_error: bool,
+    /// Whether the caller enqueued the call instead of waiting for it
+    pub is_async: bool,
+}
+
+impl ObservedCall {
+    fn target(&self) -> String {
+        format!("{}.{}", self.service, self.method)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MethodObservations {
+    invocations: u64,
+    errors: u64,
+    self_times_ms: Vec<f64>,
+    /// How often each shape of sync call stages was seen
+    stage_shapes: HashMap<Vec<Vec<String>>, u64>,
+    async_calls: BTreeSet<String>,
+}
+
+/// Accumulates observed invocations from traces and turns them into a config.
+///
+/// Each method gets the call stages it was seen making most often, an empirical
+/// distribution of its self time and the error ratio of its invocations. Entry point
+/// rates come from the number of root invocations over the observed time span.
+#[derive(Debug, Default)]
+pub struct CallGraphBuilder {
+    methods: HashMap<(String, String), MethodObservations>,
+    entry_invocations: HashMap<(String, String), u64>,
+    first_ms: Option<f64>,
+    last_ms: Option<f64>,
+}
+
+impl CallGraphBuilder {
+    /// Record one invocation of a method together with the calls it made
+    pub fn record_invocation(&mut self, invocation: &ObservedCall, children: &[ObservedCall]) {
+        let (sync, asynchronous): (Vec<_>, Vec<_>) =
+            children.iter().partition(|child| !child.is_async);
+        let stages = group_stages(sync);
+        let waiting_ms: f64 = stages.iter().map(|stage| stage_span_ms(stage)).sum();
+
+        let observations = self
+            .methods
+            .entry((invocation.service.clone(), invocation.method.clone()))
+            .or_default();
+        observations.invocations += 1;
+        if invocation.is_error {
+            observations.errors += 1;
+        }
+        observations
+            .self_times_ms
+            .push((invocation.duration_ms - waiting_ms).max(0.0));
+        let shape = stages
+            .iter()
+            .map(|stage| {
+                let mut calls: Vec<String> = stage.iter().map(|call| call.target()).collect();
+                calls.sort();
+                calls
+            })
+            .collect();
+        *observations.stage_shapes.entry(shape).or_default() += 1;
+        observations
+            .async_calls
+            .extend(asynchronous.iter().map(|call| call.target()));
+
+        for call in std::iter::once(invocation).chain(children) {
+            if let Some(start) = call.start_ms {
+                self.observe_time(start);
+                self.observe_time(start + call.duration_ms);
+            }
+        }
+    }
+
+    /// Record a request entering the system at a method
+    pub fn record_entry(&mut self, service: &str, method: &str) {
+        *self
+            .entry_invocations
+            .entry((service.to_string(), method.to_string()))
+            .or_default() += 1;
+    }
+
+    fn observe_time(&mut self, ms: f64) {
+        self.first_ms = Some(self.first_ms.map_or(ms, |first| first.min(ms)));
+        self.last_ms = Some(self.last_ms.map_or(ms, |last| last.max(ms)));
+    }
+
+    /// Build the config, scaling entry point rates by `rate_scale`.
+    ///
+    /// Calls that would close a cycle between methods are dropped, since the simulator
+    /// rejects unbounded recursion; `duration_seconds` overrides the time span seen in the
+    /// traces.
+    pub fn build(self, rate_scale: f64, duration_seconds: Option<f64>) -> Result<SimulatorConfig> {
+        if self.entry_invocations.is_empty() {
+            bail!("No root invocations found in the traces");
+        }
+
+        let span_seconds =
+            duration_seconds.unwrap_or_else(|| match (self.first_ms, self.last_ms) {
+                (Some(first), Some(last)) => (last - first) / 1000.0,
+                _ => 0.0,
+            });
+        if span_seconds <= 0.0 {
+            bail!("Traces carry no timestamps; pass the duration they cover explicitly");
+        }
+
+        let mut keys: Vec<_> = self.methods.keys().cloned().collect();
+        keys.sort();
+        let mut dependencies: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut dropped = 0;
+        let mut services: HashMap<String, ServiceConfig> = HashMap::new();
+        for key in keys {
+            let observations = &self.methods[&key];
+            let (service_name, method_name) = key;
+            let caller = format!("{}.{}", service_name, method_name);
+
+            let shape = most_common_shape(&observations.stage_shapes);
+            let mut keep = |call: &String| {
+                if *call == caller || reaches(&dependencies, call, &caller) {
+                    dropped += 1;
+                    return false;
+                }
+                dependencies
+                    .entry(caller.clone())
+                    .or_default()
+                    .insert(call.clone());
+                true
+            };
+            let calls: Vec<Vec<String>> = shape
+                .into_iter()
+                .map(|stage| stage.into_iter().filter(|call| keep(call)).collect())
+                .filter(|stage: &Vec<String>| !stage.is_empty())
+                .collect();
+            let async_calls = observations
+                .async_calls
+                .iter()
+                .filter(|call| keep(call))
+                .cloned()
+                .collect();
+
+            let method = MethodConfig {
+                calls,
+                async_calls,
+                latency_distribution: fit_empirical(&observations.self_times_ms),
+                error_rate: Some(Distribution {
+                    distribution_type: "bernoulli".to_string(),
+                    parameters: HashMap::from([(
+                        "p".to_string(),
+                        observations.errors as f64 / observations.invocations as f64,
+                    )]),
+                }),
+                max_recursion_depth: None,
+            };
+            services
+                .entry(service_name)
+                .or_insert_with(|| ServiceConfig {
+                    port: FIRST_IMPORTED_PORT,
+                    methods: HashMap::new(),
+                    concurrency: None,
+                    replicas: None,
+                    load_balancing: Default::default(),
+                    replica_overrides: Vec::new(),
+                })
+                .methods
+                .insert(method_name, method);
+        }
+        if dropped > 0 {
+            warn!(
+                "Dropped {} calls that would form a cycle between methods",
+                dropped
+            );
+        }
+
+        let mut entry_points: Vec<EntryPoint> = self
+            .entry_invocations
+            .into_iter()
+            .map(|((service, method), count)| EntryPoint {
+                service,
+                method,
+                requests_per_second: ((count as f64 / span_seconds * rate_scale).round() as u32)
+                    .max(1),
+            })
+            .collect();
+        entry_points.sort_by(|a, b| (&a.service, &a.method).cmp(&(&b.service, &b.method)));
+        assign_service_ports(&mut services, FIRST_IMPORTED_PORT);
+
+        Ok(SimulatorConfig {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            services,
+            load: Some(LoadConfig {
+                entry_points,
+                duration_seconds: None,
+            }),
+            telemetry: None,
+            slos: Vec::new(),
+            faults: Vec::new(),
+            network: None,
+        })
+    }
+}
+
+/// Group calls into sequential stages: a call overlapping the calls before it joins their stage
+fn group_stages(mut calls: Vec<&ObservedCall>) -> Vec<Vec<&ObservedCall>> {
+    // Without timestamps, the recorded order is all there is, so every call is its own stage
+    if calls.iter().any(|call| call.start_ms.is_none()) {
+        return calls.into_iter().map(|call| vec![call]).collect();
+    }
+    calls.sort_by(|a, b| {
+        a.start_ms
+            .unwrap_or_default()
+            .total_cmp(&b.start_ms.unwrap_or_default())
+    });
+
+    let mut stages: Vec<Vec<&ObservedCall>> = Vec::new();
+    let mut stage_end = f64::NEG_INFINITY;
+    for call in calls {
+        let start = call.start_ms.unwrap_or_default();
+        match stages.last_mut() {
+            Some(stage) if start < stage_end => stage.push(call),
+            _ => stages.push(vec![call]),
+        }
+        stage_end = stage_end.max(start + call.duration_ms);
+    }
+    stages
+}
+
+/// Time a stage kept its caller waiting
+fn stage_span_ms(stage: &[&ObservedCall]) -> f64 {
+    let starts = stage.iter().filter_map(|call| call.start_ms);
+    let first = starts.clone().fold(f64::INFINITY, f64::min);
+    let last = stage
+        .iter()
+        .filter_map(|call| call.start_ms.map(|start| start + call.duration_ms))
+        .fold(f64::NEG_INFINITY, f64::max);
+    if first.is_finite() && last.is_finite() {
+        last - first
+    } else {
+        stage
+            .iter()
+            .map(|call| call.duration_ms)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// The stage shape seen most often, ties broken deterministically
+fn most_common_shape(shapes: &HashMap<Vec<Vec<String>>, u64>) -> Vec<Vec<String>> {
+    shapes
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(shape, _)| shape.clone())
+        .unwrap_or_default()
+}
+
+/// Whether `to` is reachable from `from` over the method dependencies added so far
+fn reaches(dependencies: &HashMap<String, HashSet<String>>, from: &str, to: &str) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![from];
+    while let Some(service) = pending.pop() {
+        if service == to {
+            return true;
+        }
+        if seen.insert(service) {
+            pending.extend(
+                dependencies
+                    .get(service)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str),
+            );
+        }
+    }
+    false
+}
+
+/// Fit an empirical distribution to latency samples in ms
+pub fn fit_empirical(samples_ms: &[f64]) -> Distribution {
+    let mut sorted = samples_ms.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let parameters = FITTED_PERCENTILES
+        .iter()
+        .map(|&percent| {
+            let value = if sorted.is_empty() {
+                0.0
+            } else {
+                let rank = (percent / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+                sorted[rank]
+            };
+            (format!("p{}", percent), value)
+        })
+        .collect();
+    Distribution {
+        distribution_type: "empirical".to_string(),
+        parameters,
+    }
+}
+
+/// Turn a name from a trace into one usable as a service or method name
+pub fn sanitize_name(name: &str) -> String {
+    let lowered = name.trim().to_ascii_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(target: &str, start_ms: Option<f64>, duration_ms: f64) -> ObservedCall {
+        let (service, method) = target.split_once('.').unwrap();
+        ObservedCall {
+            service: service.to_string(),
+            method: method.to_string(),
+            start_ms,
+            duration_ms,
+            is_error: false,
+            is_async: false,
+        }
+    }
+
+    fn targets(stages: Vec<Vec<&ObservedCall>>) -> Vec<Vec<String>> {
+        stages
+            .into_iter()
+            .map(|stage| stage.into_iter().map(ObservedCall::target).collect())
+            .collect()
+    }
+
+    #[test]
+    fn calls_overlapping_a_stage_join_it() {
+        let calls = [
+            call("c.x", Some(30.0), 5.0),
+            call("a.x", Some(0.0), 10.0),
+            call("b.x", Some(5.0), 20.0),
+        ];
+        assert_eq!(
+            targets(group_stages(calls.iter().collect())),
+            [vec!["a.x", "b.x"], vec!["c.x"]]
+        );
+    }
+
+    #[test]
+    fn calls_without_timestamps_are_sequential() {
+        let calls = [call("a.x", None, 10.0), call("b.x", Some(0.0), 10.0)];
+        assert_eq!(
+            targets(group_stages(calls.iter().collect())),
+            [vec!["a.x"], vec!["b.x"]]
+        );
+    }
+
+    #[test]
+    fn calls_closing_a_cycle_are_dropped() {
+        let mut builder = CallGraphBuilder::default();
+        builder.record_entry("a", "x");
+        builder.record_invocation(&call("a.x", None, 10.0), &[call("b.x", None, 5.0)]);
+        builder.record_invocation(&call("b.x", None, 5.0), &[call("a.x", None, 1.0)]);
+        let config = builder.build(1.0, Some(1.0)).unwrap();
+        // `a.x` is resolved first, so the call from `b.x` back to it is the one dropped
+        assert_eq!(config.services["a"].methods["x"].calls, [vec!["b.x"]]);
+        assert!(config.services["b"].methods["x"].calls.is_empty());
+    }
+
+    #[test]
+    fn empirical_fit_keeps_percentiles() {
+        let samples: Vec<f64> = (0..=100).rev().map(f64::from).collect();
+        let fitted = fit_empirical(&samples);
+        assert_eq!(fitted.distribution_type, "empirical");
+        assert_eq!(fitted.parameters["p0"], 0.0);
+        assert_eq!(fitted.parameters["p50"], 50.0);
+        assert_eq!(fitted.parameters["p99"], 99.0);
+        assert_eq!(fitted.parameters["p100"], 100.0);
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use super::spans::{Span, SpanKind};
+
+/// OTLP/JSON `ExportTraceServiceRequest`, as written by the collector's file exporter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TracesData {
+    #[serde(default)]
+    resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    #[serde(default)]
+    resource: Resource,
+    #[serde(default, alias = "instrumentationLibrarySpans")]
+    scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Resource {
+    #[serde(default)]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScopeSpans {
+    #[serde(default)]
+    spans: Vec<OtlpSpan>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(default)]
+    parent_span_id: String,
+    name: String,
+    #[serde(default)]
+    kind: Value,
+    /// Nanoseconds since the epoch, usually as a string
+    start_time_unix_nano: Value,
+    end_time_unix_nano: Value,
+    #[serde(default)]
+    status: Option<Status>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    #[serde(default)]
+    code: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyValue {
+    key: String,
+    value: Value,
+}
+
+/// Read the spans of an OTLP/JSON file, either one document or one document per line
+pub fn read_otlp_spans(path: &Path) -> Result<Vec<Span>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let documents: Vec<TracesData> = match serde_json::from_str(&content) {
+        Ok(document) => vec![document],
+        Err(_) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(number, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!("Failed to parse line {} of {}", number + 1, path.display())
+                })
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    let mut spans = Vec::new();
+    for resource_spans in documents.into_iter().flat_map(|d| d.resource_spans) {
+        let service = resource_spans
+            .resource
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == "service.name")
+            .and_then(|attribute| attribute.value.get("stringValue"))
+            .and_then(Value::as_str)
+            .unwrap_or("unknown_service")
+            .to_string();
+        for span in resource_spans.scope_spans.into_iter().flat_map(|s| s.spans) {
+            let start_ns = number(&span.start_time_unix_nano);
+            let end_ns = number(&span.end_time_unix_nano);
+            let kind = match &span.kind {
+                Value::Number(n) => match n.as_u64() {
+                    Some(2) => SpanKind::Server,
+                    Some(3) => SpanKind::Client,
+                    Some(4) => SpanKind::Producer,
+                    Some(5) => SpanKind::Consumer,
+                    _ => SpanKind::Internal,
+                },
+                Value::String(s) => match s.as_str() {
+                    "SPAN_KIND_SERVER" => SpanKind::Server,
+                    "SPAN_KIND_CLIENT" => SpanKind::Client,
+                    "SPAN_KIND_PRODUCER" => SpanKind::Producer,
+                    "SPAN_KIND_CONSUMER" => SpanKind::Consumer,
+                    _ => SpanKind::Internal,
+                },
+                _ => SpanKind::Internal,
+            };
+            let is_error = span.status.is_some_and(|status| {
+                status.code.as_u64() == Some(2) || status.code.as_str() == Some("STATUS_CODE_ERROR")
+            });
+
+            spans.push(Span {
+                trace_id: span.trace_id,
+                span_id: span.span_id,
+                parent_id: Some(span.parent_span_id).filter(|id| !id.is_empty()),
+                follows_parent: false,
+                service: service.clone(),
+                operation: span.name,
+                kind,
+                start_ms: start_ns / 1_000_000.0,
+                duration_ms: (end_ns - start_ns).max(0.0) / 1_000_000.0,
+                is_error,
+            });
+        }
+    }
+    Ok(spans)
+}
+
+/// Read an OTLP/JSON integer, which may be encoded as a string
+fn number(value: &Value) -> f64 {
+    match value {
+        Value::String(s) => s.parse().unwrap_or_default(),
+        _ => value.as_f64().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two documents, one per line, as the collector's file exporter writes them
+    const DUMP: &str = r#"{"resourceSpans": [{"resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "frontend"}}]}, "scopeSpans": [{"spans": [{"traceId": "t", "spanId": "a", "name": "GET /home", "kind": 2, "startTimeUnixNano": "1000000000", "endTimeUnixNano": "1040000000", "status": {"code": 2}}]}]}]}
+{"resourceSpans": [{"resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "backend"}}]}, "scopeSpans": [{"spans": [{"traceId": "t", "spanId": "b", "parentSpanId": "a", "name": "get", "kind": "SPAN_KIND_CLIENT", "startTimeUnixNano": 1010000000, "endTimeUnixNano": 1030000000}]}]}]}
+"#;
+
+    #[test]
+    fn line_delimited_dumps_are_read_into_spans() {
+        let path = std::env::temp_dir().join(format!("otlp-{}.json", std::process::id()));
+        fs::write(&path, DUMP).unwrap();
+        let spans = read_otlp_spans(&path);
+        fs::remove_file(&path).unwrap();
+        let spans = spans.unwrap();
+
+        let (home, get) = (&spans[0], &spans[1]);
+        assert_eq!(
+            (home.service.as_str(), home.operation.as_str()),
+            ("frontend", "GET /home")
+        );
+        assert_eq!(home.kind, SpanKind::Server);
+        assert_eq!((home.start_ms, home.duration_ms), (1000.0, 40.0));
+        assert!(home.is_error && home.parent_id.is_none());
+
+        assert_eq!(get.service, "backend");
+        assert_eq!(get.kind, SpanKind::Client);
+        assert_eq!((get.start_ms, get.duration_ms), (1010.0, 20.0));
+        assert_eq!(get.parent_id.as_deref(), Some("a"));
+        assert!(!get.is_error);
+    }
+}
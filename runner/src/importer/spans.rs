@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use super::{CallGraphBuilder, ObservedCall, sanitize_name};
+
+/// Role of a span in a call, as in OpenTelemetry's span kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    Server,
+    Client,
+    Producer,
+    Consumer,
+    Internal,
+}
+
+/// A span normalized from any of the supported trace formats
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_id: Option<String>,
+    /// Whether the parent merely caused this span (a follows-from reference)
+    pub follows_parent: bool,
+    pub service: String,
+    pub operation: String,
+    pub kind: SpanKind,
+    pub start_ms: f64,
+    pub duration_ms: f64,
+    pub is_error: bool,
+}
+
+impl Span {
+    /// Client and producer spans only record the caller's side of a call
+    fn is_outgoing(&self) -> bool {
+        matches!(self.kind, SpanKind::Client | SpanKind::Producer)
+    }
+}
+
+/// Record the invocations found in a set of spans, grouped into traces by trace id.
+///
+/// Server, consumer and internal spans are invocations of `service.operation`; internal
+/// spans of the caller's own service are folded into it. A call's timing is taken from
+/// the caller's client span when there is one, and calls made through a producer span, to
+/// a consumer span or via a follows-from reference are async.
+pub fn record_spans(builder: &mut CallGraphBuilder, spans: Vec<Span>) {
+    let mut traces: HashMap<String, Vec<Span>> = HashMap::new();
+    for span in spans {
+        traces.entry(span.trace_id.clone()).or_default().push(span);
+    }
+    for spans in traces.values() {
+        record_trace(builder, spans);
+    }
+}
+
+fn record_trace(builder: &mut CallGraphBuilder, spans: &[Span]) {
+    let by_id: HashMap<&str, usize> = spans
+        .iter()
+        .enumerate()
+        .map(|(index, span)| (span.span_id.as_str(), index))
+        .collect();
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (index, span) in spans.iter().enumerate() {
+        match span
+            .parent_id
+            .as_deref()
+            .and_then(|parent| by_id.get(parent))
+        {
+            Some(&parent) if parent != index => children.entry(parent).or_default().push(index),
+            _ => roots.push(index),
+        }
+    }
+
+    let tree = SpanTree { spans, children };
+    for root in roots {
+        for found in tree.entry_calls(root) {
+            builder.record_entry(&found.call.service, &found.call.method);
+            tree.record_invocation(builder, found.index);
+        }
+    }
+}
+
+struct SpanTree<'a> {
+    spans: &'a [Span],
+    children: HashMap<usize, Vec<usize>>,
+}
+
+/// A call found below an invocation, pointing at the callee's span
+struct FoundCall {
+    index: usize,
+    call: ObservedCall,
+}
+
+impl SpanTree<'_> {
+    fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.children.get(&index).into_iter().flatten().copied()
+    }
+
+    fn observed(&self, index: usize, outgoing: Option<usize>, is_async: bool) -> ObservedCall {
+        let span = &self.spans[index];
+        let timing = &self.spans[outgoing.unwrap_or(index)];
+        ObservedCall {
+            service: sanitize_name(&span.service),
+            method: sanitize_name(&span.operation),
+            start_ms: Some(timing.start_ms),
+            duration_ms: timing.duration_ms,
+            is_error: span.is_error,
+            is_async: is_async || span.kind == SpanKind::Consumer || span.follows_parent,
+        }
+    }
+
+    /// Invocations through which a request entered the system at the root span `index`
+    fn entry_calls(&self, index: usize) -> Vec<FoundCall> {
+        let span = &self.spans[index];
+        if span.is_outgoing() {
+            // The caller isn't part of the traces, so whatever it called entered the system
+            self.calls_below(index, span, Some(index))
+        } else {
+            vec![FoundCall {
+                index,
+                call: self.observed(index, None, false),
+            }]
+        }
+    }
+
+    /// Record the invocation at `index` with its calls, then the callees' invocations
+    fn record_invocation(&self, builder: &mut CallGraphBuilder, index: usize) {
+        let calls = self.calls_below(index, &self.spans[index], None);
+        let observed: Vec<ObservedCall> = calls.iter().map(|found| found.call.clone()).collect();
+        builder.record_invocation(&self.observed(index, None, false), &observed);
+        for found in calls {
+            self.record_invocation(builder, found.index);
+        }
+    }
+
+    /// Invocations of other services made from below the span at `index`
+    fn calls_below(&self, index: usize, caller: &Span, outgoing: Option<usize>) -> Vec<FoundCall> {
+        let mut calls = Vec::new();
+        for child in self.children(index) {
+            let span = &self.spans[child];
+            if span.is_outgoing() {
+                calls.extend(self.calls_below(child, caller, Some(child)));
+            } else if span.service == caller.service && span.kind == SpanKind::Internal {
+                calls.extend(self.calls_below(child, caller, outgoing));
+            } else {
+                let is_async = outgoing
+                    .is_some_and(|outgoing| self.spans[outgoing].kind == SpanKind::Producer);
+                calls.push(FoundCall {
+                    index: child,
+                    call: self.observed(child, outgoing, is_async),
+                });
+            }
+        }
+        calls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: &str, parent: Option<&str>, service: &str, kind: SpanKind) -> Span {
+        Span {
+            trace_id: "t".to_string(),
+            span_id: id.to_string(),
+            parent_id: parent.map(str::to_string),
+            follows_parent: false,
+            service: service.to_string(),
+            operation: id.to_string(),
+            kind,
+            start_ms: 0.0,
+            duration_ms: 0.0,
+            is_error: false,
+        }
+    }
+
+    fn timed(mut span: Span, start_ms: f64, duration_ms: f64) -> Span {
+        span.start_ms = start_ms;
+        span.duration_ms = duration_ms;
+        span
+    }
+
+    /// A load generator's client span calling `frontend.home`, which calls `backend.get`
+    /// and publishes a message that `worker.process` consumes
+    fn client_rooted_trace() -> Vec<Span> {
+        vec![
+            timed(
+                span("home", Some("load"), "frontend", SpanKind::Server),
+                5.0,
+                40.0,
+            ),
+            timed(span("load", None, "loadgen", SpanKind::Client), 0.0, 50.0),
+            timed(
+                span("call", Some("home"), "frontend", SpanKind::Client),
+                10.0,
+                20.0,
+            ),
+            timed(
+                span("get", Some("call"), "backend", SpanKind::Server),
+                12.0,
+                15.0,
+            ),
+            timed(
+                span("send", Some("home"), "frontend", SpanKind::Producer),
+                32.0,
+                1.0,
+            ),
+            timed(
+                span("process", Some("send"), "worker", SpanKind::Consumer),
+                100.0,
+                5.0,
+            ),
+        ]
+    }
+
+    fn entry_calls(spans: &[Span], root: usize) -> Vec<ObservedCall> {
+        let tree = SpanTree {
+            spans,
+            children: HashMap::from([(root, vec![0])]),
+        };
+        tree.entry_calls(root)
+            .into_iter()
+            .map(|found| found.call)
+            .collect()
+    }
+
+    #[test]
+    fn client_roots_give_the_entry_call_their_timing() {
+        let calls = entry_calls(&client_rooted_trace(), 1);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].target(), "frontend.home");
+        assert_eq!((calls[0].start_ms, calls[0].duration_ms), (Some(0.0), 50.0));
+        assert!(!calls[0].is_async);
+    }
+
+    #[test]
+    fn producer_roots_make_async_entry_calls() {
+        let spans = [
+            span("process", Some("tick"), "worker", SpanKind::Consumer),
+            span("tick", None, "cron", SpanKind::Producer),
+        ];
+        let calls = entry_calls(&spans, 1);
+        assert_eq!(calls[0].target(), "worker.process");
+        assert!(calls[0].is_async);
+    }
+
+    #[test]
+    fn client_and_producer_spans_become_calls() {
+        let mut builder = CallGraphBuilder::default();
+        record_spans(&mut builder, client_rooted_trace());
+        let config = builder.build(1.0, Some(1.0)).unwrap();
+
+        let entry_points = config.load.unwrap().entry_points;
+        assert_eq!(entry_points.len(), 1);
+        assert_eq!(entry_points[0].service, "frontend");
+        assert_eq!(entry_points[0].method, "home");
+        let home = &config.services["frontend"].methods["home"];
+        assert_eq!(home.calls, [vec!["backend.get"]]);
+        assert_eq!(home.async_calls, ["worker.process"]);
+        // 40 ms minus the 20 ms the client span waited on `backend`
+        assert_eq!(home.latency_distribution.parameters["p50"], 20.0);
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use prost_types::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tonic::transport::Channel;
+use tracing::{debug, info, warn};
+
+use crate::metrics::LiveMetrics;
+use crate::parser::{EntryPoint, LoadConfig};
+use crate::service_proto::service_client::ServiceClient;
+use crate::service_proto::{CallData, ServiceRequest};
+
+/// How long to wait for entry point services to accept connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Outcome of one request sent to an entry point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestRecord {
+    /// Entry point as "service.method"
+    pub entry_point: String,
+    /// Milliseconds since the UNIX epoch
+    pub sent_at_ms: f64,
+    pub latency_ms: f64,
+    pub was_an_error: bool,
+    /// Calls the entry point made, nested by caller
+    pub calls: Vec<CallRecord>,
+    /// Faults injected into the deployment when the request was sent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub faults: Vec<String>,
+}
+
+/// One downstream call attempt as reported back by the caller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub service_name: String,
+    pub method_name: String,
+    pub sent_at_ms: f64,
+    pub received_at_ms: f64,
+    pub was_an_error: bool,
+    #[serde(default)]
+    pub was_async: bool,
+    #[serde(default)]
+    pub calls: Vec<CallRecord>,
+    /// Index of the caller's `calls` stage; async calls come after the last one
+    #[serde(default)]
+    pub stage: u32,
+    /// Retry round within the stage, starting at 0
+    #[serde(default)]
+    pub attempt: u32,
+}
+
+impl CallRecord {
+    pub fn callee(&self) -> String {
+        format!("{}.{}", self.service_name, self.method_name)
+    }
+
+    pub fn duration_ms(&self) -> f64 {
+        self.received_at_ms - self.sent_at_ms
+    }
+}
+
+impl From<CallData> for CallRecord {
+    fn from(call: CallData) -> Self {
+        CallRecord {
+            service_name: call.service_name,
+            method_name: call.method_name,
+            sent_at_ms: timestamp_ms(call.request_sent_at),
+            received_at_ms: timestamp_ms(call.response_received_at),
+            was_an_error: call.was_an_error,
+            was_async: call.was_async,
+            calls: call.calls.into_iter().map(CallRecord::from).collect(),
+            stage: call.stage,
+            attempt: call.attempt,
+        }
+    }
+}
+
+fn timestamp_ms(timestamp: Option<Timestamp>) -> f64 {
+    timestamp
+        .map(|t| t.seconds as f64 * 1000.0 + t.nanos as f64 / 1_000_000.0)
+        .unwrap_or_default()
+}
+
+pub fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before UNIX EPOCH")
+        .as_secs_f64()
+        * 1000.0
+}
+
+/// Drive every entry point at its configured rate until `stop` flips to true.
+///
+/// Requests are open-loop: a slow service does not reduce the offered load. An entry point
+/// with several replicas gets its requests round-robin, like behind a front load balancer.
+/// Every finished request is fed to `live` and returned at the end.
+pub async fn run_load(
+    load: &LoadConfig,
+    host_ports: &HashMap<String, Vec<u16>>,
+    live: Arc<LiveMetrics>,
+    stop: watch::Receiver<bool>,
+) -> Result<Vec<RequestRecord>> {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let mut generators = Vec::new();
+
+    for entry_point in &load.entry_points {
+        let replica_ports = host_ports
+            .get(&entry_point.service)
+            .filter(|ports| !ports.is_empty())
+            .with_context(|| format!("No host port for service {}", entry_point.service))?;
+        let mut clients = Vec::new();
+        for host_port in replica_ports {
+            clients.push(connect_entry_point(*host_port).await?);
+        }
+        info!(
+            "Generating {} rps against {}.{}",
+            entry_point.requests_per_second, entry_point.service, entry_point.method
+        );
+        generators.push(tokio::spawn(generate(
+            entry_point.clone(),
+            clients,
+            live.clone(),
+            records.clone(),
+            stop.clone(),
+        )));
+    }
+
+    for generator in generators {
+        generator.await?;
+    }
+
+    let records = std::mem::take(&mut *records.lock().unwrap());
+    info!("Load generation finished after {} requests", records.len());
+    Ok(records)
+}
+
+/// Connect to an entry point, retrying while its container starts
+async fn connect_entry_point(host_port: u16) -> Result<ServiceClient<Channel>> {
+    let url = format!("http://localhost:{}", host_port);
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        match ServiceClient::connect(url.clone()).await {
+            Ok(client) => return Ok(client),
+            Err(e) if Instant::now() < deadline => {
+                debug!("Waiting for {}: {}", url, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Err(e) => anyhow::bail!("Failed to connect to entry point at {}: {}", url, e),
+        }
+    }
+}
+
+async fn generate(
+    entry_point: EntryPoint,
+    clients: Vec<ServiceClient<Channel>>,
+    live: Arc<LiveMetrics>,
+    records: Arc<Mutex<Vec<RequestRecord>>>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let name = format!("{}.{}", entry_point.service, entry_point.method);
+    let period = Duration::from_secs_f64(1.0 / entry_point.requests_per_second as f64);
+    let mut interval = tokio::time::interval(period);
+    let mut in_flight = Vec::new();
+    let mut sent = 0;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stop.changed() => break,
+        }
+        if *stop.borrow() {
+            break;
+        }
+
+        let mut client = clients[sent % clients.len()].clone();
+        sent += 1;
+        let name = name.clone();
+        let method_name = entry_point.method.clone();
+        let live = live.clone();
+        let records = records.clone();
+        in_flight.push(tokio::spawn(async move {
+            let sent_at_ms = now_ms();
+            let started = Instant::now();
+            let response = client
+                .get_data(ServiceRequest {
+                    method_name,
+                    call_path: Vec::new(),
+                })
+                .await;
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let record = match response {
+                Ok(response) => RequestRecord {
+                    entry_point: name,
+                    sent_at_ms,
+                    latency_ms,
+                    was_an_error: false,
+                    calls: response
+                        .into_inner()
+                        .calls
+                        .into_iter()
+                        .map(CallRecord::from)
+                        .collect(),
+                    faults: Vec::new(),
+                },
+                Err(e) => {
+                    warn!("Request to {} failed: {}", name, e.message());
+                    RequestRecord {
+                        entry_point: name,
+                        sent_at_ms,
+                        latency_ms,
+                        was_an_error: true,
+                        calls: Vec::new(),
+                        faults: Vec::new(),
+                    }
+                }
+            };
+            live.record(&record);
+            records.lock().unwrap().push(record);
+        }));
+        in_flight.retain(|task| !task.is_finished());
+    }
+
+    // Let requests already sent finish so the tail of the run is not lost
+    for task in in_flight {
+        let _ = task.await;
+    }
+}
@@ -1,23 +1,49 @@
-use anyhow::Result;
-use client::cli::CliOptions;
+use anyhow::{Context, Result, bail};
+use client::cli::{CliOptions, Command};
+use generator::graph::GraphFormat;
+use generator::topology::TopologyParams;
+use importer::TraceFormat;
+use metrics::LiveMetrics;
 use orchestrator::launch_simulation_from_yaml;
-use tokio;
+use parser::ConfigFormat;
+use report::compare::{self, RegressionThresholds};
+use report::slo::{self, SloResult};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::error;
+use validator::diagnostics::Severity;
 
+mod analysis;
 mod client;
+mod experiment;
 mod generator;
+mod importer;
+mod loadgen;
+mod metrics;
 mod orchestrator;
 mod parser;
+mod report;
 mod server;
 mod validator;
 
+const HTTP_PORT: u16 = 8080;
+const GRPC_PORT: u16 = 50052;
+
 // Include the generated proto code
 pub mod proto {
     tonic::include_proto!("sim");
 }
 
-async fn run_from_input(opts: &CliOptions) -> Result<()> {
-    // Parse JSON file
-    let config = parser::json::parse_json_file(&opts.input)?;
+// Client stubs of the generic service, shared with ../generic-service
+pub mod service_proto {
+    tonic::include_proto!("service");
+}
+
+async fn run_from_input(opts: &CliOptions, input: &Path) -> Result<()> {
+    // Parse the config file
+    let variables = opts.variables.iter().cloned().collect();
+    let config = parser::parse_config_file(input, &variables)?;
 
     // Validate config
     validator::validate_config(&config)?;
@@ -27,28 +53,398 @@ async fn run_from_input(opts: &CliOptions) -> Result<()> {
     // let path = "submitted_config.yaml";
     // tokio::fs::write(path, &yaml_str).await?;
 
-    launch_simulation_from_yaml(config).await?;
+    // Serve the live metrics stream while the simulation runs
+    let live = serve_live_metrics(opts);
+    let report = launch_simulation_from_yaml(config, live).await?;
+
+    // Fail the run when an SLO was missed, for automated gating
+    check_slos(&report.slos, false)
+}
+
+/// Start the HTTP and gRPC servers in the background, streaming the returned live metrics
+fn serve_live_metrics(opts: &CliOptions) -> Arc<LiveMetrics> {
+    let live = LiveMetrics::new();
+    let orchestrator_addr = opts.orchestrator.clone();
+    let http_live = Some(live.clone());
+    tokio::spawn(async move {
+        if let Err(e) =
+            server::http::start_http_server(HTTP_PORT, orchestrator_addr, http_live).await
+        {
+            error!("HTTP server failed: {}", e);
+        }
+    });
+    let orchestrator_addr = opts.orchestrator.clone();
+    let grpc_live = Some(live.clone());
+    tokio::spawn(async move {
+        if let Err(e) =
+            server::grpc::start_grpc_server(GRPC_PORT, orchestrator_addr, grpc_live).await
+        {
+            error!("gRPC server failed: {}", e);
+        }
+    });
+    live
+}
+
+async fn run_experiment(
+    opts: &CliOptions,
+    spec: &Path,
+    variables: &HashMap<String, String>,
+    dry_run: bool,
+) -> Result<()> {
+    let combinations = experiment::load_experiment(spec, variables)?;
+    if dry_run {
+        for (index, combination) in combinations.iter().enumerate() {
+            println!(
+                "{:>4}  {}",
+                index,
+                experiment::describe(&combination.parameters)
+            );
+        }
+        println!("{} combinations, all valid", combinations.len());
+        return Ok(());
+    }
+
+    let live = serve_live_metrics(opts);
+    let (report, directory) = experiment::run_experiment(combinations, live).await?;
+    println!(
+        "{:>4}  {:>12}  {:>8}  {:>10}  parameters",
+        "#", "throughput", "errors", "worst p99"
+    );
+    for row in &report.rows {
+        let worst_p99 = row
+            .entry_points
+            .iter()
+            .map(|stats| stats.p99_ms)
+            .fold(0.0, f64::max);
+        let parameters: Vec<String> = row
+            .parameters
+            .iter()
+            .map(|(path, value)| format!("{}={}", path, value))
+            .collect();
+        match &row.error {
+            Some(err) => println!(
+                "{:>4}  failed: {}  {}",
+                row.index,
+                err,
+                parameters.join(", ")
+            ),
+            None => println!(
+                "{:>4}  {:>8.1} rps  {:>7.2}%  {:>7.1} ms  {}",
+                row.index,
+                row.throughput_rps,
+                row.error_rate * 100.0,
+                worst_p99,
+                parameters.join(", ")
+            ),
+        }
+    }
+    println!("Report written to {}", directory.display());
+    Ok(())
+}
+
+fn run_validate(input: &Path, variables: &HashMap<String, String>, json: bool) -> Result<()> {
+    let config = parser::parse_config_file(input, variables)?;
+    let diagnostics = validator::diagnose_config(&config);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+    } else {
+        for diagnostic in &diagnostics {
+            println!("{}: {}", diagnostic.severity, diagnostic);
+        }
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("{} has {} errors", input.display(), errors);
+    }
+    if !json {
+        let warnings = diagnostics.len() - errors;
+        if warnings > 0 {
+            println!("{} is valid with {} warnings", input.display(), warnings);
+        } else {
+            println!("{} is valid", input.display());
+        }
+    }
+    Ok(())
+}
+
+fn run_plan(
+    input: &Path,
+    variables: &HashMap<String, String>,
+    threshold: f64,
+    json: bool,
+) -> Result<()> {
+    let config = parser::parse_config_file(input, variables)?;
+    validator::validate_config(&config)?;
 
+    let plan = analysis::load::plan_load(&config, threshold);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<30} {:>12} {:>10} {:>12} {:>12}  status",
+        "service", "arrival rps", "busy", "concurrent", "utilization"
+    );
+    for service in &plan.services {
+        println!(
+            "{:<30} {:>12.2} {:>10.2} {:>12.2} {:>12}  {:?}",
+            service.service,
+            service.arrival_rps,
+            service.busy_time,
+            service.concurrent_requests,
+            service
+                .utilization
+                .map_or("-".to_string(), |u| format!("{:.0}%", u * 100.0)),
+            service.status
+        );
+    }
+    Ok(())
+}
+
+fn run_estimate(
+    input: &Path,
+    variables: &HashMap<String, String>,
+    samples: usize,
+    seed: u64,
+    json: bool,
+) -> Result<()> {
+    let config = parser::parse_config_file(input, variables)?;
+    validator::validate_config(&config)?;
+
+    let estimates = analysis::latency::estimate_latencies(&config, samples, seed);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&estimates)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<30} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "entry point", "mean", "p50", "p90", "p99", "p99.9", "errors"
+    );
+    for estimate in &estimates {
+        println!(
+            "{:<30} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>9.2}%",
+            estimate.entry_point,
+            estimate.mean_ms,
+            estimate.p50_ms,
+            estimate.p90_ms,
+            estimate.p99_ms,
+            estimate.p999_ms,
+            estimate.error_probability * 100.0
+        );
+    }
+    Ok(())
+}
+
+fn run_graph(
+    input: &Path,
+    variables: &HashMap<String, String>,
+    format: GraphFormat,
+    annotate: bool,
+    output: Option<&Path>,
+) -> Result<()> {
+    let config = parser::parse_config_file(input, variables)?;
+    validator::validate_config(&config)?;
+
+    let graph = generator::graph::generate_graph(&config, format, annotate);
+    match output {
+        Some(path) => std::fs::write(path, graph)
+            .with_context(|| format!("Failed to write graph to {}", path.display()))?,
+        None => print!("{}", graph),
+    }
+    Ok(())
+}
+
+fn run_compare(
+    baseline: &Path,
+    candidate: &Path,
+    thresholds: &RegressionThresholds,
+    json: bool,
+) -> Result<()> {
+    let comparison = compare::compare_runs(baseline, candidate, thresholds)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        let percent = |change: Option<f64>| {
+            change.map_or("-".to_string(), |change| format!("{:+.1}%", change * 100.0))
+        };
+        println!(
+            "{:<40} {:>9} {:>9} {:>9} {:>11} {:>10} {:>9}",
+            "series", "p50", "p90", "p99", "throughput", "errors", "p-value"
+        );
+        for (kind, diffs) in [
+            ("entry points", &comparison.entry_points),
+            ("edges", &comparison.edges),
+        ] {
+            println!("{}:", kind);
+            for diff in diffs {
+                let status = match (&diff.baseline, &diff.candidate) {
+                    (Some(_), None) => " (baseline only)",
+                    (None, Some(_)) => " (candidate only)",
+                    _ => "",
+                };
+                println!(
+                    "  {:<38} {:>9} {:>9} {:>9} {:>11} {:>+9.2}% {:>9}{}",
+                    diff.name,
+                    percent(diff.p50_change),
+                    percent(diff.p90_change),
+                    percent(diff.p99_change),
+                    percent(diff.throughput_change),
+                    diff.error_rate_delta * 100.0,
+                    diff.latency_p_value
+                        .map_or("-".to_string(), |p| format!("{:.4}", p)),
+                    status
+                );
+                for regression in &diff.regressions {
+                    println!("    REGRESSION: {}", regression);
+                }
+            }
+        }
+    }
+
+    let regressions = comparison.regressions();
+    if regressions > 0 {
+        bail!("{} series regressed", regressions);
+    }
+    if !json {
+        println!("No regressions");
+    }
+    Ok(())
+}
+
+fn run_slo(
+    run_dir: &Path,
+    config: Option<&Path>,
+    variables: &HashMap<String, String>,
+    json: bool,
+) -> Result<()> {
+    let slos = match config {
+        Some(path) => parser::parse_config_file(path, variables)?.slos,
+        None => report::read_config(run_dir)?.slos,
+    };
+    let run = report::read_report(run_dir)?;
+    let records = report::read_records(run_dir)?;
+    let results = slo::evaluate_slos(&slos, &records, run.started_at_ms, run.duration_seconds);
+    check_slos(&results, json)
+}
+
+/// Print SLO results, failing when any SLO was missed
+fn check_slos(results: &[SloResult], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(results)?);
+    } else if !results.is_empty() {
+        let seconds = |value: Option<f64>| value.map_or("-".to_string(), |s| format!("{:.0}s", s));
+        println!(
+            "{:<40} {:>10} {:>10} {:>12} {:>15}",
+            "slo", "windows", "compliance", "budget burn", "first violation"
+        );
+        for result in results {
+            println!(
+                "{:<40} {:>10} {:>9.1}% {:>12} {:>15}  {}",
+                format!(
+                    "{} ({}s windows)",
+                    result.entry_point, result.window_seconds
+                ),
+                "",
+                result.compliance * 100.0,
+                "",
+                seconds(result.first_violation_seconds),
+                if result.passed { "PASS" } else { "FAIL" }
+            );
+            for objective in &result.objectives {
+                println!(
+                    "  {:<38} {:>10} {:>9.1}% {:>12} {:>15}",
+                    objective.objective,
+                    objective.windows,
+                    objective.compliance * 100.0,
+                    objective
+                        .budget_burn
+                        .map_or("-".to_string(), |burn| format!("{:.1}%", burn * 100.0)),
+                    seconds(objective.first_violation_seconds)
+                );
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|result| !result.passed).count();
+    if failed > 0 {
+        bail!("{} of {} SLOs failed", failed, results.len());
+    }
+    if !json && !results.is_empty() {
+        println!("All SLOs passed");
+    }
+    Ok(())
+}
+
+fn run_render(
+    input: &Path,
+    variables: &HashMap<String, String>,
+    format: ConfigFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let config = parser::parse_config_file(input, variables)?;
+    let dump = parser::dump_config(&config, format)?;
+    match output {
+        Some(path) => std::fs::write(path, dump)
+            .with_context(|| format!("Failed to write config to {}", path.display()))?,
+        None => print!("{}", dump),
+    }
+    Ok(())
+}
+
+fn run_import(
+    inputs: &[PathBuf],
+    format: TraceFormat,
+    rate_scale: f64,
+    duration: Option<f64>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let config = importer::import_traces(format, inputs, rate_scale, duration)?;
+    validator::validate_config(&config).context("Imported config is invalid")?;
+
+    let json = serde_json::to_string_pretty(&config)?;
+    match output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("Failed to write config to {}", path.display()))?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn run_generate(params: &TopologyParams, output: Option<&Path>) -> Result<()> {
+    let config = generator::topology::generate_topology(params)?;
+
+    let json = serde_json::to_string_pretty(&config)?;
+    match output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("Failed to write config to {}", path.display()))?,
+        None => println!("{}", json),
+    }
     Ok(())
 }
 
 async fn run_as_server(opts: &CliOptions) -> Result<()> {
     // Start servers for receiving input
-    let http_port = 8080;
-    let grpc_port = 50052;
+    let http_port = HTTP_PORT;
+    let grpc_port = GRPC_PORT;
 
-    // Run both servers concurrently
+    // Run both servers concurrently. Submitted configs run on the orchestrator, which
+    // doesn't report back, so there are no live metrics to stream
     let orchestrator_addr = opts.orchestrator.clone();
-    let http_handle =
-        tokio::spawn(
-            async move { server::http::start_http_server(http_port, orchestrator_addr).await },
-        );
+    let http_handle = tokio::spawn(async move {
+        server::http::start_http_server(http_port, orchestrator_addr, None).await
+    });
 
     let orchestrator_addr = opts.orchestrator.clone();
-    let grpc_handle =
-        tokio::spawn(
-            async move { server::grpc::start_grpc_server(grpc_port, orchestrator_addr).await },
-        );
+    let grpc_handle = tokio::spawn(async move {
+        server::grpc::start_grpc_server(grpc_port, orchestrator_addr, None).await
+    });
 
     println!("Input parser service started:");
     println!("  - HTTP server running on port {}", http_port);
@@ -70,11 +466,106 @@ async fn main() -> Result<()> {
     // Parse command line arguments
     let opts = client::cli::parse_cli_args();
 
+    if let Some(command) = &opts.command {
+        let variables: HashMap<String, String> = opts.variables.iter().cloned().collect();
+        return match command {
+            Command::Validate { input, json } => run_validate(input, &variables, *json),
+            Command::Plan {
+                input,
+                threshold,
+                json,
+            } => run_plan(input, &variables, *threshold, *json),
+            Command::Estimate {
+                input,
+                samples,
+                seed,
+                json,
+            } => run_estimate(input, &variables, *samples, *seed, *json),
+            Command::Graph {
+                input,
+                format,
+                annotate,
+                output,
+            } => run_graph(input, &variables, *format, *annotate, output.as_deref()),
+            Command::Experiment { spec, dry_run } => {
+                run_experiment(&opts, spec, &variables, *dry_run).await
+            }
+            Command::Compare {
+                baseline,
+                candidate,
+                max_latency_increase,
+                max_throughput_decrease,
+                max_error_rate_increase,
+                alpha,
+                json,
+            } => {
+                let thresholds = RegressionThresholds {
+                    max_latency_increase: *max_latency_increase,
+                    max_throughput_decrease: *max_throughput_decrease,
+                    max_error_rate_increase: *max_error_rate_increase,
+                    significance_level: *alpha,
+                };
+                run_compare(baseline, candidate, &thresholds, *json)
+            }
+            Command::Slo {
+                run_dir,
+                config,
+                json,
+            } => run_slo(run_dir, config.as_deref(), &variables, *json),
+            Command::Render {
+                input,
+                format,
+                output,
+            } => run_render(input, &variables, *format, output.as_deref()),
+            Command::Import {
+                inputs,
+                format,
+                rate_scale,
+                duration,
+                output,
+            } => run_import(inputs, *format, *rate_scale, *duration, output.as_deref()),
+            Command::Generate {
+                services,
+                depth,
+                methods,
+                fan_out,
+                fan_out_mean,
+                max_fan_out,
+                preferential_exponent,
+                storage_share,
+                latency_min,
+                latency_max,
+                latency_cv,
+                error_min,
+                error_max,
+                rps,
+                seed,
+                output,
+            } => {
+                let params = TopologyParams {
+                    services: *services,
+                    depth: *depth,
+                    methods_per_service: *methods,
+                    fan_out: *fan_out,
+                    fan_out_mean: *fan_out_mean,
+                    max_fan_out: *max_fan_out,
+                    preferential_exponent: *preferential_exponent,
+                    storage_share: *storage_share,
+                    latency_mean_ms: (*latency_min, *latency_max),
+                    latency_cv: *latency_cv,
+                    error_rate: (*error_min, *error_max),
+                    entry_rps: *rps,
+                    seed: *seed,
+                };
+                run_generate(&params, output.as_deref())
+            }
+        };
+    }
+
     // If input file is provided, process it directly
-    if opts.input.exists() {
-        run_from_input(&opts).await?;
-    } else {
-        run_as_server(&opts).await?;
+    match &opts.input {
+        Some(input) if input.exists() => run_from_input(&opts, input).await?,
+        _ => run_as_server(&opts).await?,
     }
 
     Ok(())
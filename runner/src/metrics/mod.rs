@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::loadgen::{CallRecord, RequestRecord, now_ms};
+use crate::proto;
+
+/// Length of the window each live snapshot covers
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Request counts and latency percentiles of one entry point, service or edge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesStats {
+    /// Entry point or service name, "caller -> callee" for edges
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callee: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub throughput_rps: f64,
+    pub error_rate: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Metrics of all entry points, services and edges over one window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// End of the window, milliseconds since the UNIX epoch
+    pub timestamp_ms: u64,
+    pub window_seconds: f64,
+    pub entry_points: Vec<SeriesStats>,
+    pub services: Vec<SeriesStats>,
+    pub edges: Vec<SeriesStats>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    latencies_ms: Vec<f64>,
+    errors: u64,
+}
+
+impl Samples {
+    fn add(&mut self, latency_ms: f64, was_an_error: bool) {
+        self.latencies_ms.push(latency_ms);
+        if was_an_error {
+            self.errors += 1;
+        }
+    }
+
+    fn stats(&self, name: String, window_seconds: f64) -> SeriesStats {
+        let mut sorted = self.latencies_ms.clone();
+        sorted.sort_by(f64::total_cmp);
+        let requests = sorted.len() as u64;
+        SeriesStats {
+            name,
+            caller: None,
+            callee: None,
+            requests,
+            errors: self.errors,
+            throughput_rps: requests as f64 / window_seconds,
+            error_rate: if requests == 0 {
+                0.0
+            } else {
+                self.errors as f64 / requests as f64
+            },
+            p50_ms: percentile(&sorted, 0.50),
+            p90_ms: percentile(&sorted, 0.90),
+            p99_ms: percentile(&sorted, 0.99),
+        }
+    }
+}
+
+/// Nearest-rank percentile of already sorted values, 0 when empty
+pub fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Groups request records by entry point, service and caller -> callee edge
+#[derive(Debug, Default)]
+pub struct Aggregator {
+    entry_points: BTreeMap<String, Samples>,
+    services: BTreeMap<String, Samples>,
+    edges: BTreeMap<(String, String), Samples>,
+}
+
+impl Aggregator {
+    pub fn record(&mut self, record: &RequestRecord) {
+        self.entry_points
+            .entry(record.entry_point.clone())
+            .or_default()
+            .add(record.latency_ms, record.was_an_error);
+        let entry_service = record
+            .entry_point
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        self.services
+            .entry(entry_service)
+            .or_default()
+            .add(record.latency_ms, record.was_an_error);
+        self.record_calls(&record.entry_point, &record.calls);
+    }
+
+    fn record_calls(&mut self, caller: &str, calls: &[CallRecord]) {
+        for call in calls {
+            let callee = call.callee();
+            self.services
+                .entry(call.service_name.clone())
+                .or_default()
+                .add(call.duration_ms(), call.was_an_error);
+            self.edges
+                .entry((caller.to_string(), callee.clone()))
+                .or_default()
+                .add(call.duration_ms(), call.was_an_error);
+            self.record_calls(&callee, &call.calls);
+        }
+    }
+
+    /// Latencies of every request to each entry point, in arrival order
+    pub fn entry_point_latencies(&self) -> impl Iterator<Item = (&String, &[f64])> {
+        self.entry_points
+            .iter()
+            .map(|(name, samples)| (name, samples.latencies_ms.as_slice()))
+    }
+
+    /// Latencies of every call along each caller -> callee edge, in arrival order
+    pub fn edge_latencies(&self) -> impl Iterator<Item = (&(String, String), &[f64])> {
+        self.edges
+            .iter()
+            .map(|(edge, samples)| (edge, samples.latencies_ms.as_slice()))
+    }
+
+    pub fn snapshot(&self, timestamp_ms: u64, window_seconds: f64) -> MetricsSnapshot {
+        MetricsSnapshot {
+            timestamp_ms,
+            window_seconds,
+            entry_points: self
+                .entry_points
+                .iter()
+                .map(|(name, samples)| samples.stats(name.clone(), window_seconds))
+                .collect(),
+            services: self
+                .services
+                .iter()
+                .map(|(name, samples)| samples.stats(name.clone(), window_seconds))
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|((caller, callee), samples)| SeriesStats {
+                    caller: Some(caller.clone()),
+                    callee: Some(callee.clone()),
+                    ..samples.stats(format!("{} -> {}", caller, callee), window_seconds)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Aggregates finished requests per second and broadcasts a snapshot to every watcher
+pub struct LiveMetrics {
+    window: Mutex<Aggregator>,
+    sender: broadcast::Sender<MetricsSnapshot>,
+}
+
+impl LiveMetrics {
+    pub fn new() -> Arc<Self> {
+        let (sender, _) = broadcast::channel(64);
+        let live = Arc::new(LiveMetrics {
+            window: Mutex::new(Aggregator::default()),
+            sender,
+        });
+
+        let ticker = live.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+            loop {
+                interval.tick().await;
+                ticker.publish();
+            }
+        });
+
+        live
+    }
+
+    pub fn record(&self, record: &RequestRecord) {
+        self.window.lock().unwrap().record(record);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MetricsSnapshot> {
+        self.sender.subscribe()
+    }
+
+    /// Close the current window and send it to watchers, if there are any
+    fn publish(&self) {
+        let window = std::mem::take(&mut *self.window.lock().unwrap());
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let snapshot = window.snapshot(now_ms() as u64, SNAPSHOT_INTERVAL.as_secs_f64());
+        let _ = self.sender.send(snapshot);
+    }
+}
+
+impl From<SeriesStats> for proto::SeriesMetrics {
+    fn from(stats: SeriesStats) -> Self {
+        proto::SeriesMetrics {
+            name: stats.name,
+            caller: stats.caller.unwrap_or_default(),
+            callee: stats.callee.unwrap_or_default(),
+            requests: stats.requests,
+            errors: stats.errors,
+            throughput_rps: stats.throughput_rps,
+            error_rate: stats.error_rate,
+            p50_ms: stats.p50_ms,
+            p90_ms: stats.p90_ms,
+            p99_ms: stats.p99_ms,
+        }
+    }
+}
+
+impl From<MetricsSnapshot> for proto::MetricsSnapshot {
+    fn from(snapshot: MetricsSnapshot) -> Self {
+        proto::MetricsSnapshot {
+            timestamp_ms: snapshot.timestamp_ms,
+            window_seconds: snapshot.window_seconds,
+            entry_points: snapshot.entry_points.into_iter().map(Into::into).collect(),
+            services: snapshot.services.into_iter().map(Into::into).collect(),
+            edges: snapshot.edges.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant, sleep_until};
+use tonic::transport::Channel;
+use tracing::{info, warn};
+
+use crate::loadgen::{RequestRecord, now_ms};
+use crate::metrics::SeriesStats;
+use crate::orchestrator::container_name;
+use crate::parser::{FaultAction, FaultConfig, LinkConfig};
+use crate::service_proto::admin_client::AdminClient;
+use crate::service_proto::{ConfigPatch, FaultSettings};
+
+/// When a fault was active during a run, and how the entry points fared meanwhile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultWindow {
+    /// E.g. "kill service_gamma" or "slow service_delta x5"
+    pub label: String,
+    pub service: String,
+    /// Milliseconds since the UNIX epoch
+    pub started_at_ms: f64,
+    pub ended_at_ms: f64,
+    /// Problems injecting or reverting the fault; the run carries on regardless
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Metrics of the requests sent while the fault was active
+    #[serde(default)]
+    pub entry_points: Vec<SeriesStats>,
+}
+
+impl FaultWindow {
+    pub fn contains(&self, at_ms: f64) -> bool {
+        self.started_at_ms <= at_ms && at_ms < self.ended_at_ms
+    }
+}
+
+pub fn describe(fault: &FaultConfig) -> String {
+    let target = match fault.replica {
+        Some(replica) => format!("{} replica {}", fault.service, replica),
+        None => fault.service.clone(),
+    };
+    match fault.action {
+        FaultAction::Kill => format!("kill {}", target),
+        FaultAction::Pause => format!("pause {}", target),
+        FaultAction::Slow { latency_multiplier } => {
+            format!("slow {} x{}", target, latency_multiplier)
+        }
+        FaultAction::Error { error_rate } => {
+            format!("error {} {}%", target, error_rate * 100.0)
+        }
+        FaultAction::Partition { ref peers } => {
+            format!("partition {} from {}", target, peers.join(", "))
+        }
+    }
+}
+
+/// Inject every fault at its time, reverting it when its duration passes, until the run
+/// stops; faults still active then are reverted too. `links` are the in-process network
+/// links of each service by callee, which partitions restore when they end.
+pub async fn run_faults(
+    faults: Vec<FaultConfig>,
+    host_ports: HashMap<String, Vec<u16>>,
+    links: HashMap<String, HashMap<String, LinkConfig>>,
+    mut stop: watch::Receiver<bool>,
+) -> Vec<FaultWindow> {
+    let origin = Instant::now();
+    // (seconds, starts, fault index), with ends before starts at the same time
+    let mut transitions: Vec<(f64, bool, usize)> = faults
+        .iter()
+        .enumerate()
+        .flat_map(|(index, fault)| {
+            let end = fault
+                .duration_seconds
+                .map(|duration| (fault.at_seconds + duration, false, index));
+            std::iter::once((fault.at_seconds, true, index)).chain(end)
+        })
+        .collect();
+    transitions.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let mut injector = FaultInjector {
+        active: vec![false; faults.len()],
+        faults,
+        host_ports,
+        links,
+    };
+    let mut windows: Vec<Option<FaultWindow>> = vec![None; injector.faults.len()];
+
+    for (seconds, starts, index) in transitions {
+        tokio::select! {
+            _ = sleep_until(origin + Duration::from_secs_f64(seconds)) => {}
+            _ = stop.wait_for(|stopped| *stopped) => break,
+        }
+        let label = describe(&injector.faults[index]);
+        if starts {
+            info!("Injecting fault: {}", label);
+            let mut window = FaultWindow {
+                label,
+                service: injector.faults[index].service.clone(),
+                started_at_ms: now_ms(),
+                ended_at_ms: f64::INFINITY,
+                errors: Vec::new(),
+                entry_points: Vec::new(),
+            };
+            if let Err(e) = injector.set_active(index, true).await {
+                warn!("Failed to inject fault {}: {:#}", window.label, e);
+                window.errors.push(format!("{:#}", e));
+            }
+            windows[index] = Some(window);
+        } else if let Some(window) = &mut windows[index] {
+            info!("Reverting fault: {}", label);
+            window.ended_at_ms = now_ms();
+            if let Err(e) = injector.set_active(index, false).await {
+                warn!("Failed to revert fault {}: {:#}", label, e);
+                window.errors.push(format!("{:#}", e));
+            }
+        }
+    }
+
+    // Faults without a duration, or cut short by the end of the run
+    for (index, window) in windows.iter_mut().enumerate() {
+        let Some(window) = window.as_mut().filter(|w| w.ended_at_ms.is_infinite()) else {
+            continue;
+        };
+        window.ended_at_ms = now_ms();
+        if let Err(e) = injector.set_active(index, false).await {
+            warn!("Failed to revert fault {}: {:#}", window.label, e);
+            window.errors.push(format!("{:#}", e));
+        }
+    }
+    windows.into_iter().flatten().collect()
+}
+
+/// Tag every request with the faults active when it was sent
+pub fn annotate_records(records: &mut [RequestRecord], windows: &[FaultWindow]) {
+    for record in records {
+        record.faults = windows
+            .iter()
+            .filter(|window| window.contains(record.sent_at_ms))
+            .map(|window| window.label.clone())
+            .collect();
+    }
+}
+
+struct FaultInjector {
+    faults: Vec<FaultConfig>,
+    active: Vec<bool>,
+    /// Host port of every replica, by service
+    host_ports: HashMap<String, Vec<u16>>,
+    /// Configured in-process links, by caller and callee
+    links: HashMap<String, HashMap<String, LinkConfig>>,
+}
+
+impl FaultInjector {
+    async fn set_active(&mut self, index: usize, active: bool) -> Result<()> {
+        self.active[index] = active;
+        let fault = &self.faults[index];
+        let service = &fault.service;
+        if let FaultAction::Partition { peers } = &fault.action {
+            for peer in peers {
+                self.push_link(service, peer).await?;
+                self.push_link(peer, service).await?;
+            }
+            return Ok(());
+        }
+        let replicas = self
+            .host_ports
+            .get(service)
+            .with_context(|| format!("No host port for service {}", service))?
+            .len() as u32;
+        let targets = match fault.replica {
+            Some(replica) => replica..replica + 1,
+            None => 0..replicas,
+        };
+        for replica in targets {
+            let container = container_name(service, replica, replicas);
+            match (&fault.action, active) {
+                (FaultAction::Kill, true) => docker(&["kill", &container]).await?,
+                (FaultAction::Kill, false) => docker(&["start", &container]).await?,
+                (FaultAction::Pause, true) => docker(&["pause", &container]).await?,
+                (FaultAction::Pause, false) => docker(&["unpause", &container]).await?,
+                (FaultAction::Slow { .. } | FaultAction::Error { .. }, _) => {
+                    self.push_settings(service, replica).await?
+                }
+                // Cut off above, for the whole service
+                (FaultAction::Partition { .. }, _) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a replica the combination of the latency and error faults active on it
+    async fn push_settings(&self, service: &str, replica: u32) -> Result<()> {
+        let mut settings = FaultSettings {
+            latency_multiplier: 1.0,
+            error_rate: 0.0,
+        };
+        let active_faults = self
+            .faults
+            .iter()
+            .zip(&self.active)
+            .filter(|(fault, active)| {
+                **active
+                    && fault.service == service
+                    && fault.replica.is_none_or(|target| target == replica)
+            });
+        for (fault, _) in active_faults {
+            match fault.action {
+                FaultAction::Slow { latency_multiplier } => {
+                    settings.latency_multiplier *= latency_multiplier
+                }
+                // Independent chances to fail
+                FaultAction::Error { error_rate } => {
+                    settings.error_rate = 1.0 - (1.0 - settings.error_rate) * (1.0 - error_rate)
+                }
+                FaultAction::Kill | FaultAction::Pause | FaultAction::Partition { .. } => {}
+            }
+        }
+
+        self.admin_client(service, replica)
+            .await?
+            .set_faults(settings)
+            .await
+            .with_context(|| format!("Failed to set faults of {}", service))?;
+        Ok(())
+    }
+
+    /// Send every replica of `caller` its link to `callee`, lost entirely while a partition
+    /// between the two is active
+    async fn push_link(&self, caller: &str, callee: &str) -> Result<()> {
+        let cut = self
+            .faults
+            .iter()
+            .zip(&self.active)
+            .any(|(fault, active)| match &fault.action {
+                FaultAction::Partition { peers } if *active => {
+                    (fault.service == caller && peers.iter().any(|peer| peer == callee))
+                        || (fault.service == callee && peers.iter().any(|peer| peer == caller))
+                }
+                _ => false,
+            });
+        let configured = self
+            .links
+            .get(caller)
+            .and_then(|links| links.get(callee))
+            .copied();
+        let patch = serde_json::json!({"network": {callee: link_patch(configured, cut)}});
+        let replicas = self
+            .host_ports
+            .get(caller)
+            .with_context(|| format!("No host port for service {}", caller))?
+            .len() as u32;
+        for replica in 0..replicas {
+            self.admin_client(caller, replica)
+                .await?
+                .update_config(ConfigPatch {
+                    merge_patch: patch.to_string(),
+                })
+                .await
+                .with_context(|| format!("Failed to update the network of {}", caller))?;
+        }
+        Ok(())
+    }
+
+    async fn admin_client(&self, service: &str, replica: u32) -> Result<AdminClient<Channel>> {
+        let port = self
+            .host_ports
+            .get(service)
+            .and_then(|ports| ports.get(replica as usize))
+            .with_context(|| format!("No host port for replica {} of {}", replica, service))?;
+        let url = format!("http://localhost:{}", port);
+        AdminClient::connect(url.clone())
+            .await
+            .with_context(|| format!("Failed to connect to the admin service at {}", url))
+    }
+}
+
+/// Merge patch value of one link: the configured one with every call lost while cut, and
+/// the configured one (or none) otherwise
+fn link_patch(configured: Option<LinkConfig>, cut: bool) -> Value {
+    match (configured, cut) {
+        (configured, true) => serde_json::to_value(LinkConfig {
+            loss: 1.0,
+            ..configured.unwrap_or_default()
+        })
+        .expect("links serialize"),
+        (Some(configured), false) => serde_json::to_value(configured).expect("links serialize"),
+        (None, false) => Value::Null,
+    }
+}
+
+async fn docker(args: &[&str]) -> Result<()> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to execute 'docker {}'", args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "'docker {}' failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn partitions_lose_every_call_and_restore_the_configured_link() {
+        let configured = LinkConfig {
+            delay_ms: 5.0,
+            jitter_ms: 0.0,
+            loss: 0.1,
+        };
+        assert_eq!(
+            link_patch(Some(configured), true),
+            json!({"delay_ms": 5.0, "jitter_ms": 0.0, "loss": 1.0})
+        );
+        assert_eq!(link_patch(None, true)["loss"], 1.0);
+        assert_eq!(link_patch(Some(configured), false)["loss"], 0.1);
+        // Removed again when nothing was configured
+        assert_eq!(link_patch(None, false), Value::Null);
+    }
+}
@@ -1,12 +1,34 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{collections::HashMap, fs, path::PathBuf, process::Command};
-use tracing::{debug, error, info};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
 use yaml_rust::yaml::Hash;
 use yaml_rust::{Yaml, YamlEmitter};
 
-use crate::parser::{MethodConfig, ServiceConfig, SimulatorConfig};
-
+pub mod faults;
+pub mod network;
+
+use crate::loadgen::{RequestRecord, now_ms, run_load};
+use crate::metrics::LiveMetrics;
+use crate::parser::{LinkConfig, MethodConfig, ReplicaOverride, ServiceConfig, SimulatorConfig};
+use crate::report::{RunReport, build_report, write_run_artifacts};
+use crate::validator;
+
+/// Name of the broker container added when any method uses async calls
+pub const BROKER_SERVICE_NAME: &str = "message_broker";
+/// Port the broker listens on inside its container
+const BROKER_CONTAINER_PORT: u16 = 50051;
+/// Name of the Jaeger container added when `telemetry.collector` is set
+pub const COLLECTOR_SERVICE_NAME: &str = "jaeger";
+/// Name of the Prometheus container added when `telemetry.prometheus` is set
+pub const PROMETHEUS_SERVICE_NAME: &str = "prometheus";
+/// Port every generic service serves `/metrics` on inside its container
+const METRICS_CONTAINER_PORT: u16 = 9464;
+
+#[allow(dead_code)]
 #[derive(Deserialize, Debug, serde::Serialize, Clone)] // Added serde::Serialize and Clone
 pub struct ErrorRate {
     #[serde(rename = "distribution_type")]
@@ -15,19 +37,44 @@ pub struct ErrorRate {
     pub parameters: HashMap<String, f64>, // This maps the YAML key 'parameters' to a HashMap
 }
 
-pub fn assign_ports(services: &HashMap<String, ServiceConfig>) -> Result<HashMap<String, u16>> {
+/// Name of the container running one replica of a service; a service without replicas
+/// keeps its own name
+pub fn container_name(service_name: &str, replica: u32, replicas: u32) -> String {
+    if replicas == 1 {
+        service_name.to_string()
+    } else {
+        format!("{}-{}", service_name, replica)
+    }
+}
+
+/// Names of the containers running a service, by replica
+pub fn container_names(service_name: &str, service: &ServiceConfig) -> Vec<String> {
+    let replicas = service.replica_count();
+    (0..replicas)
+        .map(|replica| container_name(service_name, replica, replicas))
+        .collect()
+}
+
+/// Host ports of every service's containers, by replica
+pub fn assign_ports(
+    services: &HashMap<String, ServiceConfig>,
+) -> Result<HashMap<String, Vec<u16>>> {
     info!("Assigning ports to services.");
     let mut port_assignments = HashMap::new();
     let mut available_ports = (50051..60000).collect::<Vec<u16>>(); // Define a range of ports
 
-    for service_name in services.keys() {
-        if let Some(index) = available_ports.pop() {
-            port_assignments.insert(service_name.clone(), index);
-            debug!("Assigned port {} to service {}", index, service_name);
-        } else {
-            error!("Ran out of available ports.");
-            return Err(anyhow::anyhow!("Ran out of available ports."));
+    for (service_name, service) in services {
+        let mut replica_ports = Vec::new();
+        for container in container_names(service_name, service) {
+            if let Some(index) = available_ports.pop() {
+                replica_ports.push(index);
+                debug!("Assigned port {} to container {}", index, container);
+            } else {
+                error!("Ran out of available ports.");
+                return Err(anyhow::anyhow!("Ran out of available ports."));
+            }
         }
+        port_assignments.insert(service_name.clone(), replica_ports);
     }
 
     info!("Port assignment complete: {:?}", port_assignments);
@@ -54,6 +101,15 @@ pub fn generate_service_configs(config: &SimulatorConfig) -> Result<()> {
         pub ip: String,                             // Matches "ip" in example JSON
         pub port: String,                           // Matches "port" in example JSON (as String)
         pub methods: HashMap<String, MethodConfig>, // Matches "methods" in example JSON (MethodConfig already has derives)
+        pub concurrency: Option<u32>,               // Worker limit, unlimited when absent
+        #[serde(skip_serializing_if = "HashMap::is_empty")]
+        pub network: HashMap<String, LinkConfig>, // Delay and loss of calls, by callee
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub replicas: Vec<String>, // Container of each replica, when there are several
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub load_balancing: Option<&'static str>, // How callers pick a replica
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub replica_overrides: Vec<ReplicaOverride>, // Replicas behaving differently
     }
 
     // making hashmap to store the configs for each service
@@ -61,17 +117,44 @@ pub fn generate_service_configs(config: &SimulatorConfig) -> Result<()> {
 
     // populating the hashmap
     for (service_name, service_config) in &config.services {
+        let replicated = service_config.replica_count() > 1;
         // Create the config object for this service in the desired output format
         let generic_service_config = GenericServiceServiceConfig {
             ip: service_name.clone(),
             port: service_config.port.to_string(),
             methods: service_config.methods.clone(),
+            concurrency: service_config.concurrency,
+            network: network::in_process_links(config, service_name),
+            replicas: if replicated {
+                container_names(service_name, service_config)
+            } else {
+                Vec::new()
+            },
+            load_balancing: replicated.then(|| service_config.load_balancing.as_str()),
+            replica_overrides: service_config.replica_overrides.clone(),
         };
 
         // Insert the service's config into the map, using the service name as the key
         all_service_configs.insert(service_name.clone(), generic_service_config);
     }
 
+    // The broker runs the generic service without methods of its own
+    if uses_async_calls(config) {
+        all_service_configs.insert(
+            BROKER_SERVICE_NAME.to_string(),
+            GenericServiceServiceConfig {
+                ip: BROKER_SERVICE_NAME.to_string(),
+                port: BROKER_CONTAINER_PORT.to_string(),
+                methods: HashMap::new(),
+                concurrency: None,
+                network: HashMap::new(),
+                replicas: Vec::new(),
+                load_balancing: None,
+                replica_overrides: Vec::new(),
+            },
+        );
+    }
+
     // Serialize the entire map containing all service configs
     let config_json = serde_json::to_string_pretty(&all_service_configs)
         .with_context(|| "Failed to serialize all service configurations")?;
@@ -92,109 +175,246 @@ pub fn generate_service_configs(config: &SimulatorConfig) -> Result<()> {
     Ok(())
 }
 
+/// Whether any method enqueues calls on the message broker
+fn uses_async_calls(config: &SimulatorConfig) -> bool {
+    config
+        .services
+        .values()
+        .flat_map(|service| service.methods.values())
+        .any(|method| !method.async_calls.is_empty())
+}
+
+/// OTLP endpoint the generic services export spans to, if any
+fn otlp_endpoint(config: &SimulatorConfig) -> Option<String> {
+    let telemetry = config.telemetry.as_ref()?;
+    match &telemetry.otlp_endpoint {
+        Some(endpoint) => Some(endpoint.clone()),
+        None if telemetry.collector => Some(format!("http://{}:4317", COLLECTOR_SERVICE_NAME)),
+        None => None,
+    }
+}
+
+/// Build the compose definition shared by every generic service container
+fn generic_service_definition(
+    container_name: &str,
+    service_name: &str,
+    container_port: u16,
+    extra_environment: &[(&str, String)],
+) -> Hash {
+    let mut service_def = Hash::new();
+
+    let mut build_def = Hash::new();
+    build_def.insert(
+        Yaml::String("context".into()),
+        Yaml::String("../generic-service".into()),
+    );
+    build_def.insert(
+        Yaml::String("dockerfile".into()),
+        Yaml::String("Dockerfile".into()),
+    );
+    // Pass the port as a build argument (still useful for EXPOSE in Dockerfile)
+    let mut build_args = Hash::new();
+    build_args.insert(
+        Yaml::String("SERVICE_CONTAINER_PORT".into()),
+        Yaml::String(container_port.to_string()),
+    );
+    build_def.insert(Yaml::String("args".into()), Yaml::Hash(build_args));
+
+    service_def.insert(Yaml::String("build".into()), Yaml::Hash(build_def));
+    service_def.insert(
+        Yaml::String("container_name".into()),
+        Yaml::String(container_name.into()),
+    );
+
+    let mut environment = Hash::new();
+    // Add the SERVICE_NAME environment variable
+    environment.insert(
+        Yaml::String("SERVICE_NAME".into()),
+        Yaml::String(service_name.into()),
+    );
+
+    // Add the SERVICE_PORT environment variable
+    environment.insert(
+        Yaml::String("SERVICE_PORT".into()),
+        Yaml::String(container_port.to_string()),
+    );
+
+    // Define the path where the config file will be mounted INSIDE the container
+    let container_config_path = "/app/config.json"; // Example path inside the container
+    environment.insert(
+        Yaml::String("CONFIG_PATH".into()),
+        Yaml::String(container_config_path.into()),
+    );
+
+    // Settings shared by the whole deployment or specific to a role (e.g. the broker)
+    for (key, value) in extra_environment {
+        environment.insert(Yaml::String((*key).into()), Yaml::String(value.clone()));
+    }
+
+    service_def.insert(Yaml::String("environment".into()), Yaml::Hash(environment));
+
+    // Configure volumes to mount the service-specific config file
+    let mut volumes: Vec<Yaml> = Vec::new();
+    // Path on the host: ./service_configs/config.json
+    let host_config_path = "./service_configs/config.json";
+    // Mount point inside the container: /app/config.json (matches CONFIG_PATH)
+    let volume_mapping = format!("{}:{}", host_config_path, container_config_path);
+    volumes.push(Yaml::String(volume_mapping));
+
+    service_def.insert(Yaml::String("volumes".into()), Yaml::Array(volumes));
+
+    // Add networks (using 'microservice_net' as in the example)
+    service_def.insert(
+        Yaml::String("networks".into()),
+        Yaml::Array(vec![Yaml::String("microservice_net".into())]),
+    );
+
+    service_def
+}
+
 pub fn generate_docker_compose(
     config: &SimulatorConfig,
-    ports: &HashMap<String, u16>,
+    ports: &HashMap<String, Vec<u16>>,
 ) -> Result<()> {
     info!("Generating docker-compose.yml file.");
     let mut doc_hash = Hash::new();
 
     doc_hash.insert(Yaml::String("version".into()), Yaml::String("3".into()));
 
+    let mut shared_environment = vec![("METRICS_PORT", METRICS_CONTAINER_PORT.to_string())];
+    if let Some(endpoint) = otlp_endpoint(config) {
+        shared_environment.push(("OTEL_EXPORTER_OTLP_ENDPOINT", endpoint));
+    }
+
     let mut services = Hash::new();
     for (service_name, service_config) in &config.services {
-        let mut service_def = Hash::new();
-
-        let mut build_def = Hash::new();
-        build_def.insert(
-            Yaml::String("context".into()),
-            Yaml::String("../generic-service".into()),
-        );
-        build_def.insert(
-            Yaml::String("dockerfile".into()),
-            Yaml::String("Dockerfile".into()),
-        );
-        // Pass the port as a build argument (still useful for EXPOSE in Dockerfile)
-        let mut build_args = Hash::new();
-        build_args.insert(
-            Yaml::String("SERVICE_CONTAINER_PORT".into()),
-            Yaml::String(service_config.port.to_string()),
-        );
-        build_def.insert(Yaml::String("args".into()), Yaml::Hash(build_args));
-
-        service_def.insert(Yaml::String("build".into()), Yaml::Hash(build_def));
-        service_def.insert(
-            Yaml::String("container_name".into()),
-            Yaml::String(service_name.clone().into()),
-        );
+        let Some(replica_ports) = ports.get(service_name) else {
+            error!("Port not assigned for service: {}", service_name);
+            return Err(anyhow::anyhow!(
+                "Port not assigned for service: {}",
+                service_name
+            ));
+        };
+        for (replica, (container, host_port)) in container_names(service_name, service_config)
+            .into_iter()
+            .zip(replica_ports)
+            .enumerate()
+        {
+            let mut environment = shared_environment.clone();
+            // Lets each replica pick its own entry of `replica_overrides`
+            environment.push(("REPLICA_INDEX", replica.to_string()));
+            let mut service_def = generic_service_definition(
+                &container,
+                service_name,
+                service_config.port,
+                &environment,
+            );
+            if network::needs_net_admin(config, service_name) {
+                service_def.insert(
+                    Yaml::String("cap_add".into()),
+                    Yaml::Array(vec![Yaml::String("NET_ADMIN".into())]),
+                );
+            }
 
-        if let Some(&host_port) = ports.get(service_name) {
             let ports_mapping = format!("{}:{}", host_port, service_config.port);
             service_def.insert(
                 Yaml::String("ports".into()),
                 Yaml::Array(vec![Yaml::String(ports_mapping)]),
             );
-        } else {
-            error!("Port not assigned for service: {}", service_name);
-            return Err(anyhow::anyhow!(
-                "Port not assigned for service: {}",
-                service_name
-            ));
+
+            // depends_on logic can be adjusted or removed based on whether Docker Compose startup order is critical
+            // Based on previous errors and the new config method, removing automatic depends_on from calls might be necessary
+            // or implementing more sophisticated dependency analysis.
+            // Keeping it commented out for now as per previous discussion.
+            /*
+            let mut dependencies: Vec<Yaml> = Vec::new();
+             // ... dependency logic ...
+            if !dependencies.is_empty() {
+                 service_def.insert(Yaml::String("depends_on".into()), Yaml::Array(dependencies));
+            } else {
+                  service_def.insert(Yaml::String("depends_on".into()), Yaml::Null);
+            }
+            */
+
+            services.insert(Yaml::String(container), Yaml::Hash(service_def));
         }
+    }
 
-        let mut environment = Hash::new();
-        // Add the SERVICE_NAME environment variable
-        environment.insert(
-            Yaml::String("SERVICE_NAME".into()),
-            Yaml::String(service_name.clone().into()),
+    // The broker is only reachable from inside the network, so it gets no host port
+    if uses_async_calls(config) {
+        let mut broker_environment = shared_environment.clone();
+        broker_environment.push(("SERVICE_ROLE", "broker".to_string()));
+        let broker_def = generic_service_definition(
+            BROKER_SERVICE_NAME,
+            BROKER_SERVICE_NAME,
+            BROKER_CONTAINER_PORT,
+            &broker_environment,
         );
-
-        // Add the SERVICE_PORT environment variable
-        environment.insert(
-            Yaml::String("SERVICE_PORT".into()),
-            Yaml::String(service_config.port.to_string()),
+        services.insert(
+            Yaml::String(BROKER_SERVICE_NAME.into()),
+            Yaml::Hash(broker_def),
         );
+    }
 
-        // Define the path where the config file will be mounted INSIDE the container
-        let container_config_path = "/app/config.json"; // Example path inside the container
+    // Jaeger all-in-one accepts OTLP directly, so it doubles as the collector
+    if config.telemetry.as_ref().is_some_and(|t| t.collector) {
+        let mut collector_def = Hash::new();
+        collector_def.insert(
+            Yaml::String("image".into()),
+            Yaml::String("jaegertracing/all-in-one:latest".into()),
+        );
+        collector_def.insert(
+            Yaml::String("container_name".into()),
+            Yaml::String(COLLECTOR_SERVICE_NAME.into()),
+        );
+        let mut environment = Hash::new();
         environment.insert(
-            Yaml::String("CONFIG_PATH".into()),
-            Yaml::String(container_config_path.into()),
+            Yaml::String("COLLECTOR_OTLP_ENABLED".into()),
+            Yaml::String("true".into()),
         );
-
-        service_def.insert(Yaml::String("environment".into()), Yaml::Hash(environment));
-
-        // Configure volumes to mount the service-specific config file
-        let mut volumes: Vec<Yaml> = Vec::new();
-        // Path on the host: ./service_configs/config.json
-        let host_config_path = format!("./service_configs/config.json");
-        // Mount point inside the container: /app/config.json (matches CONFIG_PATH)
-        let volume_mapping = format!("{}:{}", host_config_path, container_config_path);
-        volumes.push(Yaml::String(volume_mapping.into()));
-
-        service_def.insert(Yaml::String("volumes".into()), Yaml::Array(volumes));
-
-        // Add networks (using 'microservice_net' as in the example)
-        service_def.insert(
+        collector_def.insert(Yaml::String("environment".into()), Yaml::Hash(environment));
+        collector_def.insert(
+            Yaml::String("ports".into()),
+            Yaml::Array(vec![Yaml::String("16686:16686".into())]),
+        );
+        collector_def.insert(
             Yaml::String("networks".into()),
             Yaml::Array(vec![Yaml::String("microservice_net".into())]),
         );
+        services.insert(
+            Yaml::String(COLLECTOR_SERVICE_NAME.into()),
+            Yaml::Hash(collector_def),
+        );
+    }
 
-        // depends_on logic can be adjusted or removed based on whether Docker Compose startup order is critical
-        // Based on previous errors and the new config method, removing automatic depends_on from calls might be necessary
-        // or implementing more sophisticated dependency analysis.
-        // Keeping it commented out for now as per previous discussion.
-        /*
-        let mut dependencies: Vec<Yaml> = Vec::new();
-         // ... dependency logic ...
-        if !dependencies.is_empty() {
-             service_def.insert(Yaml::String("depends_on".into()), Yaml::Array(dependencies));
-        } else {
-              service_def.insert(Yaml::String("depends_on".into()), Yaml::Null);
-        }
-        */
-
-        services.insert(Yaml::String(service_name.clone()), Yaml::Hash(service_def));
+    if config.telemetry.as_ref().is_some_and(|t| t.prometheus) {
+        let mut prometheus_def = Hash::new();
+        prometheus_def.insert(
+            Yaml::String("image".into()),
+            Yaml::String("prom/prometheus:latest".into()),
+        );
+        prometheus_def.insert(
+            Yaml::String("container_name".into()),
+            Yaml::String(PROMETHEUS_SERVICE_NAME.into()),
+        );
+        prometheus_def.insert(
+            Yaml::String("volumes".into()),
+            Yaml::Array(vec![Yaml::String(
+                "./service_configs/prometheus.yml:/etc/prometheus/prometheus.yml".into(),
+            )]),
+        );
+        prometheus_def.insert(
+            Yaml::String("ports".into()),
+            Yaml::Array(vec![Yaml::String("9090:9090".into())]),
+        );
+        prometheus_def.insert(
+            Yaml::String("networks".into()),
+            Yaml::Array(vec![Yaml::String("microservice_net".into())]),
+        );
+        services.insert(
+            Yaml::String(PROMETHEUS_SERVICE_NAME.into()),
+            Yaml::Hash(prometheus_def),
+        );
     }
 
     doc_hash.insert(Yaml::String("services".into()), Yaml::Hash(services));
@@ -230,6 +450,66 @@ pub fn generate_docker_compose(
     Ok(())
 }
 
+/// Write the Prometheus scrape config listing every generic service container
+pub fn generate_prometheus_config(config: &SimulatorConfig) -> Result<()> {
+    info!("Generating Prometheus scrape configuration.");
+    let mut containers: Vec<String> = config
+        .services
+        .iter()
+        .flat_map(|(service_name, service)| container_names(service_name, service))
+        .collect();
+    if uses_async_calls(config) {
+        containers.push(BROKER_SERVICE_NAME.to_string());
+    }
+    containers.sort();
+
+    let targets = containers
+        .iter()
+        .map(|name| Yaml::String(format!("{}:{}", name, METRICS_CONTAINER_PORT)))
+        .collect();
+
+    let mut static_config = Hash::new();
+    static_config.insert(Yaml::String("targets".into()), Yaml::Array(targets));
+
+    let mut scrape_config = Hash::new();
+    scrape_config.insert(
+        Yaml::String("job_name".into()),
+        Yaml::String("generic-services".into()),
+    );
+    scrape_config.insert(
+        Yaml::String("scrape_interval".into()),
+        Yaml::String("1s".into()),
+    );
+    scrape_config.insert(
+        Yaml::String("static_configs".into()),
+        Yaml::Array(vec![Yaml::Hash(static_config)]),
+    );
+
+    let mut doc_hash = Hash::new();
+    doc_hash.insert(
+        Yaml::String("scrape_configs".into()),
+        Yaml::Array(vec![Yaml::Hash(scrape_config)]),
+    );
+
+    let mut output_string = String::new();
+    let mut emitter = YamlEmitter::new(&mut output_string);
+    emitter.dump(&Yaml::Hash(doc_hash)).unwrap();
+
+    let config_dir = PathBuf::from("./service_configs");
+    fs::create_dir_all(&config_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", config_dir))?;
+    let prometheus_path = config_dir.join("prometheus.yml");
+    fs::write(&prometheus_path, output_string).with_context(|| {
+        format!(
+            "Failed to write Prometheus config file to {:?}",
+            prometheus_path
+        )
+    })?;
+
+    info!("Prometheus config written to {:?}", prometheus_path);
+    Ok(())
+}
+
 fn run_docker_compose() -> Result<()> {
     info!("Starting Docker Compose.");
     let output = Command::new("docker")
@@ -297,7 +577,16 @@ fn stop_docker_compose() -> Result<(), anyhow::Error> {
     }
 }
 
-pub async fn launch_simulation_from_yaml(config: SimulatorConfig) -> Result<()> {
+pub async fn launch_simulation_from_yaml(
+    config: SimulatorConfig,
+    live: Arc<LiveMetrics>,
+) -> Result<RunReport> {
+    // warnings don't stop the run, but are logged and kept in the report
+    let warnings = validator::config_warnings(&config);
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+
     // assign ports
     let port_assignments = assign_ports(&config.services)?;
     info!("Port assignments: {:?}", port_assignments);
@@ -305,19 +594,98 @@ pub async fn launch_simulation_from_yaml(config: SimulatorConfig) -> Result<()>
     // Generate service-specific config files
     generate_service_configs(&config)?;
 
+    // generate the Prometheus scrape config if requested
+    if config.telemetry.as_ref().is_some_and(|t| t.prometheus) {
+        generate_prometheus_config(&config)?;
+    }
+
     // generate docker-compose.yml
     generate_docker_compose(&config, &port_assignments)?;
 
     // running Docker Compose
     run_docker_compose()?;
+    if let Err(e) = network::apply_netem(&config) {
+        stop_docker_compose()?;
+        return Err(e);
+    }
 
-    // wait for termination signal (ctrl-c in this case) and then stopping docker compose
-    tokio::signal::ctrl_c().await?;
-    info!("Received termination signal.");
+    let simulation_id = format!("sim-{}", now_ms() as u64);
+    let started_at_ms = now_ms();
+
+    // generate load until the configured duration passes or a termination signal (ctrl-c) arrives
+    let (stop_sender, stop) = watch::channel(false);
+    let duration = config
+        .load
+        .as_ref()
+        .and_then(|load| load.duration_seconds)
+        .map(Duration::from_secs);
+    tokio::spawn(async move {
+        match duration {
+            Some(duration) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => info!("Simulation duration elapsed."),
+                    _ = tokio::signal::ctrl_c() => info!("Received termination signal."),
+                }
+            }
+            None => {
+                let _ = tokio::signal::ctrl_c().await;
+                info!("Received termination signal.");
+            }
+        }
+        let _ = stop_sender.send(true);
+    });
+    let links = config
+        .services
+        .keys()
+        .map(|service| (service.clone(), network::in_process_links(&config, service)))
+        .collect();
+    let fault_task = tokio::spawn(faults::run_faults(
+        config.faults.clone(),
+        port_assignments.clone(),
+        links,
+        stop.clone(),
+    ));
+    let records = match &config.load {
+        Some(load) => run_load(load, &port_assignments, live, stop).await,
+        None => wait_for_stop(stop).await,
+    };
+    // Without a stop signal the faults would go on after failed load generation
+    if records.is_err() {
+        fault_task.abort();
+    }
+    let fault_windows = fault_task.await.unwrap_or_default();
+
+    // stop Docker Compose even when load generation failed
     stop_docker_compose()?;
+    let mut records = records?;
+    faults::annotate_records(&mut records, &fault_windows);
 
-    // collect and report output (TODO)
+    // collect and report output
     info!("Collecting and reporting output...");
+    let duration_seconds = (now_ms() - started_at_ms) / 1000.0;
+    let report = build_report(
+        &simulation_id,
+        &config,
+        &records,
+        started_at_ms,
+        duration_seconds,
+        warnings,
+        fault_windows,
+    );
+    write_run_artifacts(&config, &records, &report)?;
+    for slo in report.failed_slos() {
+        warn!(
+            "SLO for {} failed: {:.1}% of windows compliant, {:.1}% required",
+            slo.entry_point,
+            slo.compliance * 100.0,
+            slo.min_compliance * 100.0
+        );
+    }
 
-    Ok(())
+    Ok(report)
+}
+
+async fn wait_for_stop(mut stop: watch::Receiver<bool>) -> Result<Vec<RequestRecord>> {
+    stop.wait_for(|stopped| *stopped).await?;
+    Ok(Vec::new())
 }
@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::process::Command;
+use tracing::info;
+
+use super::container_names;
+use crate::parser::{LinkConfig, NetworkEdge, NetworkMode, SimulatorConfig};
+
+/// Interface of the shared bridge network inside every container
+const INTERFACE: &str = "eth0";
+/// Rate of the traffic classes, high enough to never shape traffic itself
+const CLASS_RATE: &str = "10gbit";
+
+/// Edges handled the given way
+fn edges(config: &SimulatorConfig, mode: NetworkMode) -> impl Iterator<Item = &NetworkEdge> {
+    config
+        .network
+        .iter()
+        .filter(move |network| network.mode == mode)
+        .flat_map(|network| &network.edges)
+}
+
+/// Links the generic service applies to its own calls, by callee
+pub fn in_process_links(config: &SimulatorConfig, caller: &str) -> HashMap<String, LinkConfig> {
+    edges(config, NetworkMode::InProcess)
+        .filter(|edge| edge.caller == caller)
+        .map(|edge| (edge.callee.clone(), edge.link))
+        .collect()
+}
+
+/// Whether a service's container changes its own traffic control rules
+pub fn needs_net_admin(config: &SimulatorConfig, service: &str) -> bool {
+    edges(config, NetworkMode::Netem).any(|edge| edge.caller == service)
+}
+
+/// Install netem rules in every replica of each calling service, delaying and dropping
+/// packets sent to any replica of each callee
+pub fn apply_netem(config: &SimulatorConfig) -> Result<()> {
+    let mut by_caller: BTreeMap<&str, Vec<&NetworkEdge>> = BTreeMap::new();
+    for edge in edges(config, NetworkMode::Netem) {
+        by_caller.entry(&edge.caller).or_default().push(edge);
+    }
+    for (caller, edges) in by_caller {
+        let script = netem_script(config, &edges);
+        for container in container_names(caller, &config.services[caller]) {
+            info!("Applying netem rules to {}", container);
+            let output = Command::new("docker")
+                .args(["exec", &container, "sh", "-c", &script])
+                .output()
+                .with_context(|| format!("Failed to execute 'docker exec' in {}", container))?;
+            if !output.status.success() {
+                bail!(
+                    "Failed to apply netem rules in {}: {}",
+                    container,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shell script giving each callee its own HTB class with a netem qdisc, selected by the
+/// addresses of the callee's replicas; other traffic goes through an unaffected default class
+fn netem_script(config: &SimulatorConfig, edges: &[&NetworkEdge]) -> String {
+    let mut lines = vec![
+        "set -e".to_string(),
+        format!(
+            "tc qdisc replace dev {} root handle 1: htb default 1",
+            INTERFACE
+        ),
+        format!(
+            "tc class add dev {} parent 1: classid 1:1 htb rate {}",
+            INTERFACE, CLASS_RATE
+        ),
+    ];
+    for (index, edge) in edges.iter().enumerate() {
+        let class = index + 10;
+        let link = &edge.link;
+        let mut netem = format!("delay {}ms", link.delay_ms);
+        if link.jitter_ms > 0.0 {
+            netem += &format!(" {}ms distribution normal", link.jitter_ms);
+        }
+        if link.loss > 0.0 {
+            netem += &format!(" loss {}%", link.loss * 100.0);
+        }
+        lines.extend([
+            format!(
+                "tc class add dev {} parent 1: classid 1:{} htb rate {}",
+                INTERFACE, class, CLASS_RATE
+            ),
+            format!(
+                "tc qdisc add dev {} parent 1:{} handle {}: netem {}",
+                INTERFACE, class, class, netem
+            ),
+        ]);
+        lines.extend(
+            container_names(&edge.callee, &config.services[&edge.callee])
+                .into_iter()
+                .map(|callee| format!(
+                    "tc filter add dev {} protocol ip parent 1: prio 1 u32 match ip dst \"$(getent hosts {} | awk '{{ print $1 }}')/32\" flowid 1:{}",
+                    INTERFACE, callee, class
+                )),
+        );
+    }
+    lines.join("\n")
+}
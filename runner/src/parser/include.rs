@@ -0,0 +1,177 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::variables::substitute_variables;
+use super::{ConfigFormat, merge, migrations, parse_document};
+
+/// Config file, or list of files applied in order, that a config file builds on
+pub(super) const INCLUDE_KEY: &str = "include";
+
+/// Load a config file as a document, with variables substituted and its includes merged in.
+///
+/// Included paths are relative to the including file and may include further files, in any
+/// format. Included files are merged in the order listed and the including file's own
+/// fields are merged on top, with the same rules as templates: objects field by field,
+/// everything else replaced. Every file is migrated to the current schema version before it
+/// is merged, so files of different versions can include each other.
+pub fn load_config_document(path: &Path, variables: &HashMap<String, String>) -> Result<Value> {
+    load(path, variables, true, &mut Vec::new())
+}
+
+/// Load a document other than a simulator config, such as an experiment file, with
+/// variables and includes resolved like `load_config_document` but nothing migrated
+pub fn load_document(path: &Path, variables: &HashMap<String, String>) -> Result<Value> {
+    load(path, variables, false, &mut Vec::new())
+}
+
+/// `chain` holds the files being loaded, to catch files including each other
+fn load(
+    path: &Path,
+    variables: &HashMap<String, String>,
+    migrate: bool,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    if chain.contains(&canonical) {
+        bail!(
+            "Config files include each other in a cycle: {} -> {}",
+            chain
+                .iter()
+                .map(|file| file.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> "),
+            canonical.display()
+        );
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let document = parse_document(&content, ConfigFormat::from_path(path))
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let mut document = substitute_variables(document, variables)
+        .with_context(|| format!("Failed to substitute variables in {}", path.display()))?;
+    if migrate {
+        document = migrations::migrate(document)
+            .with_context(|| format!("Failed to migrate {}", path.display()))?;
+    }
+
+    let includes = match document.as_object_mut().and_then(|o| o.remove(INCLUDE_KEY)) {
+        None => Vec::new(),
+        Some(Value::String(file)) => vec![file],
+        Some(Value::Array(files)) => files
+            .into_iter()
+            .map(|file| match file {
+                Value::String(file) => Ok(file),
+                other => bail!("'{}' lists {}, expected a file path", INCLUDE_KEY, other),
+            })
+            .collect::<Result<_>>()?,
+        Some(other) => bail!(
+            "'{}' in {} must be a file path or a list of paths, found {}",
+            INCLUDE_KEY,
+            path.display(),
+            other
+        ),
+    };
+    if includes.is_empty() {
+        return Ok(document);
+    }
+
+    chain.push(canonical);
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        merge(
+            &mut merged,
+            load(&directory.join(include), variables, migrate, chain)?,
+        );
+    }
+    chain.pop();
+    merge(&mut merged, document);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_config_file;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Config files written to a directory of their own, by name
+    fn write_configs(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("include-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (file, content) in files {
+            fs::write(directory.join(file), content).unwrap();
+        }
+        directory
+    }
+
+    const UNVERSIONED_BASE: &str = r#"
+services:
+  svc:
+    port: 50051
+    methods:
+      get:
+        calls: []
+        latency_distribution: {distribution_type: constant, parameters: {value: 1}}
+load: null
+"#;
+
+    const CURRENT_BASE: &str = r#"
+schema_version: 2
+services:
+  svc:
+    port: 50051
+    methods:
+      get:
+        calls: []
+        latency_distribution: {type: constant, parameters: {value: 1}}
+load: null
+"#;
+
+    #[test]
+    fn current_files_include_unversioned_ones() {
+        let directory = write_configs(
+            "current",
+            &[
+                ("base.yaml", UNVERSIONED_BASE),
+                ("overlay.yaml", "schema_version: 2\ninclude: base.yaml\n"),
+            ],
+        );
+        let config = parse_config_file(&directory.join("overlay.yaml"), &HashMap::new());
+        fs::remove_dir_all(&directory).unwrap();
+        let method = &config.unwrap().services["svc"].methods["get"];
+        assert_eq!(method.latency_distribution.distribution_type, "constant");
+    }
+
+    #[test]
+    fn unversioned_files_include_current_ones() {
+        let overlay = r#"
+include: base.yaml
+services:
+  svc:
+    methods:
+      get:
+        latency_distribution: {distribution_type: exponential, parameters: {lambda: 2}}
+"#;
+        let directory = write_configs(
+            "unversioned",
+            &[("base.yaml", CURRENT_BASE), ("overlay.yaml", overlay)],
+        );
+        let config = parse_config_file(&directory.join("overlay.yaml"), &HashMap::new());
+        fs::remove_dir_all(&directory).unwrap();
+        let distribution = &config.unwrap().services["svc"].methods["get"].latency_distribution;
+        assert_eq!(distribution.distribution_type, "exponential");
+        assert_eq!(
+            distribution.parameters,
+            HashMap::from([("lambda".to_string(), 2.0)])
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Result, bail};
+use serde_json::{Map, Value};
+
+use super::{CURRENT_SCHEMA_VERSION, DISTRIBUTION_KEYS};
+
+/// Version of documents written before `schema_version` existed
+const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Upgrades between consecutive versions; the one at index i turns version i + 1 into i + 2
+const MIGRATIONS: [fn(&mut Value); 1] = [rename_distribution_type];
+
+pub fn legacy_schema_version() -> u32 {
+    LEGACY_SCHEMA_VERSION
+}
+
+/// Upgrade a config document to the current schema version
+pub fn migrate(mut document: Value) -> Result<Value> {
+    let Some(root) = document.as_object_mut() else {
+        return Ok(document);
+    };
+    let version = match root.get("schema_version") {
+        None => LEGACY_SCHEMA_VERSION,
+        Some(version) => match version.as_u64() {
+            Some(version) if version >= u64::from(LEGACY_SCHEMA_VERSION) => version as u32,
+            _ => bail!(
+                "'schema_version' must be a positive integer, found {}",
+                version
+            ),
+        },
+    };
+    if version > CURRENT_SCHEMA_VERSION {
+        bail!(
+            "Config schema version {} is newer than the latest supported version {}",
+            version,
+            CURRENT_SCHEMA_VERSION
+        );
+    }
+
+    for migration in &MIGRATIONS[(version - LEGACY_SCHEMA_VERSION) as usize..] {
+        migration(&mut document);
+    }
+    if let Some(root) = document.as_object_mut() {
+        root.insert("schema_version".to_string(), CURRENT_SCHEMA_VERSION.into());
+    }
+    Ok(document)
+}
+
+/// 1 -> 2: distributions were also written with `distribution_type`, as the generic service
+/// used to read them, instead of `type`
+fn rename_distribution_type(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if DISTRIBUTION_KEYS.contains(&key.as_str())
+                    && let Value::Object(distribution) = value
+                {
+                    rename_key(distribution, "distribution_type", "type");
+                }
+                rename_distribution_type(value);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(rename_distribution_type),
+        _ => {}
+    }
+}
+
+fn rename_key(map: &mut Map<String, Value>, from: &str, to: &str) {
+    if !map.contains_key(to)
+        && let Some(value) = map.remove(from)
+    {
+        map.insert(to.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn version_1_distributions_are_renamed() {
+        let document = json!({
+            "services": {
+                "distribution_type": {"methods": {"distribution_type": {
+                    "latency_distribution": {"distribution_type": "constant", "parameters": {}},
+                    "error_rate": {"distribution_type": "bernoulli", "parameters": {}},
+                }}},
+            },
+        });
+        assert_eq!(
+            migrate(document).unwrap(),
+            json!({
+                "schema_version": CURRENT_SCHEMA_VERSION,
+                "services": {
+                    "distribution_type": {"methods": {"distribution_type": {
+                        "latency_distribution": {"type": "constant", "parameters": {}},
+                        "error_rate": {"type": "bernoulli", "parameters": {}},
+                    }}},
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn current_documents_are_left_alone() {
+        let document = json!({
+            "schema_version": CURRENT_SCHEMA_VERSION,
+            "services": {"svc": {"methods": {"get": {
+                "latency_distribution": {"distribution_type": "constant"},
+            }}}},
+        });
+        assert_eq!(migrate(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn newer_and_invalid_versions_are_rejected() {
+        let newer = migrate(json!({"schema_version": CURRENT_SCHEMA_VERSION + 1})).unwrap_err();
+        assert!(newer.to_string().contains("is newer than"), "{}", newer);
+        for version in [json!(0), json!(-1), json!(1.5), json!("2")] {
+            let error = migrate(json!({"schema_version": version})).unwrap_err();
+            assert!(error.to_string().contains("positive integer"), "{}", error);
+        }
+    }
+}
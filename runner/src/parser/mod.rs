@@ -1,42 +1,459 @@
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
 
+pub mod include;
 pub mod json;
+pub mod migrations;
+pub mod slo;
+pub mod templates;
+pub mod toml;
+pub mod variables;
+pub mod yaml;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Version of the config format this build reads and writes
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// Format a config document is written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            other => bail!(
+                "Unknown config format '{}', expected 'json', 'yaml' or 'toml'",
+                other
+            ),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Format given by a file's extension; anything unrecognised is read as JSON
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .unwrap_or(ConfigFormat::Json)
+    }
+
+    /// Format given by a MIME type such as `application/yaml`, ignoring parameters like
+    /// `charset`; anything unrecognised is read as JSON
+    pub fn from_content_type(content_type: &str) -> Self {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                ConfigFormat::Yaml
+            }
+            "application/toml" | "text/toml" | "text/x-toml" => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+/// Parse a config file, picking the format from its extension, with `${VAR}` references
+/// filled from `variables` or the environment and the files it includes merged in
+pub fn parse_config_file(
+    path: &Path,
+    variables: &HashMap<String, String>,
+) -> Result<SimulatorConfig> {
+    from_document(include::load_config_document(path, variables)?)
+}
+
+/// Parse a config document into a generic document, ready for merging
+fn parse_document(content: &str, format: ConfigFormat) -> Result<Value> {
+    match format {
+        ConfigFormat::Json => json::parse_json_document(content),
+        ConfigFormat::Yaml => yaml::parse_yaml_document(content),
+        ConfigFormat::Toml => toml::parse_toml_document(content),
+    }
+}
+
+/// Turn a parsed document of any format into a SimulatorConfig, migrating it to the current
+/// schema version and resolving templates first
+fn from_document(document: Value) -> Result<SimulatorConfig> {
+    // Includes name files on this machine, so only config files read by path may use them
+    if document.get(include::INCLUDE_KEY).is_some() {
+        bail!(
+            "'{}' is only supported in config files read from disk",
+            include::INCLUDE_KEY
+        );
+    }
+    let document = migrations::migrate(document)?;
+    let document = templates::resolve_templates(document)?;
+    let config: SimulatorConfig =
+        serde_json::from_value(document).context("Invalid simulator config")?;
+
+    Ok(config)
+}
+
+/// Fields of a method that hold a distribution
+const DISTRIBUTION_KEYS: [&str; 2] = ["latency_distribution", "error_rate"];
+
+/// Override `base` with `overlay`, merging objects key by key and replacing everything else.
+/// A distribution naming its `type` replaces the one it overrides instead of inheriting
+/// parameters of another type.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing)
+                        if !(DISTRIBUTION_KEYS.contains(&key.as_str())
+                            && value.get("type").is_some()) =>
+                    {
+                        merge(existing, value)
+                    }
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Write a config in the given format, with keys sorted so dumps can be diffed
+pub fn dump_config(config: &SimulatorConfig, format: ConfigFormat) -> Result<String> {
+    let mut document = serde_json::to_value(config)?;
+    let dump = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(&document)? + "\n",
+        ConfigFormat::Yaml => serde_yaml::to_string(&document)?,
+        ConfigFormat::Toml => {
+            // TOML has no null, and a missing field reads back as None anyway
+            remove_nulls(&mut document);
+            ::toml::to_string_pretty(&document)?
+        }
+    };
+    Ok(dump)
+}
+
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+/// Parse a config document written in the given format
+pub fn parse_config_str(content: &str, format: ConfigFormat) -> Result<SimulatorConfig> {
+    match format {
+        ConfigFormat::Json => json::parse_json_str(content),
+        ConfigFormat::Yaml => yaml::parse_yaml_str(content),
+        ConfigFormat::Toml => toml::parse_toml_str(content),
+    }
+}
+
+/// JSON Schema of a config document at the current schema version
+pub fn config_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(SimulatorConfig))
+        .expect("JSON Schema serializes to JSON")
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SimulatorConfig {
+    /// Version of the config format; documents without one are version 1 and are migrated
+    #[serde(default = "migrations::legacy_schema_version")]
+    pub schema_version: u32,
     pub services: HashMap<String, ServiceConfig>,
     pub load: Option<LoadConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<TelemetryConfig>,
+    /// Service level objectives the run is checked against once it finishes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slos: Vec<SloConfig>,
+    /// Faults injected into the running deployment, timed from the start of the load
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub faults: Vec<FaultConfig>,
+    /// Delay, jitter and loss on calls between services
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ServiceConfig {
     pub port: u16,
     pub methods: HashMap<String, MethodConfig>,
+    /// Requests handled at once; further requests queue until a worker is free
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+    /// Identical instances of the service, each with its own container; 1 when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u32>,
+    /// How callers spread their calls over the replicas
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub load_balancing: LoadBalancing,
+    /// Replicas behaving differently from the rest, e.g. one slow instance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replica_overrides: Vec<ReplicaOverride>,
+}
+
+impl ServiceConfig {
+    pub fn replica_count(&self) -> u32 {
+        self.replicas.unwrap_or(1)
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalancing {
+    /// Each replica in turn
+    #[default]
+    RoundRobin,
+    /// The replica with the fewest calls in flight from the caller
+    LeastOutstanding,
+    /// The less busy of two replicas picked at random
+    PowerOfTwoChoices,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl LoadBalancing {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoadBalancing::RoundRobin => "round_robin",
+            LoadBalancing::LeastOutstanding => "least_outstanding",
+            LoadBalancing::PowerOfTwoChoices => "power_of_two_choices",
+        }
+    }
+}
+
+/// Behaviour of one replica on top of the service's methods
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReplicaOverride {
+    /// Index of the replica, from 0
+    pub replica: u32,
+    /// Multiplies the replica's simulated latencies
+    #[serde(default = "default_latency_multiplier")]
+    pub latency_multiplier: f64,
+    /// Share of the replica's requests failing on top of the configured error rate
+    #[serde(default)]
+    pub error_rate: f64,
+}
+
+fn default_latency_multiplier() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MethodConfig {
+    /// Stages of "service.method" calls: stages run one after another, the calls of a stage
+    /// in parallel
+    #[serde(default)]
     pub calls: Vec<Vec<String>>,
+    /// Calls enqueued on the message broker once the synchronous stages finish;
+    /// the caller does not wait for the callee to process them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub async_calls: Vec<String>,
     pub latency_distribution: Distribution,
     pub error_rate: Option<Distribution>,
+    /// Allows the method to be part of a call cycle; once it is nested this many times
+    /// within one request, it makes no further calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_recursion_depth: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Distribution {
+    /// normal, uniform, constant, exponential or empirical for latencies, bernoulli for errors
     #[serde(rename = "type")]
     pub distribution_type: String,
     pub parameters: HashMap<String, f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct LoadConfig {
     pub entry_points: Vec<EntryPoint>,
+    /// Stop generating load after this many seconds instead of waiting for Ctrl-C
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EntryPoint {
     pub service: String,
     pub method: String,
     pub requests_per_second: u32,
 }
+
+/// Service level objective of one entry point, checked over rolling windows of the run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SloConfig {
+    /// Entry point as "service.method"
+    pub entry_point: String,
+    /// Targets every window must meet, e.g. "p99 < 300ms" or "error_ratio < 1%"
+    #[schemars(with = "Vec<String>")]
+    pub objectives: Vec<slo::Objective>,
+    /// Length of the windows, which slide forward one second at a time
+    #[serde(default = "default_slo_window_seconds")]
+    pub window_seconds: u64,
+    /// Share of windows that must meet every objective for the SLO to pass
+    #[serde(default = "default_slo_min_compliance")]
+    pub min_compliance: f64,
+}
+
+fn default_slo_window_seconds() -> u64 {
+    60
+}
+
+fn default_slo_min_compliance() -> f64 {
+    1.0
+}
+
+/// A fault injected into one service for a window of the run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FaultConfig {
+    /// Seconds after load generation starts
+    pub at_seconds: f64,
+    /// How long the fault lasts; until the end of the run when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+    pub service: String,
+    /// Only this replica of the service; all of them when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replica: Option<u32>,
+    #[serde(flatten)]
+    pub action: FaultAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FaultAction {
+    /// Kill the service's container, starting it again when the fault ends
+    Kill,
+    /// Freeze the service's container, so its requests hang until the fault ends
+    Pause,
+    /// Multiply the service's simulated latencies
+    Slow { latency_multiplier: f64 },
+    /// Fail this share of the service's requests on top of its configured error rate
+    Error { error_rate: f64 },
+    /// Lose every call between the service and each of `peers`, in both directions, like a
+    /// network partition. Calls delivered through the message broker are not affected.
+    Partition { peers: Vec<String> },
+}
+
+/// Network effects on calls between services
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub mode: NetworkMode,
+    pub edges: Vec<NetworkEdge>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    /// The calling generic service delays or drops its own calls; needs no privileges
+    #[default]
+    InProcess,
+    /// tc/netem rules on the calling container's interface; needs NET_ADMIN
+    Netem,
+}
+
+/// Network effects on every call from one service to another
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkEdge {
+    pub caller: String,
+    pub callee: String,
+    #[serde(flatten)]
+    pub link: LinkConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+pub struct LinkConfig {
+    /// Extra one-way delay of each call
+    #[serde(default)]
+    pub delay_ms: f64,
+    /// Standard deviation of the delay
+    #[serde(default)]
+    pub jitter_ms: f64,
+    /// Share of calls (in-process) or packets (netem) lost
+    #[serde(default)]
+    pub loss: f64,
+}
+
+/// Observability add-ons for the generic services: span export and metrics scraping
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TelemetryConfig {
+    /// OTLP/gRPC endpoint of an existing collector, e.g. "http://otel-collector:4317"
+    pub otlp_endpoint: Option<String>,
+    /// Add a local Jaeger container that receives OTLP and serves its UI on port 16686
+    #[serde(default)]
+    pub collector: bool,
+    /// Add a Prometheus container scraping every service's `/metrics`, UI on port 9090
+    #[serde(default)]
+    pub prometheus: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn merged(mut base: Value, overlay: Value) -> Value {
+        merge(&mut base, overlay);
+        base
+    }
+
+    #[test]
+    fn maps_with_a_type_key_merge_into_the_base() {
+        let base = json!({"services": {"users": {"port": 1}}});
+        let overlay = json!({"services": {"type": {"port": 2}}});
+        assert_eq!(
+            merged(base, overlay),
+            json!({"services": {"users": {"port": 1}, "type": {"port": 2}}})
+        );
+
+        let base = json!({"methods": {"get": {"concurrency": 1}}});
+        let overlay = json!({"methods": {"type": {"concurrency": 2}}});
+        assert_eq!(
+            merged(base, overlay),
+            json!({"methods": {"get": {"concurrency": 1}, "type": {"concurrency": 2}}})
+        );
+    }
+
+    #[test]
+    fn distributions_naming_a_type_replace_the_base() {
+        let base = json!({
+            "latency_distribution": {"type": "normal", "parameters": {"mean": 10, "stddev": 2}},
+            "error_rate": {"type": "bernoulli", "parameters": {"p": 0.1}},
+        });
+        let overlay = json!({
+            "latency_distribution": {"type": "constant", "parameters": {"value": 5}},
+            "error_rate": {"parameters": {"p": 0.2}},
+        });
+        assert_eq!(
+            merged(base, overlay),
+            json!({
+                "latency_distribution": {"type": "constant", "parameters": {"value": 5}},
+                "error_rate": {"type": "bernoulli", "parameters": {"p": 0.2}},
+            })
+        );
+    }
+}
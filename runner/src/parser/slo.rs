@@ -0,0 +1,108 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// One target of a service level objective, written like "p99 < 300ms" or "error_ratio < 1%"
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Objective {
+    /// The given percentile (0-100) of end-to-end latency stays below a threshold
+    Latency { percentile: f64, threshold_ms: f64 },
+    /// The share of failed requests stays below a ratio (0-1)
+    ErrorRatio { max_ratio: f64 },
+}
+
+impl FromStr for Objective {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((metric, bound)) = s.split_once('<') else {
+            bail!(
+                "Invalid objective '{}', expected e.g. 'p99 < 300ms' or 'error_ratio < 1%'",
+                s
+            );
+        };
+        let (metric, bound) = (metric.trim(), bound.trim());
+
+        if let Some(percentile) = metric.strip_prefix('p') {
+            let percentile: f64 = percentile
+                .parse()
+                .with_context(|| format!("Invalid percentile '{}' in objective '{}'", metric, s))?;
+            if !(percentile > 0.0 && percentile < 100.0) {
+                bail!("Percentile of objective '{}' must be between 0 and 100", s);
+            }
+            return Ok(Objective::Latency {
+                percentile,
+                threshold_ms: parse_duration_ms(bound)
+                    .with_context(|| format!("Invalid latency bound in objective '{}'", s))?,
+            });
+        }
+
+        match metric {
+            "error_ratio" | "error ratio" | "error_rate" | "error rate" => {
+                let max_ratio = match bound.strip_suffix('%') {
+                    Some(percent) => percent.trim().parse::<f64>().map(|p| p / 100.0),
+                    None => bound.parse::<f64>(),
+                }
+                .with_context(|| format!("Invalid error ratio bound in objective '{}'", s))?;
+                if !(0.0..=1.0).contains(&max_ratio) {
+                    bail!(
+                        "Error ratio bound of objective '{}' must be within 0-100%",
+                        s
+                    );
+                }
+                Ok(Objective::ErrorRatio { max_ratio })
+            }
+            other => bail!(
+                "Unknown metric '{}' in objective '{}', expected a percentile like 'p99' or 'error_ratio'",
+                other,
+                s
+            ),
+        }
+    }
+}
+
+/// Latency bound such as "300ms" or "0.3s"; bare numbers are milliseconds
+fn parse_duration_ms(bound: &str) -> Result<f64> {
+    let (number, scale) = if let Some(ms) = bound.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(seconds) = bound.strip_suffix('s') {
+        (seconds, 1000.0)
+    } else {
+        (bound, 1.0)
+    };
+    let value: f64 = number.trim().parse()?;
+    if !(value > 0.0 && value.is_finite()) {
+        bail!("Latency bound must be positive, got '{}'", bound);
+    }
+    Ok(value * scale)
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::Latency {
+                percentile,
+                threshold_ms,
+            } => write!(f, "p{} < {}ms", percentile, threshold_ms),
+            Objective::ErrorRatio { max_ratio } => {
+                write!(f, "error_ratio < {}%", max_ratio * 100.0)
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Objective {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Objective> for String {
+    fn from(objective: Objective) -> Self {
+        objective.to_string()
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value};
+
+use super::merge;
+
+/// Named service and method archetypes, under `templates.services` and `templates.methods`
+const TEMPLATES_KEY: &str = "templates";
+/// Fields every service and method starts from, under `defaults.service` and `defaults.method`
+const DEFAULTS_KEY: &str = "defaults";
+/// Template name, or list of names applied in order, that a service or method builds on
+const EXTENDS_KEY: &str = "extends";
+
+/// Resolve `templates`, `defaults` and `extends` in a config document into plain services.
+///
+/// A service is its service defaults, overridden by the templates it extends, overridden by
+/// its own fields; each of its methods is resolved the same way from the method defaults and
+/// templates. Objects are merged key by key, while lists, numbers and strings replace what
+/// they override. A distribution that sets `type` replaces the inherited one whole; without
+/// `type`, it only overrides the parameters it names.
+pub fn resolve_templates(mut document: Value) -> Result<Value> {
+    let Some(root) = document.as_object_mut() else {
+        return Ok(document);
+    };
+    let templates = root.remove(TEMPLATES_KEY).unwrap_or(Value::Null);
+    let defaults = root.remove(DEFAULTS_KEY).unwrap_or(Value::Null);
+
+    let service_templates = section(&templates, TEMPLATES_KEY, "services")?;
+    let method_templates = section(&templates, TEMPLATES_KEY, "methods")?;
+    let service_defaults = expand(
+        Value::Object(section(&defaults, DEFAULTS_KEY, "service")?),
+        &service_templates,
+        "service",
+        &mut Vec::new(),
+    )
+    .context("Failed to resolve the service defaults")?;
+    let method_defaults = expand(
+        Value::Object(section(&defaults, DEFAULTS_KEY, "method")?),
+        &method_templates,
+        "method",
+        &mut Vec::new(),
+    )
+    .context("Failed to resolve the method defaults")?;
+
+    let Some(services) = root.get_mut("services").and_then(Value::as_object_mut) else {
+        return Ok(document);
+    };
+    for (service_name, service) in services.iter_mut() {
+        let mut resolved = service_defaults.clone();
+        let expanded = expand(
+            service.take(),
+            &service_templates,
+            "service",
+            &mut Vec::new(),
+        )
+        .with_context(|| format!("Failed to resolve service '{}'", service_name))?;
+        merge(&mut resolved, expanded);
+
+        if let Some(methods) = resolved.get_mut("methods").and_then(Value::as_object_mut) {
+            for (method_name, method) in methods.iter_mut() {
+                let mut resolved_method = method_defaults.clone();
+                let expanded = expand(method.take(), &method_templates, "method", &mut Vec::new())
+                    .with_context(|| {
+                        format!(
+                            "Failed to resolve method '{}.{}'",
+                            service_name, method_name
+                        )
+                    })?;
+                merge(&mut resolved_method, expanded);
+                *method = resolved_method;
+            }
+        }
+        *service = resolved;
+    }
+
+    Ok(document)
+}
+
+/// Object under `key` in a `templates` or `defaults` section, empty if absent
+fn section(parent: &Value, parent_key: &str, key: &str) -> Result<Map<String, Value>> {
+    match parent.get(key) {
+        None | Some(Value::Null) => Ok(Map::new()),
+        Some(Value::Object(map)) => Ok(map.clone()),
+        Some(_) => bail!("'{}.{}' must be an object", parent_key, key),
+    }
+}
+
+/// Put the templates a value extends beneath it, following templates that extend others.
+/// `chain` holds the templates being expanded, to catch templates extending each other.
+fn expand(
+    mut value: Value,
+    templates: &Map<String, Value>,
+    kind: &str,
+    chain: &mut Vec<String>,
+) -> Result<Value> {
+    let parents = match value.as_object_mut().and_then(|o| o.remove(EXTENDS_KEY)) {
+        None => Vec::new(),
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(names)) => names
+            .into_iter()
+            .map(|name| match name {
+                Value::String(name) => Ok(name),
+                other => bail!(
+                    "'{}' lists {}, expected a template name",
+                    EXTENDS_KEY,
+                    other
+                ),
+            })
+            .collect::<Result<_>>()?,
+        Some(other) => bail!(
+            "'{}' must be a template name or a list of names, found {}",
+            EXTENDS_KEY,
+            other
+        ),
+    };
+
+    let mut resolved = Value::Object(Map::new());
+    for parent in parents {
+        if chain.contains(&parent) {
+            bail!(
+                "Templates extend each other in a cycle: {} -> {}",
+                chain.join(" -> "),
+                parent
+            );
+        }
+        let Some(template) = templates.get(&parent) else {
+            bail!("Unknown {} template '{}'", kind, parent);
+        };
+        chain.push(parent);
+        let expanded = expand(template.clone(), templates, kind, chain)?;
+        chain.pop();
+        merge(&mut resolved, expanded);
+    }
+    merge(&mut resolved, value);
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn services_build_on_defaults_and_templates() {
+        let document = json!({
+            "defaults": {
+                "service": {"concurrency": 4},
+                "method": {"latency_distribution": {"type": "constant", "parameters": {"value": 1}}},
+            },
+            "templates": {
+                "services": {"cache": {"port": 6379, "methods": {"get": {"extends": "fast_read"}}}},
+                "methods": {
+                    "fast_read": {
+                        "latency_distribution": {"type": "normal", "parameters": {"mean": 2, "stddev": 1}},
+                    },
+                },
+            },
+            "services": {
+                "sessions": {
+                    "extends": "cache",
+                    "methods": {"get": {"latency_distribution": {"parameters": {"mean": 5}}}},
+                },
+            },
+        });
+        assert_eq!(
+            resolve_templates(document).unwrap(),
+            json!({
+                "services": {
+                    "sessions": {
+                        "concurrency": 4,
+                        "port": 6379,
+                        "methods": {
+                            "get": {
+                                "latency_distribution": {
+                                    "type": "normal",
+                                    "parameters": {"mean": 5, "stddev": 1},
+                                },
+                            },
+                        },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn services_and_methods_named_type_are_kept() {
+        let document = json!({
+            "templates": {
+                "services": {"base": {"port": 1, "methods": {"get": {"calls": []}}}},
+            },
+            "services": {
+                "type": {"extends": "base", "methods": {"type": {"calls": []}}},
+            },
+        });
+        assert_eq!(
+            resolve_templates(document).unwrap(),
+            json!({
+                "services": {
+                    "type": {"port": 1, "methods": {"get": {"calls": []}, "type": {"calls": []}}},
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn templates_extending_each_other_are_rejected() {
+        let document = json!({
+            "templates": {"services": {"a": {"extends": "b"}, "b": {"extends": "a"}}},
+            "services": {"svc": {"extends": "a"}},
+        });
+        let error = format!("{:#}", resolve_templates(document).unwrap_err());
+        assert!(error.contains("a -> b -> a"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_templates_are_rejected() {
+        let document = json!({"services": {"svc": {"methods": {"get": {"extends": "slow"}}}}});
+        let error = format!("{:#}", resolve_templates(document).unwrap_err());
+        assert!(
+            error.contains("Unknown method template 'slow'"),
+            "{}",
+            error
+        );
+    }
+}
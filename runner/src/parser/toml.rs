@@ -0,0 +1,16 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use super::{SimulatorConfig, from_document};
+
+/// Parse a TOML string into a SimulatorConfig
+pub fn parse_toml_str(content: &str) -> Result<SimulatorConfig> {
+    from_document(parse_toml_document(content)?)
+}
+
+/// Parse a TOML string into a generic document
+pub fn parse_toml_document(content: &str) -> Result<Value> {
+    let document: Value = ::toml::from_str(content).context("Failed to parse TOML content")?;
+
+    Ok(document)
+}
@@ -0,0 +1,184 @@
+use anyhow::{Result, bail};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+
+/// Replace `${NAME}` and `${NAME:-default}` in the strings and keys of a parsed config
+/// document, taking values from `overrides` (the CLI's `--set NAME=value`) first and the
+/// environment second. `$${` stands for a literal `${`.
+///
+/// Substituting after parsing leaves comments alone and keeps values from changing the
+/// document's structure, whatever characters they hold. A string that is a single reference
+/// becomes a number or boolean when its value reads as one, so `"${RPS}"` fits numeric fields.
+pub fn substitute_variables(document: Value, overrides: &HashMap<String, String>) -> Result<Value> {
+    let mut undefined = BTreeSet::new();
+    let document = substitute_value(document, overrides, &mut undefined)?;
+    if !undefined.is_empty() {
+        bail!(
+            "Undefined variables: {} (set them in the environment or with --set NAME=value)",
+            undefined.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(document)
+}
+
+fn substitute_value(
+    value: Value,
+    overrides: &HashMap<String, String>,
+    undefined: &mut BTreeSet<String>,
+) -> Result<Value> {
+    Ok(match value {
+        Value::String(text) => {
+            let substituted = substitute_str(&text, overrides, undefined)?;
+            if is_single_reference(&text) {
+                scalar(substituted)
+            } else {
+                Value::String(substituted)
+            }
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| substitute_value(item, overrides, undefined))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, field)| {
+                    Ok((
+                        substitute_str(&key, overrides, undefined)?,
+                        substitute_value(field, overrides, undefined)?,
+                    ))
+                })
+                .collect::<Result<Map<_, _>>>()?,
+        ),
+        other => other,
+    })
+}
+
+/// Whether the whole text is one `${...}` reference
+fn is_single_reference(text: &str) -> bool {
+    text.strip_prefix("${")
+        .and_then(|reference| reference.strip_suffix('}'))
+        .is_some_and(|reference| !reference.contains('}'))
+}
+
+/// A substituted value as a number or boolean when it reads as one, or else as a string
+fn scalar(value: String) -> Value {
+    match serde_json::from_str(&value) {
+        Ok(parsed @ (Value::Number(_) | Value::Bool(_))) => parsed,
+        _ => Value::String(value),
+    }
+}
+
+fn substitute_str(
+    content: &str,
+    overrides: &HashMap<String, String>,
+    undefined: &mut BTreeSet<String>,
+) -> Result<String> {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            output.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(reference) = after.strip_prefix('{') else {
+            output.push('$');
+            rest = after;
+            continue;
+        };
+        let Some(end) = reference.find('}') else {
+            bail!("Unterminated variable reference '${{{}'", reference);
+        };
+
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("Invalid variable name '{}'", name);
+        }
+        match overrides
+            .get(name)
+            .cloned()
+            .or_else(|| env::var(name).ok())
+            .or_else(|| default.map(str::to_string))
+        {
+            Some(value) => output.push_str(&value),
+            None => {
+                undefined.insert(name.to_string());
+            }
+        }
+        rest = &reference[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Parse a `NAME=value` assignment given on the command line
+pub fn parse_assignment(assignment: &str) -> Result<(String, String)> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => bail!("Invalid assignment '{}', expected NAME=value", assignment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn substitute(document: Value, overrides: &[(&str, &str)]) -> Result<Value> {
+        let overrides = overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        substitute_variables(document, &overrides)
+    }
+
+    #[test]
+    fn single_references_take_the_type_of_their_value() {
+        let document = json!({"rps": "${RPS}", "name": "${NAME}", "on": "${ON}"});
+        assert_eq!(
+            substitute(document, &[("RPS", "50"), ("NAME", "svc"), ("ON", "true")]).unwrap(),
+            json!({"rps": 50, "name": "svc", "on": true})
+        );
+    }
+
+    #[test]
+    fn references_inside_text_and_keys_stay_strings() {
+        let document = json!({"${SVC}_db": "${SVC}-${INDEX:-0}"});
+        assert_eq!(
+            substitute(document, &[("SVC", "users")]).unwrap(),
+            json!({"users_db": "users-0"})
+        );
+    }
+
+    #[test]
+    fn values_cannot_change_the_document_structure() {
+        let value = "x\"\nmethods: {}";
+        assert_eq!(
+            substitute(json!({"name": "${NAME}"}), &[("NAME", value)]).unwrap(),
+            json!({"name": value})
+        );
+    }
+
+    #[test]
+    fn escaped_references_are_literal() {
+        assert_eq!(
+            substitute(json!(["$${NAME}", "$5"]), &[]).unwrap(),
+            json!(["${NAME}", "$5"])
+        );
+    }
+
+    #[test]
+    fn every_undefined_variable_is_reported() {
+        let error = substitute(json!(["${B}", {"${A}": 1}]), &[]).unwrap_err();
+        assert!(error.to_string().contains("A, B"), "{}", error);
+    }
+}
@@ -0,0 +1,21 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use super::{SimulatorConfig, from_document};
+
+/// Parse a YAML string into a SimulatorConfig
+pub fn parse_yaml_str(content: &str) -> Result<SimulatorConfig> {
+    from_document(parse_yaml_document(content)?)
+}
+
+/// Parse a YAML string into a generic document, resolving anchors and `<<` merge keys
+pub fn parse_yaml_document(content: &str) -> Result<Value> {
+    let mut value: serde_yaml::Value =
+        serde_yaml::from_str(content).context("Failed to parse YAML content")?;
+    value
+        .apply_merge()
+        .context("Failed to apply YAML merge keys")?;
+    let document: Value = serde_yaml::from_value(value).context("Failed to parse YAML content")?;
+
+    Ok(document)
+}
@@ -0,0 +1,383 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use super::{read_records, read_report};
+use crate::analysis::distribution::normal_cdf;
+use crate::metrics::{Aggregator, SeriesStats};
+
+/// Changes beyond which a difference between two runs counts as a regression
+#[derive(Debug, Clone, Copy)]
+pub struct RegressionThresholds {
+    /// Largest tolerated relative increase of p50, p90 or p99 latency, e.g. 0.1 for 10%
+    pub max_latency_increase: f64,
+    /// Largest tolerated relative decrease of throughput
+    pub max_throughput_decrease: f64,
+    /// Largest tolerated increase of the error ratio, in absolute terms
+    pub max_error_rate_increase: f64,
+    /// Latency and error changes only count when significant at this level
+    pub significance_level: f64,
+}
+
+/// How one entry point or edge changed between a baseline and a candidate run
+#[derive(Debug, Serialize)]
+pub struct SeriesDiff {
+    /// Entry point name, "caller -> callee" for edges
+    pub name: String,
+    /// Absent when the series only appears in the candidate
+    pub baseline: Option<SeriesStats>,
+    /// Absent when the series only appears in the baseline
+    pub candidate: Option<SeriesStats>,
+    /// Relative changes, e.g. 0.25 for 25% slower; absent when the baseline is 0
+    pub p50_change: Option<f64>,
+    pub p90_change: Option<f64>,
+    pub p99_change: Option<f64>,
+    pub throughput_change: Option<f64>,
+    /// Candidate error ratio minus baseline error ratio
+    pub error_rate_delta: f64,
+    /// Two-sided Mann-Whitney U test of the latency distributions
+    pub latency_p_value: Option<f64>,
+    /// Two-sided two-proportion z-test of the error ratios
+    pub error_p_value: Option<f64>,
+    /// Why this series counts as a regression, empty if it doesn't
+    pub regressions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComparisonReport {
+    pub baseline: String,
+    pub candidate: String,
+    pub entry_points: Vec<SeriesDiff>,
+    pub edges: Vec<SeriesDiff>,
+}
+
+impl ComparisonReport {
+    pub fn regressions(&self) -> usize {
+        self.entry_points
+            .iter()
+            .chain(&self.edges)
+            .filter(|diff| !diff.regressions.is_empty())
+            .count()
+    }
+}
+
+/// Statistics and raw latencies of every series of one run
+struct RunSeries {
+    entry_points: BTreeMap<String, (SeriesStats, Vec<f64>)>,
+    edges: BTreeMap<String, (SeriesStats, Vec<f64>)>,
+}
+
+fn load_run(run_dir: &Path) -> Result<RunSeries> {
+    let report = read_report(run_dir)?;
+    let mut aggregator = Aggregator::default();
+    for record in read_records(run_dir)? {
+        aggregator.record(&record);
+    }
+    let snapshot = aggregator.snapshot(
+        (report.started_at_ms + report.duration_seconds * 1000.0) as u64,
+        report.duration_seconds.max(f64::EPSILON),
+    );
+
+    let entry_point_latencies: BTreeMap<&String, &[f64]> =
+        aggregator.entry_point_latencies().collect();
+    let edge_latencies: BTreeMap<String, &[f64]> = aggregator
+        .edge_latencies()
+        .map(|((caller, callee), latencies)| (format!("{} -> {}", caller, callee), latencies))
+        .collect();
+    Ok(RunSeries {
+        entry_points: snapshot
+            .entry_points
+            .into_iter()
+            .map(|stats| {
+                let latencies = entry_point_latencies[&stats.name].to_vec();
+                (stats.name.clone(), (stats, latencies))
+            })
+            .collect(),
+        edges: snapshot
+            .edges
+            .into_iter()
+            .map(|stats| {
+                let latencies = edge_latencies[&stats.name].to_vec();
+                (stats.name.clone(), (stats, latencies))
+            })
+            .collect(),
+    })
+}
+
+/// Compare the artifacts of two runs, flagging the series that regressed. Series missing
+/// from the candidate are regressions for entry points but only reported for edges, since
+/// architecture changes rewire calls.
+pub fn compare_runs(
+    baseline_dir: &Path,
+    candidate_dir: &Path,
+    thresholds: &RegressionThresholds,
+) -> Result<ComparisonReport> {
+    let baseline = load_run(baseline_dir)?;
+    let candidate = load_run(candidate_dir)?;
+    Ok(ComparisonReport {
+        baseline: baseline_dir.display().to_string(),
+        candidate: candidate_dir.display().to_string(),
+        entry_points: diff_series(
+            &baseline.entry_points,
+            &candidate.entry_points,
+            thresholds,
+            true,
+        ),
+        edges: diff_series(&baseline.edges, &candidate.edges, thresholds, false),
+    })
+}
+
+fn diff_series(
+    baseline: &BTreeMap<String, (SeriesStats, Vec<f64>)>,
+    candidate: &BTreeMap<String, (SeriesStats, Vec<f64>)>,
+    thresholds: &RegressionThresholds,
+    required: bool,
+) -> Vec<SeriesDiff> {
+    let names: BTreeSet<&String> = baseline.keys().chain(candidate.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| match (baseline.get(name), candidate.get(name)) {
+            (Some((before, before_latencies)), Some((after, after_latencies))) => diff_stats(
+                name,
+                (before, before_latencies),
+                (after, after_latencies),
+                thresholds,
+            ),
+            (before, after) => SeriesDiff {
+                name: name.clone(),
+                baseline: before.map(|(stats, _)| stats.clone()),
+                candidate: after.map(|(stats, _)| stats.clone()),
+                p50_change: None,
+                p90_change: None,
+                p99_change: None,
+                throughput_change: None,
+                error_rate_delta: 0.0,
+                latency_p_value: None,
+                error_p_value: None,
+                regressions: if required && after.is_none() {
+                    vec!["missing from the candidate run".to_string()]
+                } else {
+                    Vec::new()
+                },
+            },
+        })
+        .collect()
+}
+
+fn diff_stats(
+    name: &str,
+    (before, before_latencies): (&SeriesStats, &[f64]),
+    (after, after_latencies): (&SeriesStats, &[f64]),
+    thresholds: &RegressionThresholds,
+) -> SeriesDiff {
+    let latency_p_value = mann_whitney_p_value(before_latencies, after_latencies);
+    let error_p_value = two_proportion_p_value(
+        (before.errors, before.requests),
+        (after.errors, after.requests),
+    );
+    let significant =
+        |p_value: Option<f64>| p_value.is_some_and(|p| p < thresholds.significance_level);
+
+    let mut diff = SeriesDiff {
+        name: name.to_string(),
+        baseline: Some(before.clone()),
+        candidate: Some(after.clone()),
+        p50_change: relative_change(before.p50_ms, after.p50_ms),
+        p90_change: relative_change(before.p90_ms, after.p90_ms),
+        p99_change: relative_change(before.p99_ms, after.p99_ms),
+        throughput_change: relative_change(before.throughput_rps, after.throughput_rps),
+        error_rate_delta: after.error_rate - before.error_rate,
+        latency_p_value,
+        error_p_value,
+        regressions: Vec::new(),
+    };
+
+    if significant(latency_p_value) {
+        for (percentile, change) in [
+            ("p50", diff.p50_change),
+            ("p90", diff.p90_change),
+            ("p99", diff.p99_change),
+        ] {
+            if let Some(change) = change.filter(|c| *c > thresholds.max_latency_increase) {
+                diff.regressions.push(format!(
+                    "{} latency up {:.1}% (limit {:.1}%)",
+                    percentile,
+                    change * 100.0,
+                    thresholds.max_latency_increase * 100.0
+                ));
+            }
+        }
+    }
+    if let Some(change) = diff
+        .throughput_change
+        .filter(|c| -c > thresholds.max_throughput_decrease)
+    {
+        diff.regressions.push(format!(
+            "throughput down {:.1}% (limit {:.1}%)",
+            -change * 100.0,
+            thresholds.max_throughput_decrease * 100.0
+        ));
+    }
+    if significant(error_p_value) && diff.error_rate_delta > thresholds.max_error_rate_increase {
+        diff.regressions.push(format!(
+            "error ratio up {:.2} points (limit {:.2})",
+            diff.error_rate_delta * 100.0,
+            thresholds.max_error_rate_increase * 100.0
+        ));
+    }
+    diff
+}
+
+fn relative_change(before: f64, after: f64) -> Option<f64> {
+    (before > 0.0).then(|| (after - before) / before)
+}
+
+/// Two-sided p-value of the Mann-Whitney U test, by the normal approximation with a tie
+/// correction; absent when either sample is empty or all values are tied
+fn mann_whitney_p_value(first: &[f64], second: &[f64]) -> Option<f64> {
+    let (n1, n2) = (first.len() as f64, second.len() as f64);
+    if first.is_empty() || second.is_empty() {
+        return None;
+    }
+
+    // Rank the pooled sample, giving tied values their average rank
+    let mut pooled: Vec<(f64, bool)> = first
+        .iter()
+        .map(|value| (*value, true))
+        .chain(second.iter().map(|value| (*value, false)))
+        .collect();
+    pooled.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut first_rank_sum = 0.0;
+    let mut tie_term = 0.0;
+    let mut start = 0;
+    while start < pooled.len() {
+        let end = start + pooled[start..].partition_point(|(value, _)| *value == pooled[start].0);
+        let average_rank = (start + end + 1) as f64 / 2.0;
+        let ties = (end - start) as f64;
+        tie_term += ties * ties * ties - ties;
+        first_rank_sum += pooled[start..end]
+            .iter()
+            .filter(|(_, from_first)| *from_first)
+            .count() as f64
+            * average_rank;
+        start = end;
+    }
+
+    let u = first_rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return None;
+    }
+    let z = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+    Some((2.0 * (1.0 - normal_cdf(z))).clamp(0.0, 1.0))
+}
+
+/// Two-sided p-value of the two-proportion z-test on (errors, requests) counts; absent when
+/// either run has no requests or neither run has a varying outcome
+fn two_proportion_p_value(first: (u64, u64), second: (u64, u64)) -> Option<f64> {
+    let (errors1, n1) = (first.0 as f64, first.1 as f64);
+    let (errors2, n2) = (second.0 as f64, second.1 as f64);
+    if n1 == 0.0 || n2 == 0.0 {
+        return None;
+    }
+    let pooled = (errors1 + errors2) / (n1 + n2);
+    let variance = pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2);
+    if variance <= 0.0 {
+        return None;
+    }
+    let z = (errors2 / n2 - errors1 / n1).abs() / variance.sqrt();
+    Some((2.0 * (1.0 - normal_cdf(z))).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: RegressionThresholds = RegressionThresholds {
+        max_latency_increase: 0.1,
+        max_throughput_decrease: 0.1,
+        max_error_rate_increase: 0.01,
+        significance_level: 0.05,
+    };
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("p-value");
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn stats(requests: u64, errors: u64, latency_ms: f64) -> SeriesStats {
+        SeriesStats {
+            name: "api.get".to_string(),
+            caller: None,
+            callee: None,
+            requests,
+            errors,
+            throughput_rps: requests as f64 / 10.0,
+            error_rate: errors as f64 / requests as f64,
+            p50_ms: latency_ms,
+            p90_ms: latency_ms,
+            p99_ms: latency_ms,
+        }
+    }
+
+    #[test]
+    fn mann_whitney_separated_samples() {
+        let first = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let second = [6.0, 7.0, 8.0, 9.0, 10.0];
+        // U = 0 with the continuity correction, as scipy's asymptotic mannwhitneyu
+        assert_close(mann_whitney_p_value(&first, &second), 0.012186);
+        assert_close(mann_whitney_p_value(&second, &first), 0.012186);
+    }
+
+    #[test]
+    fn mann_whitney_identical_samples_are_not_significant() {
+        let sample = [1.0, 2.0, 2.0, 3.0];
+        assert_close(mann_whitney_p_value(&sample, &sample), 1.0);
+    }
+
+    #[test]
+    fn mann_whitney_needs_varying_samples() {
+        assert_eq!(mann_whitney_p_value(&[], &[1.0]), None);
+        assert_eq!(mann_whitney_p_value(&[5.0, 5.0], &[5.0]), None);
+    }
+
+    #[test]
+    fn two_proportion_test() {
+        assert_close(two_proportion_p_value((10, 100), (20, 100)), 0.047670);
+        assert_close(two_proportion_p_value((10, 100), (10, 100)), 1.0);
+        assert_eq!(two_proportion_p_value((0, 100), (0, 50)), None);
+        assert_eq!(two_proportion_p_value((1, 0), (1, 10)), None);
+    }
+
+    #[test]
+    fn significant_latency_and_error_increases_regress() {
+        let before_latencies: Vec<f64> = (0..50).map(|i| 10.0 + i as f64 * 0.1).collect();
+        let after_latencies: Vec<f64> = before_latencies.iter().map(|l| l * 2.0).collect();
+        let diff = diff_stats(
+            "api.get",
+            (&stats(1000, 10, 12.0), &before_latencies),
+            (&stats(1000, 60, 24.0), &after_latencies),
+            &THRESHOLDS,
+        );
+        assert_eq!(diff.regressions.len(), 4, "{:?}", diff.regressions);
+    }
+
+    #[test]
+    fn insignificant_changes_and_small_throughput_drops_pass() {
+        let latencies = [10.0, 11.0, 12.0];
+        let diff = diff_stats(
+            "api.get",
+            (&stats(1000, 1, 10.0), &latencies),
+            (&stats(950, 2, 20.0), &latencies),
+            &THRESHOLDS,
+        );
+        assert!(diff.regressions.is_empty(), "{:?}", diff.regressions);
+    }
+}
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::loadgen::{CallRecord, RequestRecord};
+
+/// Where the end-to-end latency of one entry point is spent
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CriticalPathReport {
+    pub entry_point: String,
+    /// Successful requests the analysis is based on
+    pub requests: u64,
+    pub mean_latency_ms: f64,
+    /// Services on the critical path, largest contribution first
+    pub services: Vec<ServiceContribution>,
+    /// Calls that run in parallel within a stage, with how much they could slow down
+    pub branches: Vec<BranchSlack>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceContribution {
+    pub service: String,
+    /// Fraction of requests whose critical path goes through this service
+    pub critical_path_ratio: f64,
+    /// Time spent in the service itself (not waiting on callees) while on the
+    /// critical path, averaged over all requests
+    pub mean_contribution_ms: f64,
+    /// `mean_contribution_ms` as a fraction of the mean end-to-end latency
+    pub latency_share: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BranchSlack {
+    /// Calling method as "service.method"
+    pub caller: String,
+    pub stage: u32,
+    /// Called method as "service.method"
+    pub callee: String,
+    /// Fraction of attempts that finished last in their stage round
+    pub critical_ratio: f64,
+    /// Average time the attempt finished before its stage round ended
+    pub mean_slack_ms: f64,
+}
+
+#[derive(Default)]
+struct EntryPointAccumulator {
+    requests: u64,
+    total_latency_ms: f64,
+    contributions_ms: HashMap<String, f64>,
+    on_critical_path: HashMap<String, u64>,
+    branches: BTreeMap<(String, u32, String), BranchAccumulator>,
+}
+
+#[derive(Default)]
+struct BranchAccumulator {
+    attempts: u64,
+    critical: u64,
+    total_slack_ms: f64,
+}
+
+/// Compute the critical path of every successful request, grouped by entry point.
+///
+/// A method's time is split into its own work and the rounds of its `calls` stages;
+/// each round lasts until its slowest attempt returns, so only that attempt extends the
+/// critical path. Waiting on async calls only counts as the caller's own time.
+pub fn analyze_critical_paths(records: &[RequestRecord]) -> Vec<CriticalPathReport> {
+    let mut entry_points: BTreeMap<String, EntryPointAccumulator> = BTreeMap::new();
+
+    for record in records.iter().filter(|record| !record.was_an_error) {
+        let accumulator = entry_points.entry(record.entry_point.clone()).or_default();
+        accumulator.requests += 1;
+        accumulator.total_latency_ms += record.latency_ms;
+
+        let entry_service = record.entry_point.split('.').next().unwrap_or_default();
+        let mut on_path = HashSet::new();
+        walk(
+            accumulator,
+            &mut on_path,
+            entry_service,
+            &record.entry_point,
+            record.latency_ms,
+            &record.calls,
+            true,
+        );
+        for service in on_path {
+            *accumulator.on_critical_path.entry(service).or_default() += 1;
+        }
+    }
+
+    entry_points
+        .into_iter()
+        .map(|(entry_point, accumulator)| {
+            let requests = accumulator.requests as f64;
+            let mean_latency_ms = accumulator.total_latency_ms / requests;
+            let mut services: Vec<ServiceContribution> = accumulator
+                .contributions_ms
+                .iter()
+                .map(|(service, total_ms)| ServiceContribution {
+                    service: service.clone(),
+                    critical_path_ratio: accumulator.on_critical_path[service] as f64 / requests,
+                    mean_contribution_ms: total_ms / requests,
+                    latency_share: if mean_latency_ms > 0.0 {
+                        total_ms / requests / mean_latency_ms
+                    } else {
+                        0.0
+                    },
+                })
+                .collect();
+            services.sort_by(|a, b| b.mean_contribution_ms.total_cmp(&a.mean_contribution_ms));
+
+            let branches = accumulator
+                .branches
+                .into_iter()
+                .map(|((caller, stage, callee), branch)| BranchSlack {
+                    caller,
+                    stage,
+                    callee,
+                    critical_ratio: branch.critical as f64 / branch.attempts as f64,
+                    mean_slack_ms: branch.total_slack_ms / branch.attempts as f64,
+                })
+                .collect();
+
+            CriticalPathReport {
+                entry_point,
+                requests: accumulator.requests,
+                mean_latency_ms,
+                services,
+                branches,
+            }
+        })
+        .collect()
+}
+
+/// Attribute the time of one method invocation and recurse into its calls.
+///
+/// Branch slack is collected everywhere, contributions only along the critical path.
+fn walk(
+    accumulator: &mut EntryPointAccumulator,
+    on_path: &mut HashSet<String>,
+    service: &str,
+    method: &str,
+    duration_ms: f64,
+    calls: &[CallRecord],
+    critical: bool,
+) {
+    // Group synchronous attempts into the rounds the caller waited on, in order
+    let mut rounds: BTreeMap<(u32, u32), Vec<&CallRecord>> = BTreeMap::new();
+    for call in calls.iter().filter(|call| !call.was_async) {
+        rounds
+            .entry((call.stage, call.attempt))
+            .or_default()
+            .push(call);
+    }
+
+    let mut waiting_ms = 0.0;
+    for ((stage, _), attempts) in rounds {
+        let round_start = attempts
+            .iter()
+            .map(|call| call.sent_at_ms)
+            .fold(f64::INFINITY, f64::min);
+        let slowest = attempts
+            .iter()
+            .copied()
+            .max_by(|a, b| a.received_at_ms.total_cmp(&b.received_at_ms))
+            .expect("rounds are never empty");
+        let round_end = slowest.received_at_ms;
+        waiting_ms += round_end - round_start;
+
+        for call in attempts {
+            let is_slowest = std::ptr::eq(call, slowest);
+            let branch = accumulator
+                .branches
+                .entry((method.to_string(), stage, call.callee()))
+                .or_default();
+            branch.attempts += 1;
+            if is_slowest {
+                branch.critical += 1;
+            }
+            branch.total_slack_ms += round_end - call.received_at_ms;
+
+            walk(
+                accumulator,
+                on_path,
+                &call.service_name,
+                &call.callee(),
+                call.duration_ms(),
+                &call.calls,
+                critical && is_slowest,
+            );
+        }
+    }
+
+    if critical {
+        let own_ms = (duration_ms - waiting_ms).max(0.0);
+        *accumulator
+            .contributions_ms
+            .entry(service.to_string())
+            .or_default() += own_ms;
+        on_path.insert(service.to_string());
+    }
+}
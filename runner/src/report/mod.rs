@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+pub mod compare;
+pub mod critical_path;
+pub mod slo;
+
+use critical_path::{CriticalPathReport, analyze_critical_paths};
+use slo::{SloResult, evaluate_slos};
+
+use crate::loadgen::RequestRecord;
+use crate::metrics::{Aggregator, MetricsSnapshot};
+use crate::orchestrator::faults::FaultWindow;
+use crate::parser::{self, ConfigFormat, SimulatorConfig};
+use crate::validator::diagnostics::Diagnostic;
+
+/// Directory holding one sub-directory of artifacts per simulation run
+pub const RUNS_DIR: &str = "./runs";
+const CONFIG_FILE: &str = "config.json";
+const RESULTS_FILE: &str = "results.jsonl";
+const REPORT_FILE: &str = "report.json";
+
+/// Summary of a finished run, written next to the raw results
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    pub simulation_id: String,
+    /// Milliseconds since the UNIX epoch
+    pub started_at_ms: f64,
+    pub duration_seconds: f64,
+    /// Metrics over the whole run, treated as a single window
+    pub summary: MetricsSnapshot,
+    /// Per entry point, which services the end-to-end latency is spent in
+    pub critical_paths: Vec<CriticalPathReport>,
+    /// Validation warnings the config was run with
+    #[serde(default)]
+    pub warnings: Vec<Diagnostic>,
+    /// Outcome of every SLO in the config
+    #[serde(default)]
+    pub slos: Vec<SloResult>,
+    /// Faults injected during the run, with the entry point metrics while each was active
+    #[serde(default)]
+    pub faults: Vec<FaultWindow>,
+}
+
+impl RunReport {
+    /// SLOs the run missed
+    pub fn failed_slos(&self) -> impl Iterator<Item = &SloResult> {
+        self.slos.iter().filter(|slo| !slo.passed)
+    }
+}
+
+pub fn build_report(
+    simulation_id: &str,
+    config: &SimulatorConfig,
+    records: &[RequestRecord],
+    started_at_ms: f64,
+    duration_seconds: f64,
+    warnings: Vec<Diagnostic>,
+    mut faults: Vec<FaultWindow>,
+) -> RunReport {
+    let mut aggregator = Aggregator::default();
+    for record in records {
+        aggregator.record(record);
+    }
+    let finished_at_ms = started_at_ms + duration_seconds * 1000.0;
+    for window in &mut faults {
+        let mut during = Aggregator::default();
+        for record in records.iter().filter(|r| window.contains(r.sent_at_ms)) {
+            during.record(record);
+        }
+        let seconds = (window.ended_at_ms - window.started_at_ms) / 1000.0;
+        window.entry_points = during
+            .snapshot(window.ended_at_ms as u64, seconds.max(f64::EPSILON))
+            .entry_points;
+    }
+    RunReport {
+        simulation_id: simulation_id.to_string(),
+        started_at_ms,
+        duration_seconds,
+        summary: aggregator.snapshot(finished_at_ms as u64, duration_seconds.max(f64::EPSILON)),
+        critical_paths: analyze_critical_paths(records),
+        warnings,
+        slos: evaluate_slos(&config.slos, records, started_at_ms, duration_seconds),
+        faults,
+    }
+}
+
+/// Write the config, raw results and report of a run to `runs/<simulation_id>`
+pub fn write_run_artifacts(
+    config: &SimulatorConfig,
+    records: &[RequestRecord],
+    report: &RunReport,
+) -> Result<PathBuf> {
+    let run_dir = Path::new(RUNS_DIR).join(&report.simulation_id);
+    fs::create_dir_all(&run_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", run_dir))?;
+
+    fs::write(
+        run_dir.join(CONFIG_FILE),
+        serde_json::to_string_pretty(config)?,
+    )
+    .with_context(|| format!("Failed to write config to {:?}", run_dir))?;
+
+    let results_path = run_dir.join(RESULTS_FILE);
+    let mut results = BufWriter::new(
+        fs::File::create(&results_path)
+            .with_context(|| format!("Failed to create {:?}", results_path))?,
+    );
+    for record in records {
+        serde_json::to_writer(&mut results, record)?;
+        results.write_all(b"\n")?;
+    }
+    results.flush()?;
+
+    fs::write(
+        run_dir.join(REPORT_FILE),
+        serde_json::to_string_pretty(report)?,
+    )
+    .with_context(|| format!("Failed to write report to {:?}", run_dir))?;
+
+    // The first service to optimize is the largest contributor to each entry point's latency
+    for critical_path in &report.critical_paths {
+        if let Some(top) = critical_path.services.first() {
+            info!(
+                "{}: {} contributes {:.1} ms ({:.0}%) of {:.1} ms mean latency",
+                critical_path.entry_point,
+                top.service,
+                top.mean_contribution_ms,
+                top.latency_share * 100.0,
+                critical_path.mean_latency_ms
+            );
+        }
+    }
+
+    info!("Run artifacts written to {:?}", run_dir);
+    Ok(run_dir)
+}
+
+/// Read the config a run wrote to its artifact directory
+pub fn read_config(run_dir: &Path) -> Result<SimulatorConfig> {
+    let path = run_dir.join(CONFIG_FILE);
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config: {}", path.display()))?;
+    parser::parse_config_str(&content, ConfigFormat::Json)
+        .with_context(|| format!("Failed to parse config: {}", path.display()))
+}
+
+/// Read the summary a run wrote to its artifact directory
+pub fn read_report(run_dir: &Path) -> Result<RunReport> {
+    let path = run_dir.join(REPORT_FILE);
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read report: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse report: {}", path.display()))
+}
+
+/// Read the raw results a run wrote to its artifact directory
+pub fn read_records(run_dir: &Path) -> Result<Vec<RequestRecord>> {
+    let path = run_dir.join(RESULTS_FILE);
+    let file = fs::File::open(&path)
+        .with_context(|| format!("Failed to read results: {}", path.display()))?;
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read results: {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line).with_context(|| {
+            format!(
+                "Invalid record on line {} of {}",
+                number + 1,
+                path.display()
+            )
+        })?);
+    }
+    Ok(records)
+}
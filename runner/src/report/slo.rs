@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+use crate::loadgen::RequestRecord;
+use crate::metrics::percentile;
+use crate::parser::SloConfig;
+use crate::parser::slo::Objective;
+
+/// How one objective of an SLO held up over the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectiveResult {
+    pub objective: String,
+    /// Windows holding at least one request; empty windows are not judged
+    pub windows: usize,
+    pub compliant_windows: usize,
+    pub compliance: f64,
+    /// Bad requests over the whole run divided by the number the objective allows, so above
+    /// 1 the error budget is exhausted; absent when the objective allows none
+    pub budget_burn: Option<f64>,
+    /// Worst windowed value: the percentile latency in ms, or the error ratio
+    pub worst_value: Option<f64>,
+    /// Seconds from the start of the run to the end of the first window missing the objective
+    pub first_violation_seconds: Option<f64>,
+}
+
+/// Outcome of one SLO: passed when enough windows meet every objective
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloResult {
+    pub entry_point: String,
+    pub window_seconds: u64,
+    pub requests: usize,
+    pub objectives: Vec<ObjectiveResult>,
+    /// Share of windows meeting every objective
+    pub compliance: f64,
+    pub min_compliance: f64,
+    pub first_violation_seconds: Option<f64>,
+    pub passed: bool,
+}
+
+/// Requests of one entry point, bucketed by the second of the run they were sent in
+struct Buckets {
+    latencies_ms: Vec<Vec<f64>>,
+    errors: Vec<usize>,
+}
+
+impl Buckets {
+    fn new(records: &[&RequestRecord], started_at_ms: f64, duration_seconds: f64) -> Self {
+        let second_of = |record: &RequestRecord| {
+            ((record.sent_at_ms - started_at_ms) / 1000.0).max(0.0) as usize
+        };
+        let seconds = records
+            .iter()
+            .map(|record| second_of(record) + 1)
+            .max()
+            .unwrap_or(0)
+            .max(duration_seconds.ceil() as usize)
+            .max(1);
+        let mut buckets = Buckets {
+            latencies_ms: vec![Vec::new(); seconds],
+            errors: vec![0; seconds],
+        };
+        for record in records {
+            let second = second_of(record);
+            buckets.latencies_ms[second].push(record.latency_ms);
+            if record.was_an_error {
+                buckets.errors[second] += 1;
+            }
+        }
+        buckets
+    }
+
+    /// Sorted latencies and error count of the seconds `start..end`
+    fn window(&self, start: usize, end: usize) -> (Vec<f64>, usize) {
+        let mut latencies: Vec<f64> = self.latencies_ms[start..end].concat();
+        latencies.sort_by(f64::total_cmp);
+        (latencies, self.errors[start..end].iter().sum())
+    }
+}
+
+/// Value an objective measures over some requests, and whether it meets the objective
+fn measure(objective: &Objective, sorted_latencies: &[f64], errors: usize) -> (f64, bool) {
+    match *objective {
+        Objective::Latency {
+            percentile: p,
+            threshold_ms,
+        } => {
+            let value = percentile(sorted_latencies, p / 100.0);
+            (value, value < threshold_ms)
+        }
+        Objective::ErrorRatio { max_ratio } => {
+            let value = errors as f64 / sorted_latencies.len() as f64;
+            (value, value < max_ratio)
+        }
+    }
+}
+
+/// Bad requests over the number the objective allows across the whole run
+fn budget_burn(objective: &Objective, records: &[&RequestRecord]) -> Option<f64> {
+    let requests = records.len() as f64;
+    let (bad, allowed) = match *objective {
+        Objective::Latency {
+            percentile,
+            threshold_ms,
+        } => (
+            records
+                .iter()
+                .filter(|record| record.latency_ms >= threshold_ms)
+                .count(),
+            (1.0 - percentile / 100.0) * requests,
+        ),
+        Objective::ErrorRatio { max_ratio } => (
+            records.iter().filter(|record| record.was_an_error).count(),
+            max_ratio * requests,
+        ),
+    };
+    (allowed > 0.0).then(|| bad as f64 / allowed)
+}
+
+/// Check every SLO against the requests of a run, over windows of `window_seconds` that
+/// slide forward one second at a time; runs shorter than a window form a single window
+pub fn evaluate_slos(
+    slos: &[SloConfig],
+    records: &[RequestRecord],
+    started_at_ms: f64,
+    duration_seconds: f64,
+) -> Vec<SloResult> {
+    slos.iter()
+        .map(|slo| {
+            let matching: Vec<&RequestRecord> = records
+                .iter()
+                .filter(|record| record.entry_point == slo.entry_point)
+                .collect();
+            let buckets = Buckets::new(&matching, started_at_ms, duration_seconds);
+            let seconds = buckets.errors.len();
+            let window = (slo.window_seconds.max(1) as usize).min(seconds);
+
+            let mut objectives: Vec<ObjectiveResult> = slo
+                .objectives
+                .iter()
+                .map(|objective| ObjectiveResult {
+                    objective: objective.to_string(),
+                    windows: 0,
+                    compliant_windows: 0,
+                    compliance: 0.0,
+                    budget_burn: budget_burn(objective, &matching),
+                    worst_value: None,
+                    first_violation_seconds: None,
+                })
+                .collect();
+            let mut windows = 0;
+            let mut compliant_windows = 0;
+            let mut first_violation_seconds = None;
+
+            for start in 0..=seconds - window {
+                let end = start + window;
+                let (latencies, errors) = buckets.window(start, end);
+                if latencies.is_empty() {
+                    continue;
+                }
+                windows += 1;
+                let mut compliant = true;
+                for (objective, result) in slo.objectives.iter().zip(&mut objectives) {
+                    let (value, met) = measure(objective, &latencies, errors);
+                    result.windows += 1;
+                    result.worst_value = Some(result.worst_value.map_or(value, |w| w.max(value)));
+                    if met {
+                        result.compliant_windows += 1;
+                    } else {
+                        compliant = false;
+                        result.first_violation_seconds.get_or_insert(end as f64);
+                    }
+                }
+                if compliant {
+                    compliant_windows += 1;
+                } else {
+                    first_violation_seconds.get_or_insert(end as f64);
+                }
+            }
+
+            for result in &mut objectives {
+                result.compliance = ratio(result.compliant_windows, result.windows);
+            }
+            let compliance = ratio(compliant_windows, windows);
+            SloResult {
+                entry_point: slo.entry_point.clone(),
+                window_seconds: slo.window_seconds,
+                requests: matching.len(),
+                objectives,
+                compliance,
+                min_compliance: slo.min_compliance,
+                first_violation_seconds,
+                // Without requests there is nothing showing the SLO holds
+                passed: windows > 0 && compliance >= slo.min_compliance,
+            }
+        })
+        .collect()
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STARTED_AT_MS: f64 = 1_000_000.0;
+
+    /// One request per second for ten seconds taking 100 ms, except a 500 ms request in
+    /// second 5 and an error in second 7
+    fn records() -> Vec<RequestRecord> {
+        (0..10)
+            .map(|second| RequestRecord {
+                entry_point: "api.get".to_string(),
+                sent_at_ms: STARTED_AT_MS + second as f64 * 1000.0 + 500.0,
+                latency_ms: if second == 5 { 500.0 } else { 100.0 },
+                was_an_error: second == 7,
+                calls: Vec::new(),
+                faults: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn slo(entry_point: &str, objectives: &[&str], window_seconds: u64) -> SloConfig {
+        serde_json::from_value(serde_json::json!({
+            "entry_point": entry_point,
+            "objectives": objectives,
+            "window_seconds": window_seconds,
+            "min_compliance": 0.3,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn windows_slide_one_second_at_a_time() {
+        let slos = [slo("api.get", &["p99 < 300ms", "error_ratio < 20%"], 3)];
+        let [result] = evaluate_slos(&slos, &records(), STARTED_AT_MS, 10.0)
+            .try_into()
+            .unwrap();
+        assert_eq!(result.requests, 10);
+
+        // Windows starting in seconds 3 to 5 hold the slow request
+        let latency = &result.objectives[0];
+        assert_eq!((latency.windows, latency.compliant_windows), (8, 5));
+        assert_eq!(latency.worst_value, Some(500.0));
+        assert_eq!(latency.first_violation_seconds, Some(6.0));
+
+        // Windows starting in seconds 5 to 7 hold the error, a third of their requests
+        let errors = &result.objectives[1];
+        assert_eq!((errors.windows, errors.compliant_windows), (8, 5));
+        assert_eq!(errors.first_violation_seconds, Some(8.0));
+
+        assert_eq!(result.compliance, 3.0 / 8.0);
+        assert_eq!(result.first_violation_seconds, Some(6.0));
+        assert!(result.passed);
+    }
+
+    #[test]
+    fn budget_burn_compares_bad_requests_with_those_allowed() {
+        let slos = [slo(
+            "api.get",
+            &["p90 < 300ms", "error_ratio < 20%", "error_ratio < 0%"],
+            3,
+        )];
+        let [result] = evaluate_slos(&slos, &records(), STARTED_AT_MS, 10.0)
+            .try_into()
+            .unwrap();
+        // One slow request of the one allowed, one error of the two allowed, none allowed
+        let burns: Vec<Option<f64>> = result.objectives.iter().map(|o| o.budget_burn).collect();
+        assert_eq!(burns[0].map(|burn| (burn * 1e9).round() / 1e9), Some(1.0));
+        assert_eq!(burns[1], Some(0.5));
+        assert_eq!(burns[2], None);
+    }
+
+    #[test]
+    fn runs_shorter_than_a_window_form_one_window() {
+        let slos = [slo("api.get", &["p99 < 300ms"], 60)];
+        let [result] = evaluate_slos(&slos, &records(), STARTED_AT_MS, 10.0)
+            .try_into()
+            .unwrap();
+        assert_eq!(result.objectives[0].windows, 1);
+        assert_eq!(result.objectives[0].first_violation_seconds, Some(10.0));
+        assert!(!result.passed);
+    }
+
+    #[test]
+    fn slos_without_requests_fail() {
+        let slos = [slo("api.other", &["p99 < 300ms"], 3)];
+        let [result] = evaluate_slos(&slos, &records(), STARTED_AT_MS, 10.0)
+            .try_into()
+            .unwrap();
+        assert_eq!(result.requests, 0);
+        assert_eq!(result.objectives[0].windows, 0);
+        assert!(!result.passed);
+    }
+}
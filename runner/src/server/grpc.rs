@@ -1,13 +1,33 @@
 use anyhow::Result;
+use futures::Stream;
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use tonic::{Request, Response, Status, transport::Server};
 
 use crate::client::grpc as orchestrator_client;
 use crate::generator::yaml;
-use crate::parser::json;
+use crate::metrics::LiveMetrics;
+use crate::parser::{self, ConfigFormat};
 use crate::proto::input_parser_server::{InputParser, InputParserServer};
-use crate::proto::{ParseRequest, ParseResponse};
+use crate::proto::simulation_monitor_server::{SimulationMonitor, SimulationMonitorServer};
+use crate::proto::{self, MetricsSnapshot, ParseRequest, ParseResponse, WatchMetricsRequest};
 use crate::validator;
+use crate::validator::diagnostics::Severity;
+
+impl From<validator::diagnostics::Diagnostic> for proto::Diagnostic {
+    fn from(diagnostic: validator::diagnostics::Diagnostic) -> Self {
+        let severity = match diagnostic.severity {
+            Severity::Error => proto::diagnostic::Severity::Error,
+            Severity::Warning => proto::diagnostic::Severity::Warning,
+        };
+        proto::Diagnostic {
+            severity: severity as i32,
+            pointer: diagnostic.pointer,
+            message: diagnostic.message,
+        }
+    }
+}
 
 pub struct InputParserService {
     orchestrator_addr: Arc<String>,
@@ -21,13 +41,32 @@ impl InputParser for InputParserService {
     ) -> Result<Response<ParseResponse>, Status> {
         let req = request.into_inner();
 
-        // Parse JSON
-        let config = json::parse_json_str(&req.json_config)
-            .map_err(|e| Status::invalid_argument(format!("Invalid JSON: {}", e)))?;
+        // Parse the config in the requested format
+        let format = if req.format.is_empty() {
+            ConfigFormat::Json
+        } else {
+            req.format
+                .parse()
+                .map_err(|e| Status::invalid_argument(format!("{}", e)))?
+        };
+        let config = parser::parse_config_str(&req.json_config, format)
+            .map_err(|e| Status::invalid_argument(format!("Invalid config: {:#}", e)))?;
 
-        // Validate config
-        validator::validate_config(&config)
-            .map_err(|e| Status::invalid_argument(format!("Validation error: {}", e)))?;
+        // Validate config, reporting every problem rather than only the first
+        let diagnostics = validator::diagnose_config(&config);
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            return Ok(Response::new(ParseResponse {
+                success: false,
+                yaml_config: String::new(),
+                simulation_id: String::new(),
+                error_message: format!("Validation failed with {} errors", errors),
+                diagnostics: diagnostics.into_iter().map(Into::into).collect(),
+            }));
+        }
 
         // Generate YAML
         let yaml_str = yaml::generate_simulator_yaml(&config)
@@ -53,20 +92,58 @@ impl InputParser for InputParserService {
             yaml_config: yaml_str,
             simulation_id,
             error_message: String::new(),
+            diagnostics: diagnostics.into_iter().map(Into::into).collect(),
         }))
     }
 }
 
-pub async fn start_grpc_server(port: u16, orchestrator_addr: String) -> Result<()> {
+pub struct SimulationMonitorService {
+    live: Option<Arc<LiveMetrics>>,
+}
+
+#[tonic::async_trait]
+impl SimulationMonitor for SimulationMonitorService {
+    type WatchMetricsStream = Pin<Box<dyn Stream<Item = Result<MetricsSnapshot, Status>> + Send>>;
+
+    async fn watch_metrics(
+        &self,
+        _request: Request<WatchMetricsRequest>,
+    ) -> Result<Response<Self::WatchMetricsStream>, Status> {
+        let live = self
+            .live
+            .as_ref()
+            .ok_or_else(|| Status::unavailable(super::NO_LIVE_METRICS))?;
+        let stream = futures::stream::unfold(live.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(snapshot) => return Some((Ok(snapshot.into()), receiver)),
+                    // Skip snapshots a slow client missed instead of ending its stream
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// `live` is the metrics stream of a simulation run by this process, if any
+pub async fn start_grpc_server(
+    port: u16,
+    orchestrator_addr: String,
+    live: Option<Arc<LiveMetrics>>,
+) -> Result<()> {
     let addr = format!("0.0.0.0:{}", port).parse()?;
     let orchestrator_addr = Arc::new(orchestrator_addr);
 
     let service = InputParserService { orchestrator_addr };
+    let monitor = SimulationMonitorService { live };
 
     println!("Starting gRPC server on {}", addr);
 
     Server::builder()
         .add_service(InputParserServer::new(service))
+        .add_service(SimulationMonitorServer::new(monitor))
         .serve(addr)
         .await?;
 
@@ -1,30 +1,90 @@
-use anyhow::Result;
-use serde_json::Value;
+use anyhow::{Context, Result};
+use futures::Stream;
+use serde::Deserialize;
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::sync::broadcast;
+use warp::hyper::body::Bytes;
+use warp::reply::{Json, WithStatus};
+use warp::sse::Event;
 use warp::{Filter, Rejection, Reply};
 
+use crate::analysis::load::{DEFAULT_SATURATION_THRESHOLD, plan_load};
 use crate::client::grpc;
+use crate::generator::graph::{self, GraphFormat};
 use crate::generator::yaml;
-use crate::parser::json;
+use crate::metrics::LiveMetrics;
+use crate::parser::{self, ConfigFormat, SimulatorConfig};
 use crate::validator;
+use crate::validator::diagnostics::{Diagnostic, Severity};
 
-pub async fn start_http_server(port: u16, orchestrator_addr: String) -> Result<()> {
+/// `live` is the metrics stream of a simulation run by this process, if any
+pub async fn start_http_server(
+    port: u16,
+    orchestrator_addr: String,
+    live: Option<Arc<LiveMetrics>>,
+) -> Result<()> {
     let orchestrator = Arc::new(orchestrator_addr);
 
-    // POST /submit endpoint for JSON submission
+    // POST /submit endpoint for config submission
     let submit = warp::path("submit")
         .and(warp::post())
         .and(warp::body::content_length_limit(1024 * 1024)) // 1MB limit
-        .and(warp::body::json())
+        .and(config_body())
         .and(with_orchestrator(orchestrator))
         .and_then(handle_submit);
 
+    // POST /plan estimates service load and capacity without launching anything
+    let plan = warp::path("plan")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 1024))
+        .and(config_body())
+        .and_then(handle_plan);
+
+    // POST /graph?format=dot|mermaid&annotate=true renders the call graph
+    let graph = warp::path("graph")
+        .and(warp::post())
+        .and(warp::query::<GraphQuery>())
+        .and(warp::body::content_length_limit(1024 * 1024))
+        .and(config_body())
+        .and_then(handle_graph);
+
     // Healthcheck endpoint
     let health = warp::path("health")
         .and(warp::get())
         .map(|| warp::reply::json(&serde_json::json!({"status": "ok"})));
 
-    let routes = submit.or(health);
+    // GET /schema serves the JSON Schema of config documents, for editors to validate against
+    let schema = warp::path("schema")
+        .and(warp::get())
+        .map(|| warp::reply::json(&parser::config_schema()))
+        .with(warp::cors().allow_any_origin());
+
+    // GET /metrics/stream pushes per-second metrics of the running simulation (SSE)
+    let metrics_stream = warp::path!("metrics" / "stream")
+        .and(warp::get())
+        .map(move || match &live {
+            Some(live) => {
+                let stream = snapshot_events(live.subscribe());
+                warp::sse::reply(warp::sse::keep_alive().stream(stream)).into_response()
+            }
+            None => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "success": false,
+                    "error": super::NO_LIVE_METRICS
+                })),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response(),
+        })
+        .with(warp::cors().allow_any_origin());
+
+    let routes = submit
+        .or(plan)
+        .or(graph)
+        .or(health)
+        .or(schema)
+        .or(metrics_stream);
 
     println!("Starting HTTP server on port {}", port);
     warp::serve(routes).run(([0, 0, 0, 0], port)).await;
@@ -32,6 +92,19 @@ pub async fn start_http_server(port: u16, orchestrator_addr: String) -> Result<(
     Ok(())
 }
 
+/// Config document in the request body, in the format its content type names (JSON by default)
+fn config_body() -> impl Filter<Extract = (Result<SimulatorConfig>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and(warp::body::bytes())
+        .map(|content_type: Option<String>, body: Bytes| {
+            let format = content_type
+                .as_deref()
+                .map_or(ConfigFormat::Json, ConfigFormat::from_content_type);
+            let content = std::str::from_utf8(&body).context("Request body is not UTF-8")?;
+            parser::parse_config_str(content, format)
+        })
+}
+
 fn with_orchestrator(
     orchestrator: Arc<String>,
 ) -> impl Filter<Extract = (Arc<String>,), Error = std::convert::Infallible> + Clone {
@@ -39,25 +112,16 @@ fn with_orchestrator(
 }
 
 async fn handle_submit(
-    json_input: Value,
+    parsed: Result<SimulatorConfig>,
     orchestrator: Arc<String>,
 ) -> Result<impl Reply, Rejection> {
-    // Convert JSON value to string
-    let json_str = json_input.to_string();
-
-    // Parse JSON
-    match json::parse_json_str(&json_str) {
+    match parsed {
         Ok(config) => {
-            // Validate config
-            if let Err(err) = validator::validate_config(&config) {
-                return Ok(warp::reply::with_status(
-                    warp::reply::json(&serde_json::json!({
-                        "success": false,
-                        "error": format!("Validation error: {}", err)
-                    })),
-                    warp::http::StatusCode::BAD_REQUEST,
-                ));
-            }
+            // Validate config, passing warnings on to the client
+            let warnings = match validate(&config) {
+                Ok(warnings) => warnings,
+                Err(reply) => return Ok(reply),
+            };
 
             // Generate YAML
             match yaml::generate_simulator_yaml(&config) {
@@ -67,7 +131,8 @@ async fn handle_submit(
                         Ok(simulation_id) => Ok(warp::reply::with_status(
                             warp::reply::json(&serde_json::json!({
                                 "success": true,
-                                "simulation_id": simulation_id
+                                "simulation_id": simulation_id,
+                                "diagnostics": warnings
                             })),
                             warp::http::StatusCode::OK,
                         )),
@@ -92,9 +157,126 @@ async fn handle_submit(
         Err(err) => Ok(warp::reply::with_status(
             warp::reply::json(&serde_json::json!({
                 "success": false,
-                "error": format!("JSON parsing error: {}", err)
+                "error": format!("Config parsing error: {:#}", err)
             })),
             warp::http::StatusCode::BAD_REQUEST,
         )),
     }
 }
+
+/// Validate a config, giving a 400 reply listing every problem if it has errors, or its
+/// warnings otherwise
+fn validate(config: &SimulatorConfig) -> Result<Vec<Diagnostic>, WithStatus<Json>> {
+    let diagnostics = validator::diagnose_config(config);
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors == 0 {
+        return Ok(diagnostics);
+    }
+    Err(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "success": false,
+            "error": format!("Validation error: {} problems found", errors),
+            "diagnostics": diagnostics
+        })),
+        warp::http::StatusCode::BAD_REQUEST,
+    ))
+}
+
+async fn handle_plan(parsed: Result<SimulatorConfig>) -> Result<impl Reply, Rejection> {
+    let config = match parsed {
+        Ok(config) => config,
+        Err(err) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "success": false,
+                    "error": format!("Config parsing error: {:#}", err)
+                })),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let warnings = match validate(&config) {
+        Ok(warnings) => warnings,
+        Err(reply) => return Ok(reply),
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "success": true,
+            "plan": plan_load(&config, DEFAULT_SATURATION_THRESHOLD),
+            "diagnostics": warnings
+        })),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQuery {
+    format: Option<String>,
+    #[serde(default)]
+    annotate: bool,
+}
+
+async fn handle_graph(
+    query: GraphQuery,
+    parsed: Result<SimulatorConfig>,
+) -> Result<impl Reply, Rejection> {
+    let bad_request = |error: String| {
+        warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+            warp::http::StatusCode::BAD_REQUEST,
+        )
+        .into_response()
+    };
+
+    let format: GraphFormat = match query.format.as_deref().unwrap_or("dot").parse() {
+        Ok(format) => format,
+        Err(err) => return Ok(bad_request(err.to_string())),
+    };
+    let config = match parsed {
+        Ok(config) => config,
+        Err(err) => return Ok(bad_request(format!("Config parsing error: {:#}", err))),
+    };
+    if let Err(reply) = validate(&config) {
+        return Ok(reply.into_response());
+    }
+
+    let content_type = match format {
+        GraphFormat::Dot => "text/vnd.graphviz",
+        GraphFormat::Mermaid => "text/plain",
+    };
+    Ok(warp::reply::with_header(
+        graph::generate_graph(&config, format, query.annotate),
+        "content-type",
+        content_type,
+    )
+    .into_response())
+}
+
+/// Turn broadcast snapshots into SSE events, skipping snapshots a slow client missed
+fn snapshot_events(
+    receiver: broadcast::Receiver<crate::metrics::MetricsSnapshot>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(snapshot) => {
+                    let event = Event::default()
+                        .event("metrics")
+                        .json_data(&snapshot)
+                        .unwrap_or_else(|_| Event::default().comment("unserializable snapshot"));
+                    return Some((Ok(event), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
@@ -1,2 +1,7 @@
 pub mod grpc;
 pub mod http;
+
+/// Why there is no live metrics stream: configs submitted to the servers run on the remote
+/// orchestrator, which doesn't send its metrics back
+const NO_LIVE_METRICS: &str =
+    "Live metrics are only streamed for simulations started from the command line";
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How serious a diagnostic is; errors stop a config from running, warnings don't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a config, located by a JSON pointer into the document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// RFC 6901 pointer, e.g. `/services/service_beta/methods/fetch_summary/error_rate`
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pointer, self.message)
+    }
+}
+
+/// Collects the diagnostics of every rule instead of stopping at the first one
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn error(&mut self, pointer: String, message: String) {
+        self.items.push(Diagnostic {
+            severity: Severity::Error,
+            pointer,
+            message,
+        });
+    }
+
+    pub fn warning(&mut self, pointer: String, message: String) {
+        self.items.push(Diagnostic {
+            severity: Severity::Warning,
+            pointer,
+            message,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.items
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// All diagnostics, ordered by where they point in the document
+    pub fn into_sorted(mut self) -> Vec<Diagnostic> {
+        self.items
+            .sort_by(|a, b| (&a.pointer, &a.message).cmp(&(&b.pointer, &b.message)));
+        self.items
+    }
+}
+
+/// Build a JSON pointer from path segments, escaping `~` and `/` within them
+pub fn pointer(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
@@ -1,20 +1,66 @@
+pub mod diagnostics;
 pub mod rules;
+pub mod warnings;
 
 use crate::parser::SimulatorConfig;
-use anyhow::Result;
+use anyhow::{Result, bail};
+use diagnostics::{Diagnostic, Diagnostics, Severity};
+
+/// Run every validation rule and collect all problems found, ordered by location
+pub fn diagnose_config(config: &SimulatorConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Diagnostics::default();
 
-/// Validate a simulator configuration
-pub fn validate_config(config: &SimulatorConfig) -> Result<()> {
     // Run all validation rules
-    rules::validate_has_services(config)?;
-    rules::validate_service_dependencies(config)?;
-    rules::validate_latency_distributions(config)?;
-    rules::validate_error_rates(config)?;
+    rules::validate_has_services(config, &mut diagnostics);
+    rules::validate_service_dependencies(config, &mut diagnostics);
+    rules::validate_latency_distributions(config, &mut diagnostics);
+    rules::validate_error_rates(config, &mut diagnostics);
+    rules::validate_concurrency_limits(config, &mut diagnostics);
+    rules::validate_replicas(config, &mut diagnostics);
+    rules::validate_recursion_limits(config, &mut diagnostics);
+    rules::validate_slos(config, &mut diagnostics);
+    rules::validate_faults(config, &mut diagnostics);
+    rules::validate_network(config, &mut diagnostics);
 
     // If load configuration is present, validate it
     if let Some(load) = &config.load {
-        rules::validate_load_config(load, config)?;
+        rules::validate_load_config(load, config, &mut diagnostics);
     }
 
+    // Problems that don't stop a run
+    warnings::warn_error_rates_out_of_range(config, &mut diagnostics);
+    warnings::warn_negative_latencies(config, &mut diagnostics);
+    warnings::warn_duplicate_ports(config, &mut diagnostics);
+    warnings::warn_invalid_service_names(config, &mut diagnostics);
+    warnings::warn_slos_without_load(config, &mut diagnostics);
+    warnings::warn_faults_after_load(config, &mut diagnostics);
+    warnings::warn_unused_network_edges(config, &mut diagnostics);
+    // These walk the call graph, so they need a config without errors
+    if !diagnostics.has_errors() {
+        warnings::warn_unreachable_methods(config, &mut diagnostics);
+        warnings::warn_predicted_overload(config, &mut diagnostics);
+    }
+
+    diagnostics.into_sorted()
+}
+
+/// Only the warnings among a config's diagnostics
+pub fn config_warnings(config: &SimulatorConfig) -> Vec<Diagnostic> {
+    diagnose_config(config)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+        .collect()
+}
+
+/// Validate a simulator configuration, failing with every error found
+pub fn validate_config(config: &SimulatorConfig) -> Result<()> {
+    let errors: Vec<String> = diagnose_config(config)
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(Diagnostic::to_string)
+        .collect();
+    if !errors.is_empty() {
+        bail!("{}", errors.join("\n"));
+    }
     Ok(())
 }
@@ -1,309 +1,640 @@
-use anyhow::{Result, bail};
 use std::collections::HashSet;
 
-use crate::parser::{Distribution, EntryPoint, LoadConfig, SimulatorConfig};
+use super::diagnostics::{Diagnostics, pointer};
+use crate::analysis::distribution::empirical_quantiles;
+use crate::orchestrator::{
+    BROKER_SERVICE_NAME, COLLECTOR_SERVICE_NAME, PROMETHEUS_SERVICE_NAME, container_names,
+};
+use crate::parser::{
+    Distribution, EntryPoint, FaultAction, FaultConfig, LoadConfig, MethodConfig, SimulatorConfig,
+};
 
 /// Validate that the configuration has at least one service
-pub fn validate_has_services(config: &SimulatorConfig) -> Result<()> {
+pub fn validate_has_services(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
     if config.services.is_empty() {
-        bail!("Configuration must define at least one service");
+        diagnostics.error(
+            pointer(&["services"]),
+            "Configuration must define at least one service".to_string(),
+        );
     }
-    Ok(())
 }
 
 /// Validate that all service dependencies exist
-pub fn validate_service_dependencies(config: &SimulatorConfig) -> Result<()> {
-    let service_names: HashSet<&String> = config.services.keys().collect();
-
+pub fn validate_service_dependencies(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
     // Check that all referenced services exist
     for (service_name, service) in &config.services {
         for (method_name, method) in &service.methods {
-            for call_sequence in &method.calls {
-                for call in call_sequence {
-                    let parts: Vec<&str> = call.split('.').collect();
-                    if parts.len() != 2 {
-                        bail!(
-                            "Invalid call format in {}.{}: '{}'. Expected 'ServiceName.MethodName'",
-                            service_name,
-                            method_name,
-                            call
-                        );
-                    }
-
-                    let called_service = parts[0];
-                    let called_method = parts[1];
-
-                    // Check if called service exists
-                    if !service_names.contains(&called_service.to_string()) {
-                        bail!(
-                            "Service '{}' called by {}.{} does not exist",
-                            called_service,
-                            service_name,
-                            method_name
-                        );
-                    }
-
-                    // Check if called method exists in that service
-                    if !config.services[called_service]
-                        .methods
-                        .contains_key(called_method)
-                    {
-                        bail!(
-                            "Method '{}' called on service '{}' does not exist",
-                            called_method,
-                            called_service
-                        );
-                    }
+            for (stage, call_sequence) in method.calls.iter().enumerate() {
+                for (index, call) in call_sequence.iter().enumerate() {
+                    let location = pointer(&[
+                        "services",
+                        service_name,
+                        "methods",
+                        method_name,
+                        "calls",
+                        &stage.to_string(),
+                        &index.to_string(),
+                    ]);
+                    validate_call_target(
+                        config,
+                        service_name,
+                        method_name,
+                        call,
+                        location,
+                        diagnostics,
+                    );
                 }
             }
+            for (index, call) in method.async_calls.iter().enumerate() {
+                let location = pointer(&[
+                    "services",
+                    service_name,
+                    "methods",
+                    method_name,
+                    "async_calls",
+                    &index.to_string(),
+                ]);
+                validate_call_target(
+                    config,
+                    service_name,
+                    method_name,
+                    call,
+                    location,
+                    diagnostics,
+                );
+            }
         }
     }
 
+    // The broker and telemetry containers are added by the orchestrator, so their names
+    // must stay free
+    let uses_async_calls = config
+        .services
+        .values()
+        .flat_map(|service| service.methods.values())
+        .any(|method| !method.async_calls.is_empty());
+    if uses_async_calls && config.services.contains_key(BROKER_SERVICE_NAME) {
+        diagnostics.error(
+            pointer(&["services", BROKER_SERVICE_NAME]),
+            format!(
+                "Service name '{}' is reserved for the message broker used by async calls",
+                BROKER_SERVICE_NAME
+            ),
+        );
+    }
+
+    let collector = config.telemetry.as_ref().is_some_and(|t| t.collector);
+    if collector && config.services.contains_key(COLLECTOR_SERVICE_NAME) {
+        diagnostics.error(
+            pointer(&["services", COLLECTOR_SERVICE_NAME]),
+            format!(
+                "Service name '{}' is reserved for the trace collector added by telemetry.collector",
+                COLLECTOR_SERVICE_NAME
+            ),
+        );
+    }
+
+    let prometheus = config.telemetry.as_ref().is_some_and(|t| t.prometheus);
+    if prometheus && config.services.contains_key(PROMETHEUS_SERVICE_NAME) {
+        diagnostics.error(
+            pointer(&["services", PROMETHEUS_SERVICE_NAME]),
+            format!(
+                "Service name '{}' is reserved for the metrics scraper added by telemetry.prometheus",
+                PROMETHEUS_SERVICE_NAME
+            ),
+        );
+    }
+
     // Check for circular dependencies using a simple DFS algorithm
-    detect_circular_dependencies(config)?;
+    detect_circular_dependencies(config, diagnostics);
+}
+
+/// Validate that a single call refers to an existing service and method
+fn validate_call_target(
+    config: &SimulatorConfig,
+    service_name: &str,
+    method_name: &str,
+    call: &str,
+    location: String,
+    diagnostics: &mut Diagnostics,
+) {
+    let parts: Vec<&str> = call.split('.').collect();
+    if parts.len() != 2 {
+        diagnostics.error(
+            location,
+            format!(
+                "Invalid call format in {}.{}: '{}'. Expected 'ServiceName.MethodName'",
+                service_name, method_name, call
+            ),
+        );
+        return;
+    }
+
+    let called_service = parts[0];
+    let called_method = parts[1];
 
-    Ok(())
+    // Check if called service exists
+    let Some(service) = config.services.get(called_service) else {
+        diagnostics.error(
+            location,
+            format!(
+                "Service '{}' called by {}.{} does not exist",
+                called_service, service_name, method_name
+            ),
+        );
+        return;
+    };
+
+    // Check if called method exists in that service
+    if !service.methods.contains_key(called_method) {
+        diagnostics.error(
+            location,
+            format!(
+                "Method '{}' called on service '{}' does not exist",
+                called_method, called_service
+            ),
+        );
+    }
 }
 
 /// Validate that all latency distributions are valid
-pub fn validate_latency_distributions(config: &SimulatorConfig) -> Result<()> {
+pub fn validate_latency_distributions(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
     for (service_name, service) in &config.services {
         for (method_name, method) in &service.methods {
-            validate_single_distribution(&method.latency_distribution, service_name, method_name)?;
+            validate_single_distribution(
+                &method.latency_distribution,
+                service_name,
+                method_name,
+                "latency_distribution",
+                diagnostics,
+            );
         }
     }
-    Ok(())
 }
 
-/// Validate a single distribution
+/// Validate a single distribution, found under `field` of the method
 fn validate_single_distribution(
     distribution: &Distribution,
     service_name: &str,
     method_name: &str,
-) -> Result<()> {
+    field: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    let location = |segments: &[&str]| {
+        let mut path = vec!["services", service_name, "methods", method_name, field];
+        path.extend_from_slice(segments);
+        pointer(&path)
+    };
+    let parameters = &distribution.parameters;
+    let missing = |diagnostics: &mut Diagnostics, name: &str, kind: &str| {
+        if parameters.contains_key(name) {
+            return false;
+        }
+        diagnostics.error(
+            location(&["parameters"]),
+            format!(
+                "{} distribution for {}.{} missing '{}' parameter",
+                kind, service_name, method_name, name
+            ),
+        );
+        true
+    };
+
     match distribution.distribution_type.as_str() {
         "normal" => {
             // Check required parameters for Normal distribution
-            if !distribution.parameters.contains_key("mean") {
-                bail!(
-                    "Normal distribution for {}.{} missing 'mean' parameter",
-                    service_name,
-                    method_name
-                );
-            }
-            if !distribution.parameters.contains_key("stddev") {
-                bail!(
-                    "Normal distribution for {}.{} missing 'stddev' parameter",
-                    service_name,
-                    method_name
-                );
-            }
+            let missing_mean = missing(diagnostics, "mean", "Normal");
+            let missing_stddev = missing(diagnostics, "stddev", "Normal");
 
             // Validate mean is non-negative
-            if distribution.parameters["mean"] < 0.0 {
-                bail!(
-                    "Normal distribution for {}.{} has negative mean: {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["mean"]
+            if !missing_mean && parameters["mean"] < 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "mean"]),
+                    format!(
+                        "Normal distribution for {}.{} has negative mean: {}",
+                        service_name, method_name, parameters["mean"]
+                    ),
                 );
             }
 
             // Validate stddev is positive
-            if distribution.parameters["stddev"] <= 0.0 {
-                bail!(
-                    "Normal distribution for {}.{} has non-positive stddev: {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["stddev"]
+            if !missing_stddev && parameters["stddev"] <= 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "stddev"]),
+                    format!(
+                        "Normal distribution for {}.{} has non-positive stddev: {}",
+                        service_name, method_name, parameters["stddev"]
+                    ),
                 );
             }
         }
         "uniform" => {
             // Check required parameters for Uniform distribution
-            if !distribution.parameters.contains_key("min") {
-                bail!(
-                    "Uniform distribution for {}.{} missing 'min' parameter",
-                    service_name,
-                    method_name
-                );
-            }
-            if !distribution.parameters.contains_key("max") {
-                bail!(
-                    "Uniform distribution for {}.{} missing 'max' parameter",
-                    service_name,
-                    method_name
-                );
+            let missing_min = missing(diagnostics, "min", "Uniform");
+            let missing_max = missing(diagnostics, "max", "Uniform");
+            if missing_min || missing_max {
+                return;
             }
 
             // Validate min <= max
-            if distribution.parameters["min"] > distribution.parameters["max"] {
-                bail!(
-                    "Uniform distribution for {}.{} has min > max: {} > {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["min"],
-                    distribution.parameters["max"]
+            if parameters["min"] > parameters["max"] {
+                diagnostics.error(
+                    location(&["parameters", "min"]),
+                    format!(
+                        "Uniform distribution for {}.{} has min > max: {} > {}",
+                        service_name, method_name, parameters["min"], parameters["max"]
+                    ),
                 );
             }
 
             // Validate min is non-negative
-            if distribution.parameters["min"] < 0.0 {
-                bail!(
-                    "Uniform distribution for {}.{} has negative min: {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["min"]
+            if parameters["min"] < 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "min"]),
+                    format!(
+                        "Uniform distribution for {}.{} has negative min: {}",
+                        service_name, method_name, parameters["min"]
+                    ),
                 );
             }
         }
         "constant" => {
-            // Check required parameter for Constant distribution
-            if !distribution.parameters.contains_key("value") {
-                bail!(
-                    "Constant distribution for {}.{} missing 'value' parameter",
-                    service_name,
-                    method_name
-                );
-            }
-
-            // Validate value is non-negative
-            if distribution.parameters["value"] < 0.0 {
-                bail!(
-                    "Constant distribution for {}.{} has negative value: {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["value"]
+            // Check required parameter for Constant distribution, which must be non-negative
+            if !missing(diagnostics, "value", "Constant") && parameters["value"] < 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "value"]),
+                    format!(
+                        "Constant distribution for {}.{} has negative value: {}",
+                        service_name, method_name, parameters["value"]
+                    ),
                 );
             }
         }
         "exponential" => {
-            // Check required parameter for Exponential distribution
-            if !distribution.parameters.contains_key("rate") {
-                bail!(
-                    "Exponential distribution for {}.{} missing 'rate' parameter",
-                    service_name,
-                    method_name
+            // Check required parameter for Exponential distribution, which must be positive
+            if !missing(diagnostics, "rate", "Exponential") && parameters["rate"] <= 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "rate"]),
+                    format!(
+                        "Exponential distribution for {}.{} has non-positive rate: {}",
+                        service_name, method_name, parameters["rate"]
+                    ),
                 );
             }
+        }
+        "empirical" => {
+            // Every parameter names a percentile, e.g. 'p50' or 'p99.9'
+            for name in parameters.keys() {
+                let percent = name.strip_prefix('p').and_then(|p| p.parse::<f64>().ok());
+                if !percent.is_some_and(|p| (0.0..=100.0).contains(&p)) {
+                    diagnostics.error(
+                        location(&["parameters", name]),
+                        format!(
+                            "Empirical distribution for {}.{} has invalid percentile '{}', expected e.g. 'p50'",
+                            service_name, method_name, name
+                        ),
+                    );
+                }
+            }
+            let missing_bounds = ["p0", "p100"]
+                .iter()
+                .filter(|bound| missing(diagnostics, bound, "Empirical"))
+                .count();
+            if missing_bounds > 0 {
+                return;
+            }
 
-            // Validate rate is positive
-            if distribution.parameters["rate"] <= 0.0 {
-                bail!(
-                    "Exponential distribution for {}.{} has non-positive rate: {}",
-                    service_name,
-                    method_name,
-                    distribution.parameters["rate"]
+            // Validate values are non-negative and non-decreasing
+            let quantiles = empirical_quantiles(distribution);
+            if quantiles[0].1 < 0.0 {
+                diagnostics.error(
+                    location(&["parameters", "p0"]),
+                    format!(
+                        "Empirical distribution for {}.{} has negative p0: {}",
+                        service_name, method_name, quantiles[0].1
+                    ),
+                );
+            }
+            if quantiles.windows(2).any(|pair| pair[1].1 < pair[0].1) {
+                diagnostics.error(
+                    location(&["parameters"]),
+                    format!(
+                        "Empirical distribution for {}.{} has decreasing percentiles",
+                        service_name, method_name
+                    ),
                 );
             }
         }
         "bernoulli" => {
-            if !distribution.parameters.contains_key("p") {
-                bail!(
-                    "Exponential distribution for {}.{} missing 'p' parameter",
-                    service_name,
-                    method_name
-                )
-            }
+            missing(diagnostics, "p", "Bernoulli");
         }
         _ => {
-            bail!(
-                "Unknown distribution type for {}.{}: '{}'",
-                service_name,
-                method_name,
-                distribution.distribution_type
+            diagnostics.error(
+                location(&["type"]),
+                format!(
+                    "Unknown distribution type for {}.{}: '{}'",
+                    service_name, method_name, distribution.distribution_type
+                ),
             );
         }
     }
-    Ok(())
 }
 
-/// Detect circular dependencies in the service call graph
-fn detect_circular_dependencies(config: &SimulatorConfig) -> Result<()> {
-    // Track visited services in current call stack
+/// Detect call cycles between methods.
+///
+/// A cycle is only allowed when one of its methods opts into bounded recursion with
+/// `max_recursion_depth`, since that method stops calling once the limit is reached. The
+/// search runs on the call graph without those methods, so every cycle it finds is
+/// unbounded, and one bounded cycle can't hide an unbounded one sharing its methods.
+fn detect_circular_dependencies(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
     let mut visited = HashSet::new();
-    let mut stack = HashSet::new();
+    let mut path = Vec::new();
 
-    // Run DFS on each service
-    for service_name in config.services.keys() {
-        if !visited.contains(service_name) {
-            if detect_cycles_dfs(config, service_name, &mut visited, &mut stack)? {
-                return Ok(());
-            }
+    // Sorted so the same cycle is reported on every run
+    let mut methods: Vec<(&str, &str)> = config
+        .services
+        .iter()
+        .flat_map(|(service_name, service)| {
+            service
+                .methods
+                .keys()
+                .map(move |method_name| (service_name.as_str(), method_name.as_str()))
+        })
+        .collect();
+    methods.sort();
+
+    for method in methods {
+        detect_cycles_dfs(config, method, &mut visited, &mut path, diagnostics);
+    }
+}
+
+/// Helper function for DFS cycle detection, keeping the current call path
+fn detect_cycles_dfs<'a>(
+    config: &'a SimulatorConfig,
+    node: (&'a str, &'a str),
+    visited: &mut HashSet<(&'a str, &'a str)>,
+    path: &mut Vec<(&'a str, &'a str)>,
+    diagnostics: &mut Diagnostics,
+) {
+    // Calls to missing methods are reported by `validate_call_target`
+    let Some(method) = find_method(config, node) else {
+        return;
+    };
+    // Bounded methods break every cycle through them
+    if method.max_recursion_depth.is_some() {
+        return;
+    }
+
+    if let Some(start) = path.iter().position(|on_path| *on_path == node) {
+        let described: Vec<String> = path[start..]
+            .iter()
+            .chain(std::iter::once(&node))
+            .map(|(service_name, method_name)| format!("{}.{}", service_name, method_name))
+            .collect();
+        let (caller_service, caller_method) = path[path.len() - 1];
+        diagnostics.error(
+            pointer(&["services", caller_service, "methods", caller_method]),
+            format!(
+                "Circular dependency detected: {} (set 'max_recursion_depth' on one of these methods to allow bounded recursion)",
+                described.join(" -> ")
+            ),
+        );
+        return;
+    }
+    if !visited.insert(node) {
+        return;
+    }
+
+    path.push(node);
+    // Async calls still trigger work downstream, so a loop through them never ends
+    for call in downstream_calls(method) {
+        if let Some(callee) = split_call(call) {
+            detect_cycles_dfs(config, callee, visited, path, diagnostics);
         }
     }
+    path.pop();
+}
 
-    Ok(())
+fn find_method<'a>(config: &'a SimulatorConfig, node: (&str, &str)) -> Option<&'a MethodConfig> {
+    config.services.get(node.0)?.methods.get(node.1)
 }
 
-/// Helper function for DFS cycle detection
-fn detect_cycles_dfs(
-    config: &SimulatorConfig,
-    service_name: &String,
-    visited: &mut HashSet<String>,
-    stack: &mut HashSet<String>,
-) -> Result<bool> {
-    visited.insert(service_name.clone());
-    stack.insert(service_name.clone());
-
-    // Check all methods in this service
-    let service = &config.services[service_name];
-    for method in service.methods.values() {
-        for call_sequence in &method.calls {
-            for call in call_sequence {
-                let parts: Vec<&str> = call.split('.').collect();
-                let called_service = parts[0].to_string();
-
-                // If this called service is already in our call stack, we have a cycle
-                if stack.contains(&called_service) {
-                    bail!(
-                        "Circular dependency detected: Service '{}' depends on '{}'",
+/// Validate that recursion limits allow the method to run at least once
+pub fn validate_recursion_limits(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (service_name, service) in &config.services {
+        for (method_name, method) in &service.methods {
+            if method.max_recursion_depth == Some(0) {
+                diagnostics.error(
+                    pointer(&[
+                        "services",
                         service_name,
-                        called_service
-                    );
-                }
+                        "methods",
+                        method_name,
+                        "max_recursion_depth",
+                    ]),
+                    format!(
+                        "Method {}.{} has a max_recursion_depth of 0; use 1 or more, or remove it",
+                        service_name, method_name
+                    ),
+                );
+            }
+        }
+    }
+}
 
-                // If we haven't visited this called service yet, recursively check it
-                if !visited.contains(&called_service) {
-                    if detect_cycles_dfs(config, &called_service, visited, stack)? {
-                        return Ok(true);
-                    }
-                }
+/// Validate that concurrency limits leave at least one worker per service
+pub fn validate_concurrency_limits(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (service_name, service) in &config.services {
+        if service.concurrency == Some(0) {
+            diagnostics.error(
+                pointer(&["services", service_name, "concurrency"]),
+                format!(
+                    "Service '{}' has a concurrency limit of 0 and could never handle a request",
+                    service_name
+                ),
+            );
+        }
+    }
+}
+
+/// Validate replica counts and overrides, and that replica containers keep distinct names
+pub fn validate_replicas(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (service_name, service) in &config.services {
+        if service.replicas == Some(0) {
+            diagnostics.error(
+                pointer(&["services", service_name, "replicas"]),
+                format!(
+                    "Service '{}' has 0 replicas and could never handle a request",
+                    service_name
+                ),
+            );
+        }
+        let replicas = service.replica_count();
+        for container in container_names(service_name, service) {
+            if container != *service_name && config.services.contains_key(&container) {
+                diagnostics.error(
+                    pointer(&["services", service_name, "replicas"]),
+                    format!(
+                        "Replica container '{}' of service '{}' has the name of another service",
+                        container, service_name
+                    ),
+                );
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for (index, replica) in service.replica_overrides.iter().enumerate() {
+            let location = |field: &str| {
+                pointer(&[
+                    "services",
+                    service_name,
+                    "replica_overrides",
+                    &index.to_string(),
+                    field,
+                ])
+            };
+            if replica.replica >= replicas {
+                diagnostics.error(
+                    location("replica"),
+                    format!(
+                        "Service '{}' overrides replica {}, but has {} replica(s)",
+                        service_name, replica.replica, replicas
+                    ),
+                );
+            } else if !seen.insert(replica.replica) {
+                diagnostics.error(
+                    location("replica"),
+                    format!(
+                        "Service '{}' overrides replica {} more than once",
+                        service_name, replica.replica
+                    ),
+                );
+            }
+            if !(replica.latency_multiplier > 0.0 && replica.latency_multiplier.is_finite()) {
+                diagnostics.error(
+                    location("latency_multiplier"),
+                    format!(
+                        "Replica {} of service '{}' has non-positive latency_multiplier {}",
+                        replica.replica, service_name, replica.latency_multiplier
+                    ),
+                );
+            }
+            if !(0.0..=1.0).contains(&replica.error_rate) {
+                diagnostics.error(
+                    location("error_rate"),
+                    format!(
+                        "Replica {} of service '{}' has error_rate {}, outside [0, 1]",
+                        replica.replica, service_name, replica.error_rate
+                    ),
+                );
             }
         }
     }
+}
 
-    // Remove from current path stack when we're done exploring this service
-    stack.remove(service_name);
-    Ok(false)
+/// All calls a method makes: every synchronous stage in order, then the async calls
+pub fn downstream_calls(method: &MethodConfig) -> impl Iterator<Item = &String> {
+    method.calls.iter().flatten().chain(&method.async_calls)
+}
+
+/// Split a "ServiceName.MethodName" call into its parts
+pub fn split_call(call: &str) -> Option<(&str, &str)> {
+    call.split_once('.')
+}
+
+/// Order all methods so that every caller comes before the methods it calls.
+///
+/// Relies on `validate_service_dependencies` having ruled out dangling calls. Calls that
+/// close an allowed recursive cycle are the only ones that can point back up the order.
+pub fn topological_methods(config: &SimulatorConfig) -> Vec<(String, String)> {
+    let mut visited = HashSet::new();
+    let mut post_order = Vec::new();
+
+    let mut methods: Vec<(&String, &String)> = config
+        .services
+        .iter()
+        .flat_map(|(service_name, service)| {
+            service
+                .methods
+                .keys()
+                .map(move |method_name| (service_name, method_name))
+        })
+        .collect();
+    methods.sort();
+
+    for (service_name, method_name) in methods {
+        visit_method(
+            config,
+            (service_name.clone(), method_name.clone()),
+            &mut visited,
+            &mut post_order,
+        );
+    }
+
+    post_order.reverse();
+    post_order
+}
+
+/// Depth-first post-order visit used by `topological_methods`
+fn visit_method(
+    config: &SimulatorConfig,
+    node: (String, String),
+    visited: &mut HashSet<(String, String)>,
+    post_order: &mut Vec<(String, String)>,
+) {
+    if !visited.insert(node.clone()) {
+        return;
+    }
+    let method = &config.services[&node.0].methods[&node.1];
+    for call in downstream_calls(method) {
+        if let Some((service_name, method_name)) = split_call(call) {
+            visit_method(
+                config,
+                (service_name.to_string(), method_name.to_string()),
+                visited,
+                post_order,
+            );
+        }
+    }
+    post_order.push(node);
 }
 
 /// Validate error rates for all methods in all services
-pub fn validate_error_rates(config: &SimulatorConfig) -> Result<()> {
+pub fn validate_error_rates(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
     for (service_name, service) in &config.services {
         for (method_name, method) in &service.methods {
             // Check if error_rate exists
             if let Some(error_rate) = &method.error_rate {
-                validate_single_distribution(error_rate, service_name, method_name)?;
+                validate_single_distribution(
+                    error_rate,
+                    service_name,
+                    method_name,
+                    "error_rate",
+                    diagnostics,
+                );
             }
         }
     }
-    Ok(())
 }
 
 /// Validate load configuration
-pub fn validate_load_config(load: &LoadConfig, config: &SimulatorConfig) -> Result<()> {
+pub fn validate_load_config(
+    load: &LoadConfig,
+    config: &SimulatorConfig,
+    diagnostics: &mut Diagnostics,
+) {
     // Ensure there's at least one entry point
     if load.entry_points.is_empty() {
-        bail!("Load configuration must have at least one entry point");
+        diagnostics.error(
+            pointer(&["load", "entry_points"]),
+            "Load configuration must have at least one entry point".to_string(),
+        );
     }
 
     // Validate each entry point
     for (index, entry_point) in load.entry_points.iter().enumerate() {
-        validate_entry_point(entry_point, config, index)?;
+        validate_entry_point(entry_point, config, index, diagnostics);
     }
-
-    Ok(())
 }
 
 /// Validate a single entry point
@@ -311,36 +642,423 @@ fn validate_entry_point(
     entry_point: &EntryPoint,
     config: &SimulatorConfig,
     index: usize,
-) -> Result<()> {
-    // Validate service exists
-    if !config.services.contains_key(&entry_point.service) {
-        bail!(
-            "Entry point service '{}' at index {} does not exist",
-            entry_point.service,
-            index
+    diagnostics: &mut Diagnostics,
+) {
+    let location = |field: &str| pointer(&["load", "entry_points", &index.to_string(), field]);
+
+    // Validate service and method exist
+    match config.services.get(&entry_point.service) {
+        None => diagnostics.error(
+            location("service"),
+            format!(
+                "Entry point service '{}' at index {} does not exist",
+                entry_point.service, index
+            ),
+        ),
+        Some(service) if !service.methods.contains_key(&entry_point.method) => diagnostics.error(
+            location("method"),
+            format!(
+                "Entry point method '{}' at index {} does not exist in service '{}'",
+                entry_point.method, index, entry_point.service
+            ),
+        ),
+        Some(_) => {}
+    }
+
+    // Validate requests per second - must be positive (u32 is already non-negative)
+    if entry_point.requests_per_second == 0 {
+        diagnostics.error(
+            location("requests_per_second"),
+            format!(
+                "Entry point requests_per_second at index {} must be positive",
+                index
+            ),
         );
     }
+}
+
+/// Validate that every SLO names an existing entry point method and sensible targets
+pub fn validate_slos(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (index, slo) in config.slos.iter().enumerate() {
+        let location = |field: &str| pointer(&["slos", &index.to_string(), field]);
+
+        let exists = split_call(&slo.entry_point)
+            .is_some_and(|target| find_method(config, target).is_some());
+        if !exists {
+            diagnostics.error(
+                location("entry_point"),
+                format!(
+                    "SLO entry point '{}' at index {} is not an existing 'ServiceName.MethodName'",
+                    slo.entry_point, index
+                ),
+            );
+        }
+        if slo.objectives.is_empty() {
+            diagnostics.error(
+                location("objectives"),
+                format!("SLO for '{}' has no objectives", slo.entry_point),
+            );
+        }
+        if slo.window_seconds == 0 {
+            diagnostics.error(
+                location("window_seconds"),
+                format!(
+                    "SLO for '{}' must use windows of at least 1 second",
+                    slo.entry_point
+                ),
+            );
+        }
+        if !(0.0..=1.0).contains(&slo.min_compliance) {
+            diagnostics.error(
+                location("min_compliance"),
+                format!(
+                    "SLO for '{}' has min_compliance {}, outside [0, 1]",
+                    slo.entry_point, slo.min_compliance
+                ),
+            );
+        }
+    }
+}
+
+/// Validate that faults target existing services with sensible timing and strength
+pub fn validate_faults(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (index, fault) in config.faults.iter().enumerate() {
+        let location = |field: &str| pointer(&["faults", &index.to_string(), field]);
 
-    // Validate method exists in that service
-    if !config.services[&entry_point.service]
-        .methods
-        .contains_key(&entry_point.method)
-    {
-        bail!(
-            "Entry point method '{}' at index {} does not exist in service '{}'",
-            entry_point.method,
-            index,
-            entry_point.service
+        match (config.services.get(&fault.service), fault.replica) {
+            (None, _) => diagnostics.error(
+                location("service"),
+                format!(
+                    "Fault service '{}' at index {} does not exist",
+                    fault.service, index
+                ),
+            ),
+            (Some(service), Some(replica)) if replica >= service.replica_count() => diagnostics
+                .error(
+                    location("replica"),
+                    format!(
+                        "Fault at index {} targets replica {} of '{}', which has {} replica(s)",
+                        index,
+                        replica,
+                        fault.service,
+                        service.replica_count()
+                    ),
+                ),
+            _ => {}
+        }
+        if !(fault.at_seconds >= 0.0 && fault.at_seconds.is_finite()) {
+            diagnostics.error(
+                location("at_seconds"),
+                format!(
+                    "Fault at index {} must start at a non-negative time, got {}",
+                    index, fault.at_seconds
+                ),
+            );
+        }
+        if let Some(duration) = fault.duration_seconds
+            && !(duration > 0.0 && duration.is_finite())
+        {
+            diagnostics.error(
+                location("duration_seconds"),
+                format!(
+                    "Fault at index {} must last a positive time, got {}",
+                    index, duration
+                ),
+            );
+        }
+        match fault.action {
+            FaultAction::Slow { latency_multiplier }
+                if !(latency_multiplier > 0.0 && latency_multiplier.is_finite()) =>
+            {
+                diagnostics.error(
+                    location("latency_multiplier"),
+                    format!(
+                        "Fault at index {} has non-positive latency_multiplier {}",
+                        index, latency_multiplier
+                    ),
+                );
+            }
+            FaultAction::Error { error_rate } if !(0.0..=1.0).contains(&error_rate) => {
+                diagnostics.error(
+                    location("error_rate"),
+                    format!(
+                        "Fault at index {} has error_rate {}, outside [0, 1]",
+                        index, error_rate
+                    ),
+                );
+            }
+            FaultAction::Partition { ref peers } => {
+                validate_partition(config, fault, index, peers, diagnostics)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Partitions cut whole services off from other existing services
+fn validate_partition(
+    config: &SimulatorConfig,
+    fault: &FaultConfig,
+    index: usize,
+    peers: &[String],
+    diagnostics: &mut Diagnostics,
+) {
+    let location = |field: &str| pointer(&["faults", &index.to_string(), field]);
+    // Callers pick a callee's replica themselves, so only whole services can be cut off
+    if fault.replica.is_some() {
+        diagnostics.error(
+            location("replica"),
+            format!(
+                "Partition fault at index {} must apply to every replica of '{}'",
+                index, fault.service
+            ),
+        );
+    }
+    if peers.is_empty() {
+        diagnostics.error(
+            location("peers"),
+            format!("Partition fault at index {} has no peers", index),
         );
     }
+    for (peer_index, peer) in peers.iter().enumerate() {
+        let location = pointer(&[
+            "faults",
+            &index.to_string(),
+            "peers",
+            &peer_index.to_string(),
+        ]);
+        if !config.services.contains_key(peer) {
+            diagnostics.error(
+                location,
+                format!(
+                    "Partition fault at index {} names peer '{}', which does not exist",
+                    index, peer
+                ),
+            );
+        } else if *peer == fault.service {
+            diagnostics.error(
+                location,
+                format!(
+                    "Partition fault at index {} cannot cut '{}' off from itself",
+                    index, peer
+                ),
+            );
+        }
+    }
+}
 
-    // Validate requests per second - must be positive (u32 is already non-negative)
-    if entry_point.requests_per_second == 0 {
-        bail!(
-            "Entry point requests_per_second at index {} must be positive",
-            index
+/// Validate that network edges join existing services with sensible link settings
+pub fn validate_network(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let Some(network) = &config.network else {
+        return;
+    };
+    let mut seen = HashSet::new();
+    for (index, edge) in network.edges.iter().enumerate() {
+        let location = |field: &str| pointer(&["network", "edges", &index.to_string(), field]);
+
+        for (field, service) in [("caller", &edge.caller), ("callee", &edge.callee)] {
+            if !config.services.contains_key(service) {
+                diagnostics.error(
+                    location(field),
+                    format!(
+                        "Network edge {} service '{}' at index {} does not exist",
+                        field, service, index
+                    ),
+                );
+            }
+        }
+        if !seen.insert((&edge.caller, &edge.callee)) {
+            diagnostics.error(
+                location("callee"),
+                format!(
+                    "Network edge {} -> {} is configured more than once",
+                    edge.caller, edge.callee
+                ),
+            );
+        }
+        for (field, value) in [
+            ("delay_ms", edge.link.delay_ms),
+            ("jitter_ms", edge.link.jitter_ms),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                diagnostics.error(
+                    location(field),
+                    format!(
+                        "Network edge {} -> {} has negative {}: {}",
+                        edge.caller, edge.callee, field, value
+                    ),
+                );
+            }
+        }
+        if !(0.0..=1.0).contains(&edge.link.loss) {
+            diagnostics.error(
+                location("loss"),
+                format!(
+                    "Network edge {} -> {} has loss {}, outside [0, 1]",
+                    edge.caller, edge.callee, edge.link.loss
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ConfigFormat, parse_config_str};
+
+    /// Config with one service whose methods make the given calls; `bounded` methods set
+    /// `max_recursion_depth`
+    fn config(calls: &[(&str, &[&str])], bounded: &[&str]) -> SimulatorConfig {
+        let methods: serde_json::Map<String, serde_json::Value> = calls
+            .iter()
+            .map(|(method, callees)| {
+                let mut config = serde_json::json!({
+                    "calls": callees.iter().map(|callee| vec![format!("svc.{}", callee)]).collect::<Vec<_>>(),
+                    "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+                    "error_rate": null,
+                });
+                if bounded.contains(method) {
+                    config["max_recursion_depth"] = 3.into();
+                }
+                (method.to_string(), config)
+            })
+            .collect();
+        let document = serde_json::json!({
+            "schema_version": 2,
+            "services": {"svc": {"port": 50051, "methods": methods}},
+            "load": null,
+        });
+        parse_config_str(&document.to_string(), ConfigFormat::Json).unwrap()
+    }
+
+    fn cycle_errors(config: &SimulatorConfig) -> Vec<String> {
+        let mut diagnostics = Diagnostics::default();
+        detect_circular_dependencies(config, &mut diagnostics);
+        diagnostics
+            .into_sorted()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn acyclic_calls_pass() {
+        let config = config(&[("a", &["b", "c"]), ("b", &["c"]), ("c", &[])], &[]);
+        assert!(cycle_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn unbounded_cycle_is_rejected() {
+        let config = config(&[("a", &["b"]), ("b", &["a"])], &[]);
+        let errors = cycle_errors(&config);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].contains("svc.a -> svc.b -> svc.a"),
+            "{}",
+            errors[0]
+        );
+    }
+
+    #[test]
+    fn bounded_cycle_passes() {
+        let config = config(&[("a", &["b"]), ("b", &["a"])], &["b"]);
+        assert!(cycle_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn bounded_self_call_passes() {
+        let config = config(&[("a", &["a"])], &["a"]);
+        assert!(cycle_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn bounded_cycle_does_not_hide_unbounded_one() {
+        // k0 -> m -> k1 -> k0 is bounded by m, but k0 -> k1 -> k0 is not
+        let config = config(
+            &[("k0", &["m", "k1"]), ("m", &["k1"]), ("k1", &["k0"])],
+            &["m"],
+        );
+        let errors = cycle_errors(&config);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].contains("svc.k0 -> svc.k1 -> svc.k0"),
+            "{}",
+            errors[0]
         );
     }
 
-    Ok(())
+    /// Errors about service names for a config with one service named `name`
+    fn dependency_errors(name: &str, telemetry: serde_json::Value) -> Vec<String> {
+        let document = serde_json::json!({
+            "schema_version": 2,
+            "services": {name: {"port": 50051, "methods": {"get": {
+                "calls": [],
+                "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+            }}}},
+            "telemetry": telemetry,
+        });
+        let config = parse_config_str(&document.to_string(), ConfigFormat::Json).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        validate_service_dependencies(&config, &mut diagnostics);
+        diagnostics
+            .into_sorted()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn collector_name_is_reserved_with_the_collector() {
+        let collector = serde_json::json!({"collector": true});
+        assert_eq!(dependency_errors("jaeger", collector.clone()).len(), 1);
+        assert!(dependency_errors("jaeger", serde_json::Value::Null).is_empty());
+        assert!(dependency_errors("tracing", collector).is_empty());
+    }
+
+    #[test]
+    fn prometheus_name_is_reserved_with_the_scraper() {
+        let prometheus = serde_json::json!({"prometheus": true});
+        assert_eq!(dependency_errors("prometheus", prometheus.clone()).len(), 1);
+        assert!(dependency_errors("prometheus", serde_json::Value::Null).is_empty());
+        assert!(dependency_errors("jaeger", prometheus).is_empty());
+    }
+
+    #[test]
+    fn partitions_need_other_existing_services() {
+        let method = serde_json::json!({"get": {
+            "calls": [],
+            "latency_distribution": {"type": "constant", "parameters": {"value": 1.0}},
+        }});
+        let document = serde_json::json!({
+            "schema_version": 2,
+            "services": {
+                "a": {"port": 50051, "methods": method},
+                "b": {"port": 50052, "methods": method},
+            },
+            "load": null,
+            "faults": [
+                {"at_seconds": 1, "service": "a", "action": "partition", "peers": ["b"]},
+                {"at_seconds": 1, "service": "a", "action": "partition", "peers": ["a", "c"]},
+                {"at_seconds": 1, "service": "a", "replica": 0, "action": "partition", "peers": []},
+            ],
+        });
+        let config = parse_config_str(&document.to_string(), ConfigFormat::Json).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        validate_faults(&config, &mut diagnostics);
+        let pointers: Vec<String> = diagnostics
+            .into_sorted()
+            .into_iter()
+            .map(|diagnostic| diagnostic.pointer)
+            .collect();
+        assert_eq!(
+            pointers,
+            [
+                "/faults/1/peers/0",
+                "/faults/1/peers/1",
+                "/faults/2/peers",
+                "/faults/2/replica",
+            ]
+        );
+    }
 }
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+
+use super::diagnostics::{Diagnostics, pointer};
+use super::rules::{downstream_calls, split_call};
+use crate::analysis::distribution::normal_cdf;
+use crate::analysis::load::{CapacityStatus, DEFAULT_SATURATION_THRESHOLD, plan_load};
+use crate::parser::{Distribution, SimulatorConfig};
+
+/// Share of a normal latency distribution below zero worth warning about
+const NEGATIVE_MASS_THRESHOLD: f64 = 0.05;
+/// Longest name Docker and DNS labels accept
+const MAX_NAME_LENGTH: usize = 63;
+
+/// Warn about Bernoulli error rates outside [0, 1], which are clamped when simulated
+pub fn warn_error_rates_out_of_range(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (service_name, service) in &config.services {
+        for (method_name, method) in &service.methods {
+            let Some(error_rate) = &method.error_rate else {
+                continue;
+            };
+            let p = error_rate.parameters.get("p").copied();
+            if error_rate.distribution_type == "bernoulli"
+                && p.is_some_and(|p| !(0.0..=1.0).contains(&p))
+            {
+                diagnostics.warning(
+                    pointer(&[
+                        "services",
+                        service_name,
+                        "methods",
+                        method_name,
+                        "error_rate",
+                        "parameters",
+                        "p",
+                    ]),
+                    format!(
+                        "Bernoulli p for {}.{} is {}, outside [0, 1]; it will be clamped",
+                        service_name,
+                        method_name,
+                        p.unwrap_or_default()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Warn about normal latencies that often fall below zero, since those samples become 0 ms
+pub fn warn_negative_latencies(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for (service_name, service) in &config.services {
+        for (method_name, method) in &service.methods {
+            let Some(mass) = negative_mass(&method.latency_distribution) else {
+                continue;
+            };
+            if mass > NEGATIVE_MASS_THRESHOLD {
+                diagnostics.warning(
+                    pointer(&[
+                        "services",
+                        service_name,
+                        "methods",
+                        method_name,
+                        "latency_distribution",
+                        "parameters",
+                        "stddev",
+                    ]),
+                    format!(
+                        "{:.0}% of latency samples for {}.{} fall below zero and are clamped to 0 ms",
+                        mass * 100.0,
+                        service_name,
+                        method_name
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Probability that a normal distribution draws a negative value
+fn negative_mass(distribution: &Distribution) -> Option<f64> {
+    if distribution.distribution_type != "normal" {
+        return None;
+    }
+    let mean = *distribution.parameters.get("mean")?;
+    let stddev = *distribution.parameters.get("stddev")?;
+    (stddev > 0.0).then(|| normal_cdf(-mean / stddev))
+}
+
+/// Warn about methods no entry point ever reaches
+pub fn warn_unreachable_methods(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let Some(load) = &config.load else {
+        return;
+    };
+    let mut reached: HashSet<(&str, &str)> = HashSet::new();
+    let mut pending: Vec<(&str, &str)> = load
+        .entry_points
+        .iter()
+        .map(|entry_point| (entry_point.service.as_str(), entry_point.method.as_str()))
+        .collect();
+    while let Some(node) = pending.pop() {
+        if !reached.insert(node) {
+            continue;
+        }
+        let method = &config.services[node.0].methods[node.1];
+        pending.extend(downstream_calls(method).filter_map(|call| split_call(call)));
+    }
+
+    for (service_name, service) in &config.services {
+        for method_name in service.methods.keys() {
+            if !reached.contains(&(service_name.as_str(), method_name.as_str())) {
+                diagnostics.warning(
+                    pointer(&["services", service_name, "methods", method_name]),
+                    format!(
+                        "Method {}.{} is not reachable from any entry point",
+                        service_name, method_name
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Warn about services listening on the same container port, which collide on a shared host
+pub fn warn_duplicate_ports(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let mut by_port: HashMap<u16, Vec<&String>> = HashMap::new();
+    for (service_name, service) in &config.services {
+        by_port.entry(service.port).or_default().push(service_name);
+    }
+    for (port, mut services) in by_port {
+        if services.len() < 2 {
+            continue;
+        }
+        services.sort();
+        for service_name in &services {
+            diagnostics.warning(
+                pointer(&["services", service_name, "port"]),
+                format!(
+                    "Port {} is used by several services ({}) and would collide if they share a host",
+                    port,
+                    services
+                        .iter()
+                        .map(|name| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+    }
+}
+
+/// Warn about service names Docker or DNS would reject or mangle.
+///
+/// Underscores are accepted: they aren't valid in DNS labels, but Docker's embedded DNS
+/// resolves them and the example configs rely on them.
+pub fn warn_invalid_service_names(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for service_name in config.services.keys() {
+        let valid_characters = service_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let valid_edges = service_name
+            .chars()
+            .next()
+            .zip(service_name.chars().last())
+            .is_some_and(|(first, last)| first.is_ascii_alphanumeric() && last != '-');
+        if !valid_characters || !valid_edges || service_name.len() > MAX_NAME_LENGTH {
+            diagnostics.warning(
+                pointer(&["services", service_name]),
+                format!(
+                    "Service name '{}' is not a valid container or DNS name; use up to {} letters, digits, '-' or '_', starting with a letter or digit",
+                    service_name, MAX_NAME_LENGTH
+                ),
+            );
+        }
+    }
+}
+
+/// Warn about services the entry point rates are predicted to overload.
+///
+/// Only services with a `concurrency` limit can queue, so only they are checked; a service
+/// without one takes every request at once and its latency doesn't grow with load. Retries
+/// that never succeed overload any service.
+pub fn warn_predicted_overload(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    for service in plan_load(config, DEFAULT_SATURATION_THRESHOLD).services {
+        let utilization = service
+            .utilization
+            .map_or("unbounded".to_string(), |u| format!("{:.0}%", u * 100.0));
+        let message = match service.status {
+            CapacityStatus::Saturated => format!(
+                "Service '{}' is predicted to be overloaded at {:.1} requests/s (utilization {})",
+                service.service, service.arrival_rps, utilization
+            ),
+            CapacityStatus::NearCapacity => format!(
+                "Service '{}' is predicted to run near capacity at {:.1} requests/s (utilization {})",
+                service.service, service.arrival_rps, utilization
+            ),
+            CapacityStatus::Ok | CapacityStatus::Unlimited => continue,
+        };
+        diagnostics.warning(pointer(&["services", &service.service]), message);
+    }
+}
+
+/// Warn about SLOs of entry points the load never sends requests to, which cannot pass
+pub fn warn_slos_without_load(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let Some(load) = &config.load else {
+        return;
+    };
+    for (index, slo) in config.slos.iter().enumerate() {
+        let loaded = load.entry_points.iter().any(|entry_point| {
+            format!("{}.{}", entry_point.service, entry_point.method) == slo.entry_point
+        });
+        if !loaded {
+            diagnostics.warning(
+                pointer(&["slos", &index.to_string(), "entry_point"]),
+                format!(
+                    "SLO entry point '{}' receives no load, so the SLO will fail",
+                    slo.entry_point
+                ),
+            );
+        }
+    }
+}
+
+/// Warn about faults starting after the load's configured duration, which never fire
+pub fn warn_faults_after_load(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let Some(duration) = config.load.as_ref().and_then(|load| load.duration_seconds) else {
+        return;
+    };
+    for (index, fault) in config.faults.iter().enumerate() {
+        if fault.at_seconds >= duration as f64 {
+            diagnostics.warning(
+                pointer(&["faults", &index.to_string(), "at_seconds"]),
+                format!(
+                    "Fault on '{}' starts at {}s, after the load ends at {}s, so it never fires",
+                    fault.service, fault.at_seconds, duration
+                ),
+            );
+        }
+    }
+}
+
+/// Warn about network edges between services without direct calls, which have no effect;
+/// async calls go through the broker
+pub fn warn_unused_network_edges(config: &SimulatorConfig, diagnostics: &mut Diagnostics) {
+    let Some(network) = &config.network else {
+        return;
+    };
+    for (index, edge) in network.edges.iter().enumerate() {
+        let Some(caller) = config.services.get(&edge.caller) else {
+            continue;
+        };
+        let calls_callee = caller
+            .methods
+            .values()
+            .flat_map(|method| method.calls.iter().flatten())
+            .filter_map(|call| split_call(call))
+            .any(|(service, _)| service == edge.callee);
+        if !calls_callee {
+            diagnostics.warning(
+                pointer(&["network", "edges", &index.to_string()]),
+                format!(
+                    "'{}' never calls '{}' directly, so this network edge has no effect",
+                    edge.caller, edge.callee
+                ),
+            );
+        }
+    }
+}